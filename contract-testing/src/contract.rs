@@ -0,0 +1,245 @@
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// The expectations one consumer has of one provider.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Contract {
+    pub consumer: String,
+    pub provider: String,
+    pub interactions: Vec<Interaction>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Interaction {
+    pub description: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider_state: Option<String>,
+    pub request: ContractRequest,
+    pub response: ContractResponse,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ContractRequest {
+    pub method: String,
+    pub path: String,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub query: BTreeMap<String, String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ContractResponse {
+    pub status: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<Value>,
+    /// JSON pointers into `body` that only need to match by type rather than
+    /// by value, e.g. timestamps or temperatures.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub match_type: Vec<String>,
+}
+
+impl Contract {
+    pub fn new(consumer: &str, provider: &str) -> Contract {
+        Contract {
+            consumer: consumer.to_string(),
+            provider: provider.to_string(),
+            interactions: vec![],
+        }
+    }
+
+    pub fn interaction(mut self, interaction: Interaction) -> Contract {
+        self.interactions.push(interaction);
+        self
+    }
+
+    pub fn file_name(consumer: &str, provider: &str) -> String {
+        format!("{}-{}.json", consumer, provider)
+    }
+
+    /// Loads the committed contract between `consumer` and `provider`.
+    pub fn load(consumer: &str, provider: &str) -> io::Result<Contract> {
+        Contract::load_from(&contracts_dir().join(Contract::file_name(consumer, provider)))
+    }
+
+    pub fn load_from(path: &Path) -> io::Result<Contract> {
+        let json = fs::read_to_string(path)?;
+        serde_json::from_str(&json).map_err(io::Error::other)
+    }
+
+    /// Records the contract into the shared contracts directory so provider
+    /// test suites can verify it.
+    pub fn write(&self) -> io::Result<PathBuf> {
+        let path = contracts_dir().join(Contract::file_name(&self.consumer, &self.provider));
+        let json = serde_json::to_string_pretty(self).map_err(io::Error::other)?;
+        fs::write(&path, format!("{}\n", json))?;
+        Ok(path)
+    }
+
+    /// Asserts the committed contract is the one the consumer's tests just
+    /// exercised, so a changed expectation fails until it is recorded on
+    /// purpose. Records it first when `UPDATE_CONTRACTS` is set.
+    pub fn assert_matches_committed(&self) {
+        if std::env::var("UPDATE_CONTRACTS").is_ok() {
+            self.write().unwrap();
+        }
+        let file_name = Contract::file_name(&self.consumer, &self.provider);
+        let committed = Contract::load(&self.consumer, &self.provider)
+            .unwrap_or_else(|e| panic!("unable to load {}: {}", file_name, e));
+        assert_eq!(
+            &committed, self,
+            "contracts/{} is stale, regenerate it with UPDATE_CONTRACTS=1 cargo test",
+            file_name
+        );
+    }
+}
+
+impl Interaction {
+    pub fn new(description: &str) -> Interaction {
+        Interaction {
+            description: description.to_string(),
+            provider_state: None,
+            request: ContractRequest {
+                method: String::from("GET"),
+                path: String::from("/"),
+                query: BTreeMap::new(),
+            },
+            response: ContractResponse {
+                status: 200,
+                body: None,
+                match_type: vec![],
+            },
+        }
+    }
+
+    pub fn given(mut self, provider_state: &str) -> Interaction {
+        self.provider_state = Some(provider_state.to_string());
+        self
+    }
+
+    pub fn get(mut self, path: &str) -> Interaction {
+        self.request.method = String::from("GET");
+        self.request.path = path.to_string();
+        self
+    }
+
+    pub fn query(mut self, name: &str, value: &str) -> Interaction {
+        self.request
+            .query
+            .insert(name.to_string(), value.to_string());
+        self
+    }
+
+    pub fn will_respond_with(mut self, status: u16, body: Option<Value>) -> Interaction {
+        self.response.status = status;
+        self.response.body = body;
+        self
+    }
+
+    pub fn match_type(mut self, pointer: &str) -> Interaction {
+        self.response.match_type.push(pointer.to_string());
+        self
+    }
+
+    /// Compares an actual provider response with the expectation, returning a
+    /// description of every difference.
+    pub fn mismatches(&self, status: u16, body: &Value) -> Vec<String> {
+        let mut mismatches = vec![];
+        if status != self.response.status {
+            mismatches.push(format!(
+                "expected status {} but was {}",
+                self.response.status, status
+            ));
+        }
+        if let Some(expected) = &self.response.body {
+            compare(
+                "",
+                expected,
+                body,
+                &self.response.match_type,
+                &mut mismatches,
+            );
+        }
+        mismatches
+    }
+}
+
+/// Expected fields must be present in the actual body; extra fields are
+/// allowed since consumers ignore them.
+fn compare(
+    pointer: &str,
+    expected: &Value,
+    actual: &Value,
+    match_type: &[String],
+    mismatches: &mut Vec<String>,
+) {
+    match (expected, actual) {
+        (Value::Object(expected), Value::Object(actual)) => {
+            for (key, value) in expected {
+                let child = format!("{}/{}", pointer, key);
+                match actual.get(key) {
+                    Some(a) => compare(&child, value, a, match_type, mismatches),
+                    None => mismatches.push(format!("{} is missing", child)),
+                }
+            }
+        }
+        (Value::Array(expected), Value::Array(actual)) if expected.len() == actual.len() => {
+            for (i, (e, a)) in expected.iter().zip(actual).enumerate() {
+                compare(&format!("{}/{}", pointer, i), e, a, match_type, mismatches);
+            }
+        }
+        _ if match_type.iter().any(|p| p == pointer) => {
+            if !same_type(expected, actual) {
+                mismatches.push(format!(
+                    "{} expected a value like {} but was {}",
+                    pointer, expected, actual
+                ));
+            }
+        }
+        _ => {
+            if expected != actual {
+                mismatches.push(format!(
+                    "{} expected {} but was {}",
+                    pointer, expected, actual
+                ));
+            }
+        }
+    }
+}
+
+fn same_type(a: &Value, b: &Value) -> bool {
+    std::mem::discriminant(a) == std::mem::discriminant(b)
+}
+
+/// The workspace level directory holding recorded contracts.
+pub fn contracts_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("../contracts")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn matches_by_type_only_where_asked() {
+        let interaction = Interaction::new("time")
+            .get("/time")
+            .will_respond_with(
+                200,
+                Some(json!({"key_time": "2024-06-01T12:00:00Z", "zone": "UTC"})),
+            )
+            .match_type("/key_time");
+
+        let actual = json!({"key_time": "2025-01-01T00:00:00Z", "zone": "UTC", "extra": 1});
+        assert!(interaction.mismatches(200, &actual).is_empty());
+
+        let actual = json!({"key_time": 1, "zone": "CST"});
+        assert_eq!(2, interaction.mismatches(200, &actual).len());
+        assert_eq!(3, interaction.mismatches(500, &json!({})).len());
+    }
+}
@@ -0,0 +1,7 @@
+pub mod contract;
+pub mod mock;
+pub mod verify;
+
+pub use contract::{Contract, Interaction};
+pub use mock::MockProvider;
+pub use verify::verify;
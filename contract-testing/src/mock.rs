@@ -0,0 +1,98 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+
+use axum::{
+    extract::{Query, State},
+    http::{Method, StatusCode, Uri},
+    response::{IntoResponse, Response},
+    Json, Router,
+};
+use tokio::net::TcpListener;
+
+use crate::contract::{Contract, Interaction};
+
+/// Stands in for the provider while the consumer's client code runs, serving
+/// the responses promised by the contract and remembering which interactions
+/// were exercised.
+pub struct MockProvider {
+    url: String,
+    exercised: Arc<Mutex<Vec<bool>>>,
+    interactions: Vec<Interaction>,
+}
+
+#[derive(Clone)]
+struct MockState {
+    interactions: Arc<Vec<Interaction>>,
+    exercised: Arc<Mutex<Vec<bool>>>,
+}
+
+impl MockProvider {
+    pub async fn start(contract: &Contract) -> MockProvider {
+        let exercised = Arc::new(Mutex::new(vec![false; contract.interactions.len()]));
+        let state = MockState {
+            interactions: Arc::new(contract.interactions.clone()),
+            exercised: exercised.clone(),
+        };
+        let app = Router::new().fallback(respond).with_state(state);
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        MockProvider {
+            url,
+            exercised,
+            interactions: contract.interactions.clone(),
+        }
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Panics naming any interaction the consumer never made.
+    pub fn assert_all_exercised(&self) {
+        let exercised = self.exercised.lock().unwrap();
+        let missing: Vec<&str> = self
+            .interactions
+            .iter()
+            .zip(exercised.iter())
+            .filter(|(_, hit)| !**hit)
+            .map(|(i, _)| i.description.as_str())
+            .collect();
+        assert!(
+            missing.is_empty(),
+            "interactions never exercised: {:?}",
+            missing
+        );
+    }
+}
+
+async fn respond(
+    State(state): State<MockState>,
+    method: Method,
+    uri: Uri,
+    Query(query): Query<BTreeMap<String, String>>,
+) -> Response {
+    let found = state.interactions.iter().position(|i| {
+        i.request.method == method.as_str()
+            && i.request.path == uri.path()
+            && i.request.query == query
+    });
+    let Some(index) = found else {
+        return (
+            StatusCode::NOT_IMPLEMENTED,
+            format!("no interaction for {} {}", method, uri),
+        )
+            .into_response();
+    };
+    state.exercised.lock().unwrap()[index] = true;
+
+    let response = &state.interactions[index].response;
+    let status = StatusCode::from_u16(response.status).unwrap();
+    match &response.body {
+        Some(body) => (status, Json(body.clone())).into_response(),
+        None => status.into_response(),
+    }
+}
@@ -0,0 +1,61 @@
+use std::future::Future;
+
+use axum::{body::Body, http::Request, Router};
+use serde_json::Value;
+use tower::ServiceExt;
+
+use crate::contract::{Contract, Interaction};
+
+/// Replays every interaction against the provider's real router. The
+/// `router_for` callback receives the interaction's provider state so the
+/// test can arrange any dependencies before the request is made.
+pub async fn verify<F, Fut>(contract: &Contract, mut router_for: F) -> Result<(), String>
+where
+    F: FnMut(Option<String>) -> Fut,
+    Fut: Future<Output = Router>,
+{
+    let mut failures = vec![];
+    for interaction in &contract.interactions {
+        let router = router_for(interaction.provider_state.clone()).await;
+        let (status, body) = send(router, interaction).await;
+        for mismatch in interaction.mismatches(status, &body) {
+            failures.push(format!("{}: {}", interaction.description, mismatch));
+        }
+    }
+
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(format!(
+            "{} does not satisfy {}:\n{}",
+            contract.provider,
+            contract.consumer,
+            failures.join("\n")
+        ))
+    }
+}
+
+async fn send(router: Router, interaction: &Interaction) -> (u16, Value) {
+    let mut uri = interaction.request.path.clone();
+    if !interaction.request.query.is_empty() {
+        let query = url::form_urlencoded::Serializer::new(String::new())
+            .extend_pairs(&interaction.request.query)
+            .finish();
+        uri = format!("{}?{}", uri, query);
+    }
+    let request = Request::builder()
+        .method(interaction.request.method.as_str())
+        .uri(uri)
+        .body(Body::empty())
+        .unwrap();
+
+    let response = router.oneshot(request).await.unwrap();
+    let status = response.status().as_u16();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (
+        status,
+        serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+    )
+}
@@ -0,0 +1,132 @@
+//! Runs every service's router in-process on ephemeral ports so tests can
+//! drive the whole call chain through service-b.
+use std::sync::OnceLock;
+
+use axum::Router;
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_sdk::{
+    export::trace::SpanData,
+    testing::trace::InMemorySpanExporter,
+    trace::{Config, Sampler, TracerProvider},
+};
+use tokio::net::TcpListener;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, Registry};
+use weather_stub::{Fixtures, Stub};
+
+pub const WEATHER_API_KEY: &str = "integration";
+
+struct Telemetry {
+    exporter: InMemorySpanExporter,
+    _provider: TracerProvider,
+}
+
+static TELEMETRY: OnceLock<Telemetry> = OnceLock::new();
+
+/// Installs a subscriber exporting every span to memory. The services only
+/// propagate trace context when a telemetry layer is present, so the harness
+/// always installs one.
+fn telemetry() -> &'static Telemetry {
+    TELEMETRY.get_or_init(|| {
+        let exporter = InMemorySpanExporter::default();
+        let provider = TracerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .with_config(Config::default().with_sampler(Sampler::AlwaysOn))
+            .build();
+        let telemetry_layer =
+            tracing_opentelemetry::layer().with_tracer(provider.tracer("integration-tests"));
+        Registry::default().with(telemetry_layer).init();
+        Telemetry {
+            exporter,
+            _provider: provider,
+        }
+    })
+}
+
+/// Every span finished so far by any service in this test binary.
+pub fn finished_spans() -> Vec<SpanData> {
+    telemetry().exporter.get_finished_spans().unwrap()
+}
+
+pub struct Harness {
+    pub service_a: String,
+    pub service_b: String,
+    pub service_c: String,
+    pub service_d: String,
+    pub weather_api: String,
+}
+
+impl Harness {
+    pub async fn start() -> Harness {
+        telemetry();
+        let weather_api = spawn(weather_api()).await;
+        let service_a = spawn(service_a::app(service_a::AppState { has_apm: true })).await;
+        let service_c = spawn(service_c::app(service_c::AppState {
+            has_apm: true,
+            streams: service_c::Streams::new(service_c::StreamConfig::default()),
+        }))
+        .await;
+        let service_d = spawn(service_d::app(service_d_state(&weather_api))).await;
+        let service_b = spawn(service_b::app(service_b::AppState {
+            service_a: http_upstream(&service_a),
+            service_c: http_upstream(&service_c),
+            service_d: http_upstream(&service_d),
+        }))
+        .await;
+
+        Harness {
+            service_a,
+            service_b,
+            service_c,
+            service_d,
+            weather_api,
+        }
+    }
+
+    /// The state service-b runs with, for tests that swap out an upstream.
+    pub fn service_b_state(&self) -> service_b::AppState {
+        service_b::AppState {
+            service_a: http_upstream(&self.service_a),
+            service_c: http_upstream(&self.service_c),
+            service_d: http_upstream(&self.service_d),
+        }
+    }
+}
+
+pub fn http_upstream(base_url: &str) -> service_b::Upstream {
+    service_b::Upstream::http(reqwest::Client::new(), base_url)
+}
+
+pub fn service_d_state(weather_api: &str) -> service_d::AppState {
+    service_d::AppState {
+        has_apm: true,
+        http_client: reqwest::Client::new(),
+        weather_api_url: weather_api.to_string(),
+        weather_api_key: String::from(WEATHER_API_KEY),
+        cache: None,
+        batch: service_d::BatchConfig::default(),
+        alerts: service_d::Alerts::new(service_d::AlertsConfig::default()),
+        history: None,
+    }
+}
+
+/// Serves `router` on an ephemeral port and returns its base url.
+pub async fn spawn(router: Router) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+    url
+}
+
+/// A base url nothing is listening on.
+pub async fn unreachable() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    format!("http://{}", listener.local_addr().unwrap())
+}
+
+/// The bundled weather stub, accepting only [`WEATHER_API_KEY`].
+pub fn weather_api() -> Router {
+    weather_stub::app(Stub::new(
+        Fixtures::bundled(),
+        Some(String::from(WEATHER_API_KEY)),
+    ))
+}
@@ -0,0 +1,106 @@
+use std::time::Duration;
+
+use axum::{body::Bytes, http::HeaderMap, routing::post, Router};
+use integration_tests::{service_d_state, spawn, Harness};
+use reqwest::StatusCode;
+use serde_json::json;
+use service_models::v1::service_d::{AlertNotification, AlertSubscription};
+use tokio::sync::mpsc;
+
+/// Serves a webhook that passes on each delivery once its signature checks
+/// out against `secret`.
+async fn webhook(secret: &'static str) -> (String, mpsc::UnboundedReceiver<AlertNotification>) {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    let router = Router::new().route(
+        "/hook",
+        post(move |headers: HeaderMap, body: Bytes| async move {
+            let signature = headers[service_d::SIGNATURE_HEADER].to_str().unwrap();
+            if signature != service_d::signature(secret, &body) {
+                return StatusCode::UNAUTHORIZED;
+            }
+            sender.send(serde_json::from_slice(&body).unwrap()).unwrap();
+            StatusCode::NO_CONTENT
+        }),
+    );
+    (format!("{}/hook", spawn(router).await), receiver)
+}
+
+async fn next(receiver: &mut mpsc::UnboundedReceiver<AlertNotification>) -> AlertNotification {
+    tokio::time::timeout(Duration::from_secs(5), receiver.recv())
+        .await
+        .expect("no delivery within 5s")
+        .unwrap()
+}
+
+#[tokio::test]
+async fn pushes_each_new_alert_to_subscribers_once() {
+    let harness = Harness::start().await;
+    let service_d = spawn(service_d::app(service_d::AppState {
+        alerts: service_d::Alerts::new(service_d::AlertsConfig {
+            poll_interval: Duration::from_millis(50),
+            delivery_attempts: 3,
+            retry_backoff: Duration::from_millis(10),
+            // The webhook below listens on loopback.
+            allow_private_webhooks: true,
+            ..Default::default()
+        }),
+        ..service_d_state(&harness.weather_api)
+    }))
+    .await;
+    let (webhook_url, mut deliveries) = webhook("s3cret").await;
+
+    let client = reqwest::Client::new();
+    let response = client
+        .post(format!("{}/alerts/subscriptions", service_d))
+        .json(&json!({"location": "Miami, FL", "webhook_url": webhook_url, "secret": "s3cret"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::CREATED, response.status());
+    let subscription: AlertSubscription = response.json().await.unwrap();
+
+    let first = next(&mut deliveries).await;
+    assert_eq!(subscription.id, first.subscription_id);
+    assert_eq!("Miami", first.city);
+    assert_eq!(1, first.alerts.len());
+    assert_eq!("Tropical Storm Warning", first.alerts[0].event);
+
+    let tornado = json!({
+        "headline": "Tornado Warning issued June 1 at 2:00PM EDT by NWS Miami FL",
+        "severity": "Extreme",
+        "areas": "Miami-Dade",
+        "event": "Tornado Warning",
+        "effective": "2024-06-01T14:00:00-04:00",
+        "expires": "2024-06-01T14:45:00-04:00",
+        "desc": "A tornado has been observed.",
+        "instruction": "Take cover now."
+    });
+    let current = reqwest::get(format!(
+        "{}/alerts.json?q=33101&key=integration",
+        harness.weather_api
+    ))
+    .await
+    .unwrap()
+    .json::<serde_json::Value>()
+    .await
+    .unwrap();
+    let mut alerts = current["alerts"]["alert"].as_array().unwrap().clone();
+    alerts.push(tornado);
+    let response = client
+        .put(format!("{}/_stub/alerts/33101", harness.weather_api))
+        .json(&alerts)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::NO_CONTENT, response.status());
+
+    let second = next(&mut deliveries).await;
+    assert_eq!(1, second.alerts.len());
+    assert_eq!("Tornado Warning", second.alerts[0].event);
+    assert!(
+        tokio::time::timeout(Duration::from_millis(300), deliveries.recv())
+            .await
+            .is_err(),
+        "alerts already delivered were sent again"
+    );
+}
@@ -0,0 +1,165 @@
+use integration_tests::{http_upstream, service_d_state, spawn, unreachable, Harness};
+use reqwest::StatusCode;
+use service_models::v1::{
+    service_b::ExternalModel,
+    service_d::{WeatherBatchRequest, WeatherBatchResponse},
+    HealthCheck,
+};
+
+#[tokio::test]
+async fn aggregates_every_upstream() {
+    let harness = Harness::start().await;
+
+    let response = reqwest::get(format!("{}/?name=Ben&zip=76262", harness.service_b))
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::OK, response.status());
+
+    let model: ExternalModel = response.json().await.unwrap();
+    assert_eq!("(Ben)Field 1", model.key_one);
+    assert_eq!("(Ben)Field 2", model.key_two);
+    assert!((now_secs() - model.key_time.timestamp()).abs() < 60);
+    assert_eq!("Roanoke", model.weather.city);
+    assert_eq!("Texas", model.weather.state);
+    assert_eq!(20.0, model.weather.celcius);
+    assert_eq!(68.0, model.weather.farenheight);
+}
+
+#[tokio::test]
+async fn includes_the_forecast_when_asked() {
+    let harness = Harness::start().await;
+
+    let response = reqwest::get(format!("{}/?name=Ben&zip=98101&days=2", harness.service_b))
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::OK, response.status());
+
+    let model: ExternalModel = response.json().await.unwrap();
+    let forecast = model.forecast.expect("asked for a forecast");
+    assert_eq!("Seattle", forecast.city);
+    assert_eq!(2, forecast.days.len());
+    assert_eq!(90, forecast.days[0].precipitation_chance);
+    assert_eq!("Light rain", forecast.days[0].condition);
+}
+
+#[tokio::test]
+async fn accepts_a_city_and_state() {
+    let harness = Harness::start().await;
+
+    let response = reqwest::get(format!("{}/?name=Ben&zip=seattle,%20wa", harness.service_b))
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::OK, response.status());
+
+    let model: ExternalModel = response.json().await.unwrap();
+    assert_eq!("Seattle", model.weather.city);
+}
+
+#[tokio::test]
+async fn looks_up_weather_in_batches() {
+    let harness = Harness::start().await;
+    let request = WeatherBatchRequest {
+        locations: ["76262", "seattle, wa", "00000", "Unknown"]
+            .map(String::from)
+            .to_vec(),
+    };
+
+    let response = reqwest::Client::new()
+        .post(format!("{}/weather/batch", harness.service_d))
+        .json(&request)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::OK, response.status());
+
+    let batch: WeatherBatchResponse = response.json().await.unwrap();
+    let statuses: Vec<u16> = batch.results.iter().map(|r| r.status).collect();
+    assert_eq!(vec![200, 200, 400, 422], statuses);
+    assert_eq!("Roanoke", batch.results[0].weather.as_ref().unwrap().city);
+    assert_eq!("Seattle", batch.results[1].weather.as_ref().unwrap().city);
+}
+
+#[tokio::test]
+async fn every_service_reports_healthy() {
+    let harness = Harness::start().await;
+
+    for url in [
+        format!("{}/health", harness.service_a),
+        format!("{}/health", harness.service_b),
+        format!("{}/", harness.service_c),
+        format!("{}/health", harness.service_d),
+    ] {
+        let health: HealthCheck = reqwest::get(&url).await.unwrap().json().await.unwrap();
+        assert_eq!("Healthy", health.status, "{}", url);
+    }
+}
+
+#[tokio::test]
+async fn unknown_location_is_a_bad_request() {
+    let harness = Harness::start().await;
+
+    let response = reqwest::get(format!("{}/?name=Ben&zip=00000", harness.service_b))
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+}
+
+#[tokio::test]
+async fn unreachable_upstream_is_an_internal_error() {
+    let harness = Harness::start().await;
+    let service_b = spawn(service_b::app(service_b::AppState {
+        service_a: http_upstream(&unreachable().await),
+        ..harness.service_b_state()
+    }))
+    .await;
+
+    let response = reqwest::get(format!("{}/?name=Ben&zip=76262", service_b))
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::INTERNAL_SERVER_ERROR, response.status());
+}
+
+#[tokio::test]
+async fn weather_provider_outage_is_a_bad_request() {
+    let harness = Harness::start().await;
+    let service_d = spawn(service_d::app(service_d_state(&unreachable().await))).await;
+    let service_b = spawn(service_b::app(service_b::AppState {
+        service_d: http_upstream(&service_d),
+        ..harness.service_b_state()
+    }))
+    .await;
+
+    let response = reqwest::get(format!("{}/weather?zip=76262", service_d))
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::INTERNAL_SERVER_ERROR, response.status());
+
+    let response = reqwest::get(format!("{}/?name=Ben&zip=76262", service_b))
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+}
+
+#[tokio::test]
+async fn malformed_weather_is_a_bad_request() {
+    let harness = Harness::start().await;
+    let fault = reqwest::Client::new()
+        .put(format!("{}/_stub/fault", harness.weather_api))
+        .json(&serde_json::json!({"malformed": true}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::NO_CONTENT, fault.status());
+
+    let response = reqwest::get(format!("{}/weather?zip=76262", harness.service_d))
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+}
+
+fn now_secs() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64
+}
@@ -0,0 +1,70 @@
+use integration_tests::{finished_spans, http_upstream, spawn, Harness};
+use reqwest::StatusCode;
+use service_common::fault::{self, Fault, FaultInjector, FaultRule};
+
+fn faulty_service_a(fault: Fault) -> axum::Router {
+    fault::inject(
+        service_a::app(service_a::AppState { has_apm: true }),
+        FaultInjector::new(vec![FaultRule {
+            routes: vec![String::from("/route")],
+            percentage: 100.0,
+            fault,
+        }]),
+    )
+}
+
+#[tokio::test]
+async fn injected_faults_show_up_in_the_trace() {
+    let harness = Harness::start().await;
+    let service_a = spawn(faulty_service_a(Fault::Latency { ms: 10 })).await;
+    let service_b = spawn(service_b::app(service_b::AppState {
+        service_a: http_upstream(&service_a),
+        ..harness.service_b_state()
+    }))
+    .await;
+
+    let response = reqwest::get(format!("{}/?name=fault-check&zip=76262", service_b))
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::OK, response.status());
+
+    let spans = finished_spans();
+    let root = spans
+        .iter()
+        .find(|s| {
+            s.name == "GET /"
+                && s.attributes
+                    .iter()
+                    .any(|kv| kv.value.as_str().contains("fault-check"))
+        })
+        .expect("service-b did not record its request span");
+    let client = spans
+        .iter()
+        .find(|s| s.name == "http-service-a" && s.parent_span_id == root.span_context.span_id())
+        .expect("no call to service-a");
+    let injected = spans
+        .iter()
+        .find(|s| s.name == "fault-injection" && s.parent_span_id == client.span_context.span_id())
+        .expect("the fault was not recorded under the call to service-a");
+    assert!(injected
+        .attributes
+        .iter()
+        .any(|kv| kv.key.as_str() == "fault" && kv.value.as_str() == "latency"));
+    assert_eq!(1, injected.events.len());
+}
+
+#[tokio::test]
+async fn aborted_upstream_is_a_bad_request() {
+    let harness = Harness::start().await;
+    let service_a = spawn(faulty_service_a(Fault::Abort { status: 503 })).await;
+    let service_b = spawn(service_b::app(service_b::AppState {
+        service_a: http_upstream(&service_a),
+        ..harness.service_b_state()
+    }))
+    .await;
+
+    let response = reqwest::get(format!("{}/?name=Ben&zip=76262", service_b))
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+}
@@ -0,0 +1,88 @@
+use integration_tests::{finished_spans, spawn, Harness, WEATHER_API_KEY};
+use monolith::{Config, Mode, Services, UpstreamUrls};
+use opentelemetry::trace::SpanId;
+use opentelemetry_sdk::export::trace::SpanData;
+use reqwest::StatusCode;
+use service_common::tls::ClientTlsConfig;
+use service_models::v1::service_b::ExternalModel;
+
+fn find<'a>(spans: &'a [SpanData], name: &str) -> &'a SpanData {
+    spans
+        .iter()
+        .find(|s| s.name == name)
+        .unwrap_or_else(|| panic!("no span named {}", name))
+}
+
+#[tokio::test]
+async fn in_process_calls_keep_the_span_topology() {
+    let harness = Harness::start().await;
+    let config = Config {
+        mode: Mode::Prefix {
+            bind_address: String::from("127.0.0.1:0"),
+        },
+        in_process: true,
+        tracing_enabled: true,
+        agent_address: None,
+        tls: None,
+        faults: None,
+        upstream_tls: ClientTlsConfig::default(),
+        weather_api_url: harness.weather_api.clone(),
+        weather_api_key: String::from(WEATHER_API_KEY),
+        weather_cache: None,
+        weather_batch: service_d::BatchConfig::default(),
+        weather_alerts: service_d::AlertsConfig::default(),
+        weather_history: None,
+        time_streams: service_c::StreamConfig::default(),
+    };
+    // Nothing listens on these, so every upstream call has to stay in process.
+    let urls = UpstreamUrls {
+        service_a: integration_tests::unreachable().await,
+        service_c: integration_tests::unreachable().await,
+        service_d: integration_tests::unreachable().await,
+    };
+    let monolith = spawn(Services::build(&config, urls).nested()).await;
+
+    let response = reqwest::get(format!(
+        "{}/service-b?name=monolith-check&zip=76262",
+        monolith
+    ))
+    .await
+    .unwrap();
+    assert_eq!(StatusCode::OK, response.status());
+    let model: ExternalModel = response.json().await.unwrap();
+    assert_eq!("(monolith-check)Field 1", model.key_one);
+    assert_eq!("Roanoke", model.weather.city);
+
+    let spans = finished_spans();
+    let root = spans
+        .iter()
+        .find(|s| {
+            s.name == "GET /"
+                && s.attributes
+                    .iter()
+                    .any(|kv| kv.value.as_str().contains("monolith-check"))
+        })
+        .expect("service-b did not record its request span");
+    assert_eq!(SpanId::INVALID, root.parent_span_id);
+
+    let trace: Vec<SpanData> = spans
+        .iter()
+        .filter(|s| s.span_context.trace_id() == root.span_context.trace_id())
+        .cloned()
+        .collect();
+    for (client, server) in [
+        ("http-service-a", "GET /route"),
+        ("http-service-c", "GET /time"),
+        ("http-service-d", "GET /weather"),
+    ] {
+        let client_span = find(&trace, client);
+        assert_eq!(root.span_context.span_id(), client_span.parent_span_id);
+        assert_eq!(
+            client_span.span_context.span_id(),
+            find(&trace, server).parent_span_id,
+            "{} should continue the trace from {}",
+            server,
+            client
+        );
+    }
+}
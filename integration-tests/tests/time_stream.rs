@@ -0,0 +1,46 @@
+use std::time::Duration;
+
+use integration_tests::{finished_spans, Harness};
+use opentelemetry::Value;
+use reqwest::StatusCode;
+
+#[tokio::test]
+async fn records_the_ticks_of_each_stream_when_it_closes() {
+    let harness = Harness::start().await;
+    let mut response = reqwest::get(format!(
+        "{}/time/stream?interval_ms=100&tz=Europe/Paris",
+        harness.service_c
+    ))
+    .await
+    .unwrap();
+    assert_eq!(StatusCode::OK, response.status());
+
+    let mut text = String::new();
+    while text.matches("event: tick").count() < 3 {
+        let chunk = response.chunk().await.unwrap().unwrap();
+        text.push_str(std::str::from_utf8(&chunk).unwrap());
+    }
+    drop(response);
+
+    let ticks = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            let span = finished_spans().into_iter().find(|s| {
+                s.name == "time-stream"
+                    && s.attributes
+                        .iter()
+                        .any(|kv| kv.value.as_str() == "Europe/Paris")
+            });
+            if let Some(span) = span {
+                let ticks = span.attributes.iter().find(|kv| kv.key.as_str() == "ticks");
+                break ticks.map(|kv| kv.value.clone());
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    })
+    .await
+    .expect("the stream's span did not close");
+    match ticks {
+        Some(Value::I64(ticks)) => assert!(ticks >= 3, "{} ticks", ticks),
+        other => panic!("ticks recorded as {:?}", other),
+    }
+}
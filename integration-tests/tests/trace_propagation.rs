@@ -0,0 +1,126 @@
+use integration_tests::{finished_spans, Harness};
+use opentelemetry::trace::SpanId;
+use opentelemetry_sdk::export::trace::SpanData;
+use reqwest::StatusCode;
+
+fn find<'a>(spans: &'a [SpanData], name: &str) -> &'a SpanData {
+    spans
+        .iter()
+        .find(|s| s.name == name)
+        .unwrap_or_else(|| panic!("no span named {}", name))
+}
+
+#[tokio::test]
+async fn one_trace_spans_every_hop() {
+    let harness = Harness::start().await;
+
+    let response = reqwest::get(format!("{}/?name=trace-check&zip=76262", harness.service_b))
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::OK, response.status());
+
+    let spans = finished_spans();
+    let root = spans
+        .iter()
+        .find(|s| {
+            s.name == "GET /"
+                && s.attributes
+                    .iter()
+                    .any(|kv| kv.value.as_str().contains("trace-check"))
+        })
+        .expect("service-b did not record its request span");
+    assert_eq!(SpanId::INVALID, root.parent_span_id);
+
+    let trace: Vec<SpanData> = spans
+        .iter()
+        .filter(|s| s.span_context.trace_id() == root.span_context.trace_id())
+        .cloned()
+        .collect();
+    for (client, server) in [
+        ("http-service-a", "GET /route"),
+        ("http-service-c", "GET /time"),
+        ("http-service-d", "GET /weather"),
+    ] {
+        let client_span = find(&trace, client);
+        assert_eq!(
+            root.span_context.span_id(),
+            client_span.parent_span_id,
+            "{} should be a child of service-b's request span",
+            client
+        );
+        assert_eq!(
+            client_span.span_context.span_id(),
+            find(&trace, server).parent_span_id,
+            "{} should continue the trace from {}",
+            server,
+            client
+        );
+    }
+}
+
+#[tokio::test]
+async fn service_b_continues_a_callers_trace() {
+    let harness = Harness::start().await;
+    let trace_id = "4bf92f3577b34da6a3ce929d0e0e4736";
+
+    let response = reqwest::Client::new()
+        .get(format!(
+            "{}/?name=caller-check&zip=76262",
+            harness.service_b
+        ))
+        .header(
+            "traceparent",
+            format!("00-{}-00f067aa0ba902b7-01", trace_id),
+        )
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::OK, response.status());
+
+    let root = finished_spans()
+        .into_iter()
+        .find(|s| {
+            s.name == "GET /"
+                && s.attributes
+                    .iter()
+                    .any(|kv| kv.value.as_str().contains("caller-check"))
+        })
+        .expect("service-b did not record its request span");
+    assert_eq!(trace_id, root.span_context.trace_id().to_string());
+    assert_eq!("00f067aa0ba902b7", root.parent_span_id.to_string());
+}
+
+#[tokio::test]
+async fn the_forecast_hop_joins_the_trace() {
+    let harness = Harness::start().await;
+
+    let response = reqwest::get(format!(
+        "{}/?name=forecast-check&zip=76262&days=2",
+        harness.service_b
+    ))
+    .await
+    .unwrap();
+    assert_eq!(StatusCode::OK, response.status());
+
+    let spans = finished_spans();
+    let root = spans
+        .iter()
+        .find(|s| {
+            s.name == "GET /"
+                && s.attributes
+                    .iter()
+                    .any(|kv| kv.value.as_str().contains("forecast-check"))
+        })
+        .expect("service-b did not record its request span");
+    let trace: Vec<SpanData> = spans
+        .iter()
+        .filter(|s| s.span_context.trace_id() == root.span_context.trace_id())
+        .cloned()
+        .collect();
+    let client_span = find(&trace, "http-service-d-forecast");
+    assert_eq!(root.span_context.span_id(), client_span.parent_span_id);
+    assert_eq!(
+        client_span.span_context.span_id(),
+        find(&trace, "GET /forecast").parent_span_id
+    );
+}
@@ -0,0 +1,372 @@
+//! Replays JSONL request definitions against a service and reports latency
+//! percentiles, error rates and the trace ids worth looking at.
+use std::{
+    collections::BTreeMap,
+    fmt, io,
+    path::Path,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use reqwest::{Client, Method};
+use serde::Deserialize;
+use tokio::task::JoinSet;
+
+/// One line of a requests file.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct RequestDef {
+    #[serde(default = "default_method")]
+    pub method: String,
+    #[serde(default = "default_path")]
+    pub path: String,
+    #[serde(default)]
+    pub query: BTreeMap<String, String>,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+}
+
+fn default_method() -> String {
+    String::from("GET")
+}
+
+fn default_path() -> String {
+    String::from("/")
+}
+
+/// Parses one definition per non-blank line.
+pub fn parse_requests(contents: &str) -> Result<Vec<RequestDef>, String> {
+    let mut requests = vec![];
+    for (n, line) in contents.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let request: RequestDef =
+            serde_json::from_str(line).map_err(|e| format!("line {}: {}", n + 1, e))?;
+        Method::from_bytes(request.method.as_bytes())
+            .map_err(|e| format!("line {}: {}", n + 1, e))?;
+        requests.push(request);
+    }
+    if requests.is_empty() {
+        return Err(String::from("no requests defined"));
+    }
+    Ok(requests)
+}
+
+pub fn load_requests(path: &Path) -> io::Result<Vec<RequestDef>> {
+    let contents = std::fs::read_to_string(path)?;
+    parse_requests(&contents).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Pace {
+    /// Starts this many requests a second regardless of how long they take.
+    Rate(f64),
+    /// Keeps this many requests in flight.
+    Concurrency(usize),
+}
+
+#[derive(Clone, Debug)]
+pub struct Plan {
+    pub pace: Pace,
+    pub duration: Duration,
+    pub max_requests: Option<usize>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Sample {
+    pub latency: Duration,
+    /// `None` when no response came back.
+    pub status: Option<u16>,
+    pub trace_id: String,
+}
+
+impl Sample {
+    pub fn failed(&self) -> bool {
+        !matches!(self.status, Some(status) if (200..300).contains(&status))
+    }
+}
+
+/// Sends the definitions round robin until the plan's duration or request
+/// limit runs out. Every request starts its own trace.
+pub async fn run(client: Client, base_url: &str, requests: Vec<RequestDef>, plan: &Plan) -> Report {
+    let started = Instant::now();
+    let deadline = started + plan.duration;
+    let target = Arc::new(Target {
+        client,
+        base_url: base_url.trim_end_matches('/').to_string(),
+        requests,
+    });
+    let issued = Arc::new(AtomicUsize::new(0));
+    let max_requests = plan.max_requests.unwrap_or(usize::MAX);
+
+    let mut tasks = JoinSet::new();
+    match plan.pace {
+        Pace::Concurrency(workers) => {
+            for _ in 0..workers.max(1) {
+                let target = target.clone();
+                let issued = issued.clone();
+                tasks.spawn(async move {
+                    let mut samples = vec![];
+                    loop {
+                        let n = issued.fetch_add(1, Ordering::Relaxed);
+                        if n >= max_requests || Instant::now() >= deadline {
+                            return samples;
+                        }
+                        samples.push(target.send(n).await);
+                    }
+                });
+            }
+        }
+        Pace::Rate(rps) => {
+            let mut ticks = tokio::time::interval(Duration::from_secs_f64(1.0 / rps));
+            loop {
+                ticks.tick().await;
+                let n = issued.fetch_add(1, Ordering::Relaxed);
+                if n >= max_requests || Instant::now() >= deadline {
+                    break;
+                }
+                let target = target.clone();
+                tasks.spawn(async move { vec![target.send(n).await] });
+            }
+        }
+    }
+
+    let mut samples = vec![];
+    while let Some(batch) = tasks.join_next().await {
+        samples.extend(batch.expect("request task panicked"));
+    }
+    Report {
+        samples,
+        elapsed: started.elapsed(),
+    }
+}
+
+struct Target {
+    client: Client,
+    base_url: String,
+    requests: Vec<RequestDef>,
+}
+
+impl Target {
+    async fn send(&self, n: usize) -> Sample {
+        let def = &self.requests[n % self.requests.len()];
+        let trace_id = format!("{:032x}", rand::random::<u128>() | 1);
+        let traceparent = format!("00-{}-{:016x}-01", trace_id, rand::random::<u64>() | 1);
+
+        let method = Method::from_bytes(def.method.as_bytes()).unwrap_or(Method::GET);
+        let mut request = self
+            .client
+            .request(method, format!("{}{}", self.base_url, def.path))
+            .query(&def.query)
+            .header("traceparent", traceparent);
+        for (name, value) in &def.headers {
+            request = request.header(name, value);
+        }
+
+        let started = Instant::now();
+        let status = match request.send().await {
+            // Read the body so the latency covers the whole response.
+            Ok(response) => {
+                let status = response.status().as_u16();
+                response.bytes().await.ok().map(|_| status)
+            }
+            Err(_) => None,
+        };
+        Sample {
+            latency: started.elapsed(),
+            status,
+            trace_id,
+        }
+    }
+}
+
+pub struct Report {
+    pub samples: Vec<Sample>,
+    pub elapsed: Duration,
+}
+
+impl Report {
+    pub fn errors(&self) -> usize {
+        self.samples.iter().filter(|s| s.failed()).count()
+    }
+
+    pub fn error_rate(&self) -> f64 {
+        if self.samples.is_empty() {
+            return 0.0;
+        }
+        self.errors() as f64 / self.samples.len() as f64
+    }
+
+    /// Nearest-rank percentile of the request latencies.
+    pub fn percentile(&self, p: f64) -> Duration {
+        let mut latencies: Vec<Duration> = self.samples.iter().map(|s| s.latency).collect();
+        if latencies.is_empty() {
+            return Duration::ZERO;
+        }
+        latencies.sort();
+        let rank = ((p / 100.0) * latencies.len() as f64).ceil() as usize;
+        latencies[rank.clamp(1, latencies.len()) - 1]
+    }
+
+    pub fn slowest(&self, n: usize) -> Vec<&Sample> {
+        let mut samples: Vec<&Sample> = self.samples.iter().collect();
+        samples.sort_by_key(|s| std::cmp::Reverse(s.latency));
+        samples.truncate(n);
+        samples
+    }
+
+    pub fn failures(&self, n: usize) -> Vec<&Sample> {
+        self.samples.iter().filter(|s| s.failed()).take(n).collect()
+    }
+}
+
+fn status(sample: &Sample) -> String {
+    sample
+        .status
+        .map(|s| s.to_string())
+        .unwrap_or_else(|| String::from("error"))
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut statuses: BTreeMap<String, usize> = BTreeMap::new();
+        for sample in &self.samples {
+            *statuses.entry(status(sample)).or_default() += 1;
+        }
+        let statuses: Vec<String> = statuses
+            .iter()
+            .map(|(status, count)| format!("{}={}", status, count))
+            .collect();
+
+        writeln!(
+            f,
+            "requests  {} in {:.1}s ({:.1}/s)",
+            self.samples.len(),
+            self.elapsed.as_secs_f64(),
+            self.samples.len() as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+        )?;
+        writeln!(
+            f,
+            "errors    {} ({:.2}%)",
+            self.errors(),
+            self.error_rate() * 100.0
+        )?;
+        writeln!(f, "status    {}", statuses.join(" "))?;
+        writeln!(
+            f,
+            "latency   p50={:?} p90={:?} p99={:?}",
+            self.percentile(50.0),
+            self.percentile(90.0),
+            self.percentile(99.0)
+        )?;
+        for sample in self.slowest(5) {
+            writeln!(
+                f,
+                "slowest   {} {:?} {}",
+                sample.trace_id,
+                sample.latency,
+                status(sample)
+            )?;
+        }
+        for sample in self.failures(5) {
+            writeln!(f, "failed    {} {}", sample.trace_id, status(sample))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{http::HeaderMap, http::StatusCode, routing::get, Router};
+    use std::sync::Mutex;
+
+    fn sample(ms: u64, status: Option<u16>) -> Sample {
+        Sample {
+            latency: Duration::from_millis(ms),
+            status,
+            trace_id: format!("{:032x}", ms),
+        }
+    }
+
+    #[test]
+    fn parses_request_files() {
+        let requests = parse_requests(
+            r#"{"path": "/", "query": {"name": "Ben", "zip": "76262"}}
+
+{"method": "POST", "path": "/health", "headers": {"x-test": "1"}}
+"#,
+        )
+        .unwrap();
+        assert_eq!(2, requests.len());
+        assert_eq!("GET", requests[0].method);
+        assert_eq!(Some(&String::from("76262")), requests[0].query.get("zip"));
+        assert_eq!("POST", requests[1].method);
+        assert_eq!(Some(&String::from("1")), requests[1].headers.get("x-test"));
+
+        assert_eq!(
+            Err(String::from("no requests defined")),
+            parse_requests("\n\n")
+        );
+        assert!(parse_requests("{\"path\": 1}")
+            .unwrap_err()
+            .starts_with("line 1"));
+    }
+
+    #[test]
+    fn summarises_samples() {
+        let mut samples: Vec<Sample> = (1..=100).map(|ms| sample(ms, Some(200))).collect();
+        samples[10].status = Some(400);
+        samples[20].status = None;
+        let report = Report {
+            samples,
+            elapsed: Duration::from_secs(1),
+        };
+
+        assert_eq!(Duration::from_millis(50), report.percentile(50.0));
+        assert_eq!(Duration::from_millis(90), report.percentile(90.0));
+        assert_eq!(Duration::from_millis(99), report.percentile(99.0));
+        assert_eq!(2, report.errors());
+        assert_eq!(0.02, report.error_rate());
+        assert_eq!(Duration::from_millis(100), report.slowest(1)[0].latency);
+        assert_eq!(2, report.failures(5).len());
+    }
+
+    #[tokio::test]
+    async fn replays_requests_with_their_own_traces() {
+        let seen = Arc::new(Mutex::new(vec![]));
+        let recorded = seen.clone();
+        let app = Router::new()
+            .route(
+                "/",
+                get(move |headers: HeaderMap| async move {
+                    let traceparent = headers["traceparent"].to_str().unwrap().to_string();
+                    recorded.lock().unwrap().push(traceparent);
+                    "ok"
+                }),
+            )
+            .route("/broken", get(|| async { StatusCode::BAD_REQUEST }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let requests = parse_requests("{\"path\": \"/\"}\n{\"path\": \"/broken\"}").unwrap();
+        let plan = Plan {
+            pace: Pace::Concurrency(4),
+            duration: Duration::from_secs(10),
+            max_requests: Some(20),
+        };
+        let report = run(Client::new(), &url, requests, &plan).await;
+
+        assert_eq!(20, report.samples.len());
+        assert_eq!(10, report.errors());
+        let seen = seen.lock().unwrap();
+        assert_eq!(10, seen.len());
+        for sample in report.samples.iter().filter(|s| !s.failed()) {
+            assert!(seen.iter().any(|t| t.contains(&sample.trace_id)));
+        }
+    }
+}
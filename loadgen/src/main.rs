@@ -0,0 +1,62 @@
+use std::{path::PathBuf, time::Duration};
+
+use loadgen::{load_requests, run, Pace, Plan};
+
+#[tokio::main]
+async fn main() {
+    let target_url = std::env::var("LOADGEN_TARGET_URL")
+        .unwrap_or_else(|_| String::from("http://localhost:3000"));
+    let requests_path = std::env::var("LOADGEN_REQUESTS_PATH")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("requests.jsonl"));
+    let pace = match (
+        std::env::var("LOADGEN_RPS"),
+        std::env::var("LOADGEN_CONCURRENCY"),
+    ) {
+        (Ok(rps), _) => {
+            let rps: f64 = rps.parse().expect("LOADGEN_RPS must be a number");
+            // Requests are 1 / rps seconds apart, which must be a finite,
+            // non-zero duration.
+            let finite = rps > 0.0 && rps.is_finite();
+            match Duration::try_from_secs_f64(1.0 / rps) {
+                Ok(period) if finite && !period.is_zero() => {}
+                _ => panic!(
+                    "LOADGEN_RPS must be a positive number in range, not {}",
+                    rps
+                ),
+            }
+            Pace::Rate(rps)
+        }
+        (_, Ok(concurrency)) => Pace::Concurrency(
+            concurrency
+                .parse()
+                .expect("LOADGEN_CONCURRENCY must be a number"),
+        ),
+        _ => Pace::Concurrency(10),
+    };
+    let duration = std::env::var("LOADGEN_DURATION_SECS")
+        .map(|d| d.parse().expect("LOADGEN_DURATION_SECS must be a number"))
+        .unwrap_or(30);
+    let max_requests = std::env::var("LOADGEN_MAX_REQUESTS")
+        .ok()
+        .map(|m| m.parse().expect("LOADGEN_MAX_REQUESTS must be a number"));
+
+    let requests = load_requests(&requests_path)
+        .unwrap_or_else(|e| panic!("error reading {}: {}", requests_path.display(), e));
+    let plan = Plan {
+        pace,
+        duration: Duration::from_secs(duration),
+        max_requests,
+    };
+
+    println!(
+        "Replaying {} requests from {} against {} with {:?} for {}s",
+        requests.len(),
+        requests_path.display(),
+        target_url,
+        plan.pace,
+        duration
+    );
+    let report = run(reqwest::Client::new(), &target_url, requests, &plan).await;
+    print!("{}", report);
+}
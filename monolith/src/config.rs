@@ -0,0 +1,89 @@
+use std::str::ParseBoolError;
+
+use service_common::{
+    fault::FaultInjector,
+    tls::{ClientTlsConfig, TlsConfig},
+};
+
+/// How the four services are exposed.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Mode {
+    /// One listener, each service nested under `/service-a` .. `/service-d`.
+    Prefix { bind_address: String },
+    /// One listener per service, each with the routes it has standalone.
+    Ports {
+        service_a: String,
+        service_b: String,
+        service_c: String,
+        service_d: String,
+    },
+}
+
+/// Everything the monolith reads from its environment.
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub mode: Mode,
+    /// Whether service-b calls the other routers directly instead of going
+    /// back out over the network.
+    pub in_process: bool,
+    pub tracing_enabled: bool,
+    pub agent_address: Option<String>,
+    pub tls: Option<TlsConfig>,
+    pub faults: Option<FaultInjector>,
+    pub upstream_tls: ClientTlsConfig,
+    pub weather_api_url: String,
+    pub weather_api_key: String,
+    pub weather_cache: Option<service_d::CacheConfig>,
+    pub weather_batch: service_d::BatchConfig,
+    pub weather_alerts: service_d::AlertsConfig,
+    pub weather_history: Option<service_d::HistoryConfig>,
+    pub time_streams: service_c::StreamConfig,
+}
+
+impl Config {
+    pub fn from_env() -> Config {
+        let tracing_enabled =
+            std::env::var("DD_TRACING_ENABLED").expect("DD_TRACING_ENABLED is required");
+        let use_tracing: Result<bool, ParseBoolError> = tracing_enabled.parse();
+
+        let mode = match std::env::var("MONOLITH_MODE").as_deref() {
+            Ok("ports") => Mode::Ports {
+                service_a: bind_address("SERVICE_A_BIND_ADDRESS"),
+                service_b: bind_address("SERVICE_B_BIND_ADDRESS"),
+                service_c: bind_address("SERVICE_C_BIND_ADDRESS"),
+                service_d: bind_address("SERVICE_D_BIND_ADDRESS"),
+            },
+            Ok("prefix") | Err(_) => Mode::Prefix {
+                bind_address: bind_address("BIND_ADDRESS"),
+            },
+            Ok(other) => panic!("MONOLITH_MODE must be prefix or ports, got {}", other),
+        };
+        let in_process = std::env::var("MONOLITH_IN_PROCESS")
+            .map(|v| {
+                v.parse()
+                    .expect("MONOLITH_IN_PROCESS must be true or false")
+            })
+            .unwrap_or(true);
+
+        Config {
+            mode,
+            in_process,
+            tracing_enabled: use_tracing.unwrap_or_default(),
+            agent_address: std::env::var("AGENT_ADDRESS").ok(),
+            tls: TlsConfig::from_env(),
+            faults: FaultInjector::from_env(),
+            upstream_tls: ClientTlsConfig::from_env(),
+            weather_api_url: std::env::var("WEATHER_API_URL").expect("WEATHER_API_URL Must be Set"),
+            weather_api_key: std::env::var("WEATHER_API_KEY").expect("WEATHER_API_KEY Must be set"),
+            weather_cache: service_d::CacheConfig::from_env(),
+            weather_batch: service_d::BatchConfig::from_env(),
+            weather_alerts: service_d::AlertsConfig::from_env(),
+            weather_history: service_d::HistoryConfig::from_env(),
+            time_streams: service_c::StreamConfig::from_env(),
+        }
+    }
+}
+
+fn bind_address(name: &str) -> String {
+    std::env::var(name).unwrap_or_else(|_| panic!("{} is required", name))
+}
@@ -0,0 +1,223 @@
+//! Hosts service-a, service-b, service-c and service-d in a single process
+//! for local development.
+use std::net::SocketAddr;
+
+use axum::Router;
+use service_b::Upstream;
+
+mod config;
+
+pub use config::{Config, Mode};
+
+/// Where service-b reaches the other services when it calls them over HTTP.
+#[derive(Clone, Debug)]
+pub struct UpstreamUrls {
+    pub service_a: String,
+    pub service_c: String,
+    pub service_d: String,
+}
+
+pub struct Services {
+    pub service_a: Router,
+    pub service_b: Router,
+    pub service_c: Router,
+    pub service_d: Router,
+}
+
+impl Services {
+    pub fn build(config: &Config, urls: UpstreamUrls) -> Services {
+        let service_a = service_a::build_router(&service_a::Config {
+            bind_address: String::new(),
+            tracing_enabled: config.tracing_enabled,
+            agent_address: config.agent_address.clone(),
+            tls: config.tls.clone(),
+            faults: config.faults.clone(),
+        });
+        let service_c = service_c::build_router(&service_c::Config {
+            bind_address: String::new(),
+            tracing_enabled: config.tracing_enabled,
+            agent_address: config.agent_address.clone(),
+            tls: config.tls.clone(),
+            faults: config.faults.clone(),
+            streams: config.time_streams,
+        });
+        let service_d = service_d::build_router(&service_d::Config {
+            bind_address: String::new(),
+            tracing_enabled: config.tracing_enabled,
+            agent_address: config.agent_address.clone(),
+            tls: config.tls.clone(),
+            faults: config.faults.clone(),
+            upstream_tls: config.upstream_tls.clone(),
+            weather_api_url: config.weather_api_url.clone(),
+            weather_api_key: config.weather_api_key.clone(),
+            weather_cache: config.weather_cache.clone(),
+            weather_batch: config.weather_batch,
+            weather_alerts: config.weather_alerts,
+            weather_history: config.weather_history.clone(),
+        });
+
+        let mut service_b_state = service_b::AppState::from_config(&service_b::Config {
+            bind_address: String::new(),
+            tracing_enabled: config.tracing_enabled,
+            agent_address: config.agent_address.clone(),
+            tls: config.tls.clone(),
+            faults: config.faults.clone(),
+            upstream_tls: config.upstream_tls.clone(),
+            service_a_url: urls.service_a,
+            service_c_url: urls.service_c,
+            service_d_url: urls.service_d,
+            recording: None,
+            service_a_hedge: None,
+            service_c_hedge: None,
+            service_d_hedge: None,
+            service_a_bulkhead: service_b::BulkheadConfig::default(),
+            service_c_bulkhead: service_b::BulkheadConfig::default(),
+            service_d_bulkhead: service_b::BulkheadConfig::default(),
+        });
+        if config.in_process {
+            service_b_state.service_a = Upstream::in_process("service-a", service_a.clone());
+            service_b_state.service_c = Upstream::in_process("service-c", service_c.clone());
+            service_b_state.service_d = Upstream::in_process("service-d", service_d.clone());
+        }
+
+        Services {
+            service_a,
+            service_b: service_b::app(service_b_state),
+            service_c,
+            service_d,
+        }
+    }
+
+    /// Mounts every service under its own name on one router.
+    pub fn nested(self) -> Router {
+        Router::new()
+            .nest("/service-a", self.service_a)
+            .nest("/service-b", self.service_b)
+            .nest("/service-c", self.service_c)
+            .nest("/service-d", self.service_d)
+    }
+}
+
+/// The base url a listener bound to `addr` is reachable on from this process.
+pub fn local_url(addr: SocketAddr, tls: bool, prefix: &str) -> String {
+    let scheme = if tls { "https" } else { "http" };
+    let host = if addr.ip().is_unspecified() {
+        match addr {
+            SocketAddr::V4(_) => String::from("127.0.0.1"),
+            SocketAddr::V6(_) => String::from("[::1]"),
+        }
+    } else {
+        match addr {
+            SocketAddr::V4(a) => a.ip().to_string(),
+            SocketAddr::V6(a) => format!("[{}]", a.ip()),
+        }
+    };
+    format!("{}://{}:{}{}", scheme, host, addr.port(), prefix)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::Request, http::StatusCode};
+    use serde_json::Value;
+    use service_common::tls::ClientTlsConfig;
+    use tower::ServiceExt;
+
+    fn config() -> Config {
+        Config {
+            mode: Mode::Prefix {
+                bind_address: String::from("127.0.0.1:0"),
+            },
+            in_process: true,
+            tracing_enabled: false,
+            agent_address: None,
+            tls: None,
+            faults: None,
+            upstream_tls: ClientTlsConfig::default(),
+            weather_api_url: String::from("http://127.0.0.1:1"),
+            weather_api_key: String::from("test"),
+            weather_cache: None,
+            weather_batch: service_d::BatchConfig::default(),
+            weather_alerts: service_d::AlertsConfig::default(),
+            weather_history: None,
+            time_streams: service_c::StreamConfig::default(),
+        }
+    }
+
+    fn urls() -> UpstreamUrls {
+        UpstreamUrls {
+            service_a: String::from("http://127.0.0.1:1"),
+            service_c: String::from("http://127.0.0.1:1"),
+            service_d: String::from("http://127.0.0.1:1"),
+        }
+    }
+
+    async fn call(router: Router, uri: &str) -> (StatusCode, Value) {
+        let response = router
+            .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+    }
+
+    #[tokio::test]
+    async fn prefix_mode_mounts_every_service() {
+        let router = Services::build(&config(), urls()).nested();
+
+        for uri in [
+            "/service-a/health",
+            "/service-b/health",
+            "/service-c",
+            "/service-d/health",
+        ] {
+            let (status, body) = call(router.clone(), uri).await;
+            assert_eq!(StatusCode::OK, status, "{}", uri);
+            assert_eq!("Healthy", body["status"], "{}", uri);
+        }
+        let (status, body) = call(router, "/service-a/route?p=Ben").await;
+        assert_eq!(StatusCode::OK, status);
+        assert_eq!("(Ben)Field 1", body["key_one"]);
+    }
+
+    #[tokio::test]
+    async fn service_b_calls_upstreams_in_process() {
+        let services = Services::build(&config(), urls());
+
+        // The upstream urls point nowhere. In process, service-a and service-c
+        // answer and only service-d's weather provider is unreachable, which
+        // service-b reports as a bad request rather than an internal error.
+        let (status, body) = call(services.service_b, "/?name=Ben&zip=76262").await;
+        assert_eq!(StatusCode::BAD_REQUEST, status);
+        assert_eq!(Value::Null, body);
+
+        let services = Services::build(
+            &Config {
+                in_process: false,
+                ..config()
+            },
+            urls(),
+        );
+        let (status, _) = call(services.service_b, "/?name=Ben&zip=76262").await;
+        assert_eq!(StatusCode::INTERNAL_SERVER_ERROR, status);
+    }
+
+    #[test]
+    fn local_url_uses_loopback_for_wildcard_binds() {
+        assert_eq!(
+            "http://127.0.0.1:8080/service-a",
+            local_url("0.0.0.0:8080".parse().unwrap(), false, "/service-a")
+        );
+        assert_eq!(
+            "https://[::1]:8443",
+            local_url("[::]:8443".parse().unwrap(), true, "")
+        );
+        assert_eq!(
+            "http://10.0.0.2:3000",
+            local_url("10.0.0.2:3000".parse().unwrap(), false, "")
+        );
+    }
+}
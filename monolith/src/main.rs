@@ -0,0 +1,96 @@
+use monolith::{local_url, Config, Mode, Services, UpstreamUrls};
+use opentelemetry::global;
+use opentelemetry_datadog::{new_pipeline, ApiVersion};
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use service_common::tls::serve;
+use tokio::net::TcpListener;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, Registry};
+
+#[tokio::main]
+async fn main() {
+    global::set_text_map_propagator(TraceContextPropagator::new());
+
+    let config = Config::from_env();
+
+    let fmt_layer = tracing_subscriber::fmt::layer()
+        .with_target(false)
+        .without_time();
+
+    if config.tracing_enabled {
+        let agent_address = config
+            .agent_address
+            .clone()
+            .expect("AGENT_ADDRESS is required");
+        let tracer = match new_pipeline()
+            .with_service_name("monolith")
+            .with_agent_endpoint(format!("http://{}:8126", agent_address))
+            .with_api_version(ApiVersion::Version05)
+            .install_batch(opentelemetry_sdk::runtime::Tokio)
+        {
+            Ok(a) => a,
+            Err(e) => {
+                panic!("error starting! {}", e);
+            }
+        };
+        let telemetry_layer = tracing_opentelemetry::layer().with_tracer(tracer);
+        Registry::default()
+            .with(fmt_layer)
+            .with(telemetry_layer)
+            .with(tracing_subscriber::EnvFilter::from_default_env())
+            .init();
+    } else {
+        Registry::default()
+            .with(fmt_layer)
+            .with(tracing_subscriber::EnvFilter::from_default_env())
+            .init();
+    }
+
+    let tls = config.tls.is_some();
+    match config.mode.clone() {
+        Mode::Prefix { bind_address } => {
+            let listener = TcpListener::bind(bind_address.clone()).await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            let urls = UpstreamUrls {
+                service_a: local_url(addr, tls, "/service-a"),
+                service_c: local_url(addr, tls, "/service-c"),
+                service_d: local_url(addr, tls, "/service-d"),
+            };
+            let app = Services::build(&config, urls).nested();
+
+            tracing::info!("Up and running ... listening on {}", bind_address);
+            serve(listener, app, config.tls.clone()).await.unwrap();
+        }
+        Mode::Ports {
+            service_a,
+            service_b,
+            service_c,
+            service_d,
+        } => {
+            let a = TcpListener::bind(service_a.clone()).await.unwrap();
+            let b = TcpListener::bind(service_b.clone()).await.unwrap();
+            let c = TcpListener::bind(service_c.clone()).await.unwrap();
+            let d = TcpListener::bind(service_d.clone()).await.unwrap();
+            let urls = UpstreamUrls {
+                service_a: local_url(a.local_addr().unwrap(), tls, ""),
+                service_c: local_url(c.local_addr().unwrap(), tls, ""),
+                service_d: local_url(d.local_addr().unwrap(), tls, ""),
+            };
+            let services = Services::build(&config, urls);
+
+            tracing::info!(
+                "Up and running ... service-a on {}, service-b on {}, service-c on {}, service-d on {}",
+                service_a,
+                service_b,
+                service_c,
+                service_d
+            );
+            tokio::try_join!(
+                serve(a, services.service_a, config.tls.clone()),
+                serve(b, services.service_b, config.tls.clone()),
+                serve(c, services.service_c, config.tls.clone()),
+                serve(d, services.service_d, config.tls.clone()),
+            )
+            .unwrap();
+        }
+    }
+}
@@ -0,0 +1,29 @@
+use std::str::ParseBoolError;
+
+use service_common::{fault::FaultInjector, tls::TlsConfig};
+
+/// Everything the service reads from its environment.
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub bind_address: String,
+    pub tracing_enabled: bool,
+    pub agent_address: Option<String>,
+    pub tls: Option<TlsConfig>,
+    pub faults: Option<FaultInjector>,
+}
+
+impl Config {
+    pub fn from_env() -> Config {
+        let tracing_enabled =
+            std::env::var("DD_TRACING_ENABLED").expect("DD_TRACING_ENABLED is required");
+        let use_tracing: Result<bool, ParseBoolError> = tracing_enabled.parse();
+
+        Config {
+            bind_address: std::env::var("BIND_ADDRESS").expect("BIND_ADDRESS is required"),
+            tracing_enabled: use_tracing.unwrap_or_default(),
+            agent_address: std::env::var("AGENT_ADDRESS").ok(),
+            tls: TlsConfig::from_env(),
+            faults: FaultInjector::from_env(),
+        }
+    }
+}
@@ -0,0 +1,195 @@
+use std::collections::HashMap;
+
+use axum::{
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    routing::get,
+    Json, Router,
+};
+use opentelemetry::propagation::TextMapPropagator;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use service_common::fault;
+use service_models::v1::{
+    service_a::{Model, Prefix},
+    HealthCheck,
+};
+use tracing::instrument;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use utoipa::OpenApi;
+
+mod config;
+
+pub use config::Config;
+
+#[derive(OpenApi)]
+#[openapi(paths(handler, health), components(schemas(Model, HealthCheck)))]
+struct ApiDoc;
+
+#[derive(Clone, Debug)]
+pub struct AppState {
+    pub has_apm: bool,
+}
+
+impl AppState {
+    pub fn from_config(config: &Config) -> AppState {
+        AppState {
+            has_apm: config.tracing_enabled,
+        }
+    }
+}
+
+/// Builds the service's routes with state derived from `config`. The router
+/// can be served on its own or nested under a prefix in another axum app.
+pub fn build_router(config: &Config) -> Router {
+    let app = app(AppState::from_config(config));
+    match &config.faults {
+        Some(faults) => fault::inject(app, faults.clone()),
+        None => app,
+    }
+}
+
+pub fn app(app_state: AppState) -> Router {
+    let app = Router::new()
+        .route("/route", get(handler))
+        .route("/health", get(health))
+        .route("/openapi.json", get(openapi))
+        .with_state(app_state);
+    #[cfg(feature = "swagger-ui")]
+    let app = app.merge(
+        utoipa_swagger_ui::SwaggerUi::new("/swagger-ui")
+            .config(utoipa_swagger_ui::Config::from("/openapi.json")),
+    );
+    app
+}
+
+#[utoipa::path(
+    get,
+    operation_id = "get_route",
+    path = "/route",
+    params(Prefix),
+    responses((status = 200, description = "Fields built from the prefix", body = Model))
+)]
+#[instrument(name = "GET /route")]
+async fn handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    query: Query<Prefix>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let traceparent = headers.get("traceparent").and_then(|v| v.to_str().ok());
+    if let (true, Some(traceparent)) = (state.has_apm, traceparent) {
+        let mut fields: HashMap<String, String> = HashMap::new();
+        fields.insert("traceparent".to_string(), String::from(traceparent));
+
+        let propagator = TraceContextPropagator::new();
+        let context = propagator.extract(&fields);
+        let span = tracing::Span::current();
+        span.set_parent(context);
+    }
+    let prefix: String;
+    let passed_value = &query.p;
+
+    if let Some(s) = passed_value {
+        prefix = String::from(s.as_str());
+    } else {
+        prefix = String::from("Unknown");
+    }
+
+    tracing::info!("(Request)={}", prefix);
+    let m: Model = Model {
+        key_two: format!("({})Field 2", prefix),
+        key_one: format!("({})Field 1", prefix),
+    };
+
+    Ok(Json(m))
+}
+
+#[utoipa::path(
+    get,
+    operation_id = "get_health",
+    path = "/health",
+    responses((status = 200, description = "Service is healthy", body = HealthCheck))
+)]
+async fn health() -> Result<impl IntoResponse, StatusCode> {
+    let healthy = HealthCheck {
+        status: String::from("Healthy"),
+    };
+
+    Ok(Json(healthy))
+}
+
+async fn openapi() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::{to_bytes, Body};
+    use axum::http::Request;
+    use contract_testing::{verify, Contract};
+    use serde_json::json;
+    use tower::ServiceExt;
+
+    fn config() -> Config {
+        Config {
+            bind_address: String::from("127.0.0.1:0"),
+            tracing_enabled: false,
+            agent_address: None,
+            tls: None,
+            faults: None,
+        }
+    }
+
+    async fn call(router: Router, uri: &str) -> (StatusCode, serde_json::Value) {
+        let request = Request::builder().uri(uri).body(Body::empty()).unwrap();
+        let response = router.oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or_default())
+    }
+
+    #[test]
+    fn fake_1() {
+        let s = "one";
+        assert_eq!("one", s);
+    }
+
+    #[test]
+    fn openapi_spec_matches_committed_file() {
+        service_common::openapi::assert_matches_committed(
+            &ApiDoc::openapi().to_pretty_json().unwrap(),
+            concat!(env!("CARGO_MANIFEST_DIR"), "/openapi.json"),
+        );
+    }
+
+    #[tokio::test]
+    async fn honours_service_b_contract() {
+        let contract = Contract::load("service-b", "service-a").unwrap();
+        let result = verify(&contract, |_| async { app(AppState { has_apm: false }) }).await;
+        if let Err(e) = result {
+            panic!("{}", e);
+        }
+    }
+
+    #[tokio::test]
+    async fn builds_fields_from_the_prefix() {
+        let (status, body) = call(build_router(&config()), "/route?p=Ben").await;
+        assert_eq!(StatusCode::OK, status);
+        assert_eq!(
+            json!({"key_one": "(Ben)Field 1", "key_two": "(Ben)Field 2"}),
+            body
+        );
+
+        let (_, body) = call(build_router(&config()), "/route").await;
+        assert_eq!("(Unknown)Field 1", body["key_one"]);
+    }
+
+    #[tokio::test]
+    async fn can_be_nested_under_a_prefix() {
+        let router = Router::new().nest("/service-a", build_router(&config()));
+        let (status, body) = call(router, "/service-a/health").await;
+        assert_eq!(StatusCode::OK, status);
+        assert_eq!(json!({"status": "Healthy"}), body);
+    }
+}
@@ -0,0 +1,54 @@
+use std::path::PathBuf;
+
+use service_b::{app, recording, AppState, Upstream};
+use service_common::tls::ClientTlsConfig;
+
+/// Replays a file written with `RECORDING_PATH` set. Upstream responses come
+/// from the file unless `REPLAY_UPSTREAMS=live`, in which case the upstreams
+/// at `SERVICE_A_URL`, `SERVICE_C_URL` and `SERVICE_D_URL` are called.
+#[tokio::main]
+async fn main() {
+    let path = PathBuf::from(std::env::var("REPLAY_PATH").expect("REPLAY_PATH is required"));
+    let live = match std::env::var("REPLAY_UPSTREAMS").as_deref() {
+        Ok("live") => true,
+        Ok("recorded") | Err(_) => false,
+        Ok(other) => panic!("REPLAY_UPSTREAMS must be recorded or live, got {}", other),
+    };
+    let live_state = live.then(|| {
+        let http_client = ClientTlsConfig::from_env()
+            .http_client()
+            .expect("invalid upstream TLS configuration");
+        let url =
+            |name: &str| std::env::var(name).unwrap_or_else(|_| panic!("{} Must be Set", name));
+        AppState {
+            service_a: Upstream::http(http_client.clone(), &url("SERVICE_A_URL"))
+                .with_name("service-a"),
+            service_c: Upstream::http(http_client.clone(), &url("SERVICE_C_URL"))
+                .with_name("service-c"),
+            service_d: Upstream::http(http_client, &url("SERVICE_D_URL")).with_name("service-d"),
+        }
+    });
+
+    let recordings = recording::load(&path)
+        .unwrap_or_else(|e| panic!("error reading {}: {}", path.display(), e));
+    let mut mismatches = 0;
+    for recording in &recordings {
+        let state = live_state
+            .clone()
+            .unwrap_or_else(|| recording.recorded_upstreams());
+        match recording.replay(app(state)).await {
+            Ok(()) => println!("ok       {} {}", recording.method, recording.uri),
+            Err(difference) => {
+                mismatches += 1;
+                println!(
+                    "mismatch {} {}: {}",
+                    recording.method, recording.uri, difference
+                );
+            }
+        }
+    }
+    println!("{} replayed, {} mismatched", recordings.len(), mismatches);
+    if mismatches > 0 {
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,238 @@
+use std::{
+    future::Future,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use axum::http::StatusCode;
+use tokio::sync::Semaphore;
+
+use crate::metrics;
+
+const DEFAULT_MAX_CONCURRENT: usize = 64;
+
+/// Built from `<PREFIX>_BULKHEAD_MAX_CONCURRENT` and
+/// `<PREFIX>_BULKHEAD_MAX_QUEUE`, e.g. `SERVICE_D_BULKHEAD_MAX_CONCURRENT=8`.
+/// The queue is unbounded unless a limit is given.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BulkheadConfig {
+    pub max_concurrent: usize,
+    pub max_queue: Option<usize>,
+}
+
+impl Default for BulkheadConfig {
+    fn default() -> Self {
+        BulkheadConfig {
+            max_concurrent: DEFAULT_MAX_CONCURRENT,
+            max_queue: None,
+        }
+    }
+}
+
+impl BulkheadConfig {
+    pub fn from_env(prefix: &str) -> BulkheadConfig {
+        let number = |suffix: &str| {
+            let name = format!("{}_BULKHEAD_{}", prefix, suffix);
+            std::env::var(&name).ok().map(|v| {
+                v.parse::<usize>()
+                    .unwrap_or_else(|_| panic!("{} must be a number", name))
+            })
+        };
+        BulkheadConfig {
+            max_concurrent: number("MAX_CONCURRENT").unwrap_or(DEFAULT_MAX_CONCURRENT),
+            max_queue: number("MAX_QUEUE"),
+        }
+    }
+}
+
+/// Caps the calls in flight to one upstream so a slow dependency can only
+/// tie up its own share of service-b.
+#[derive(Clone, Debug)]
+pub struct Bulkhead {
+    name: String,
+    config: BulkheadConfig,
+    permits: Arc<Semaphore>,
+    queued: Arc<AtomicUsize>,
+}
+
+/// Moves a gauge up for as long as it is held, including when the future
+/// holding it is cancelled.
+struct Gauge<'a> {
+    name: &'static str,
+    upstream: &'a str,
+}
+
+impl<'a> Gauge<'a> {
+    fn raise(name: &'static str, upstream: &'a str) -> Gauge<'a> {
+        metrics::add(name, &[("upstream", upstream)], 1);
+        Gauge { name, upstream }
+    }
+}
+
+impl Drop for Gauge<'_> {
+    fn drop(&mut self) {
+        metrics::add(self.name, &[("upstream", self.upstream)], -1);
+    }
+}
+
+struct Queued<'a>(&'a AtomicUsize);
+
+impl Drop for Queued<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl Bulkhead {
+    pub fn new(name: &str, config: BulkheadConfig) -> Bulkhead {
+        Bulkhead {
+            name: name.to_string(),
+            permits: Arc::new(Semaphore::new(config.max_concurrent.max(1))),
+            config,
+            queued: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Waits for a free slot, then runs `call`. Answers 503 without calling
+    /// when the queue is already full.
+    pub async fn run<F, T>(&self, call: F) -> Result<T, StatusCode>
+    where
+        F: Future<Output = Result<T, StatusCode>>,
+    {
+        let permit = match self.permits.try_acquire() {
+            Ok(permit) => permit,
+            Err(_) => {
+                let waiting = self.queued.fetch_add(1, Ordering::SeqCst);
+                let _queued = Queued(&self.queued);
+                if self.config.max_queue.is_some_and(|max| waiting >= max) {
+                    tracing::error!("Bulkhead for {} is full", self.name);
+                    metrics::increment(
+                        "upstream_bulkhead_rejected_total",
+                        &[("upstream", &self.name)],
+                    );
+                    return Err(StatusCode::SERVICE_UNAVAILABLE);
+                }
+                let _gauge = Gauge::raise("upstream_bulkhead_queued", &self.name);
+                self.permits
+                    .acquire()
+                    .await
+                    .expect("bulkhead is never closed")
+            }
+        };
+        let _in_flight = Gauge::raise("upstream_bulkhead_in_flight", &self.name);
+        let result = call.await;
+        drop(permit);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn bulkhead(name: &str, max_concurrent: usize, max_queue: Option<usize>) -> Bulkhead {
+        Bulkhead::new(
+            name,
+            BulkheadConfig {
+                max_concurrent,
+                max_queue,
+            },
+        )
+    }
+
+    #[tokio::test]
+    async fn limits_calls_in_flight() {
+        let bulkhead = bulkhead("limit-test", 2, None);
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let most = Arc::new(AtomicUsize::new(0));
+
+        let calls: Vec<_> = (0..6)
+            .map(|_| {
+                let bulkhead = bulkhead.clone();
+                let in_flight = in_flight.clone();
+                let most = most.clone();
+                tokio::spawn(async move {
+                    bulkhead
+                        .run(async {
+                            let now = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                            most.fetch_max(now, Ordering::SeqCst);
+                            tokio::time::sleep(Duration::from_millis(20)).await;
+                            in_flight.fetch_sub(1, Ordering::SeqCst);
+                            Ok::<_, StatusCode>(())
+                        })
+                        .await
+                })
+            })
+            .collect();
+        for call in calls {
+            call.await.unwrap().unwrap();
+        }
+
+        assert_eq!(2, most.load(Ordering::SeqCst));
+        let labels = [("upstream", "limit-test")];
+        assert_eq!(0, metrics::value("upstream_bulkhead_in_flight", &labels));
+        assert_eq!(0, metrics::value("upstream_bulkhead_queued", &labels));
+    }
+
+    #[tokio::test]
+    async fn rejects_once_the_queue_is_full() {
+        let bulkhead = bulkhead("queue-test", 1, Some(1));
+        let labels = [("upstream", "queue-test")];
+
+        let busy = {
+            let bulkhead = bulkhead.clone();
+            tokio::spawn(async move {
+                bulkhead
+                    .run(async {
+                        tokio::time::sleep(Duration::from_millis(200)).await;
+                        Ok::<_, StatusCode>(())
+                    })
+                    .await
+            })
+        };
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let waiting = {
+            let bulkhead = bulkhead.clone();
+            tokio::spawn(async move { bulkhead.run(async { Ok::<_, StatusCode>(()) }).await })
+        };
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(1, metrics::value("upstream_bulkhead_queued", &labels));
+
+        assert_eq!(
+            Err(StatusCode::SERVICE_UNAVAILABLE),
+            bulkhead.run(async { Ok::<_, StatusCode>(()) }).await
+        );
+        assert_eq!(
+            1,
+            metrics::value("upstream_bulkhead_rejected_total", &labels)
+        );
+
+        busy.await.unwrap().unwrap();
+        waiting.await.unwrap().unwrap();
+        assert_eq!(0, metrics::value("upstream_bulkhead_queued", &labels));
+    }
+
+    #[tokio::test]
+    async fn cancelled_calls_release_their_slot() {
+        let bulkhead = bulkhead("cancel-test", 1, None);
+        let slow = bulkhead.run(async {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            Ok::<_, StatusCode>(())
+        });
+        assert!(tokio::time::timeout(Duration::from_millis(20), slow)
+            .await
+            .is_err());
+
+        assert_eq!(
+            0,
+            metrics::value(
+                "upstream_bulkhead_in_flight",
+                &[("upstream", "cancel-test")]
+            )
+        );
+        assert_eq!(Ok(()), bulkhead.run(async { Ok(()) }).await);
+    }
+}
@@ -0,0 +1,56 @@
+use std::str::ParseBoolError;
+
+use service_common::{
+    fault::FaultInjector,
+    tls::{ClientTlsConfig, TlsConfig},
+};
+
+use crate::{bulkhead::BulkheadConfig, hedge::HedgeConfig, recording::RecordingConfig};
+
+/// Everything the service reads from its environment.
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub bind_address: String,
+    pub tracing_enabled: bool,
+    pub agent_address: Option<String>,
+    pub tls: Option<TlsConfig>,
+    pub faults: Option<FaultInjector>,
+    pub upstream_tls: ClientTlsConfig,
+    pub service_a_url: String,
+    pub service_c_url: String,
+    pub service_d_url: String,
+    pub recording: Option<RecordingConfig>,
+    pub service_a_hedge: Option<HedgeConfig>,
+    pub service_c_hedge: Option<HedgeConfig>,
+    pub service_d_hedge: Option<HedgeConfig>,
+    pub service_a_bulkhead: BulkheadConfig,
+    pub service_c_bulkhead: BulkheadConfig,
+    pub service_d_bulkhead: BulkheadConfig,
+}
+
+impl Config {
+    pub fn from_env() -> Config {
+        let tracing_enabled =
+            std::env::var("DD_TRACING_ENABLED").expect("DD_TRACING_ENABLED is required");
+        let use_tracing: Result<bool, ParseBoolError> = tracing_enabled.parse();
+
+        Config {
+            bind_address: std::env::var("BIND_ADDRESS").expect("BIND_ADDRESS is required"),
+            tracing_enabled: use_tracing.unwrap_or_default(),
+            agent_address: std::env::var("AGENT_ADDRESS").ok(),
+            tls: TlsConfig::from_env(),
+            faults: FaultInjector::from_env(),
+            upstream_tls: ClientTlsConfig::from_env(),
+            service_a_url: std::env::var("SERVICE_A_URL").expect("SERVICE_A_URL Must be Set"),
+            service_c_url: std::env::var("SERVICE_C_URL").expect("SERVICE_C_URL Must be Set"),
+            service_d_url: std::env::var("SERVICE_D_URL").expect("SERVICE_D_URL Must be Set"),
+            recording: RecordingConfig::from_env(),
+            service_a_hedge: HedgeConfig::from_env("SERVICE_A"),
+            service_c_hedge: HedgeConfig::from_env("SERVICE_C"),
+            service_d_hedge: HedgeConfig::from_env("SERVICE_D"),
+            service_a_bulkhead: BulkheadConfig::from_env("SERVICE_A"),
+            service_c_bulkhead: BulkheadConfig::from_env("SERVICE_C"),
+            service_d_bulkhead: BulkheadConfig::from_env("SERVICE_D"),
+        }
+    }
+}
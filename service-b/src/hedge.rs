@@ -0,0 +1,234 @@
+use std::{
+    collections::VecDeque,
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use tracing::Span;
+
+use crate::metrics;
+
+/// Latencies kept per upstream to derive the hedging delay from.
+const WINDOW: usize = 200;
+/// Until this many calls have been seen the initial delay is used.
+const MIN_SAMPLES: usize = 20;
+const DEFAULT_INITIAL_DELAY_MS: u64 = 100;
+
+/// Built from `<PREFIX>_HEDGE_PERCENTILE`, e.g. `SERVICE_A_HEDGE_PERCENTILE=95`,
+/// and `HEDGE_INITIAL_DELAY_MS`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HedgeConfig {
+    pub percentile: f64,
+    pub initial_delay: Duration,
+}
+
+impl HedgeConfig {
+    pub fn from_env(prefix: &str) -> Option<HedgeConfig> {
+        let name = format!("{}_HEDGE_PERCENTILE", prefix);
+        let percentile = std::env::var(&name).ok()?;
+        let initial_delay = std::env::var("HEDGE_INITIAL_DELAY_MS")
+            .map(|d| d.parse().expect("HEDGE_INITIAL_DELAY_MS must be a number"))
+            .unwrap_or(DEFAULT_INITIAL_DELAY_MS);
+        Some(HedgeConfig {
+            percentile: percentile
+                .parse()
+                .unwrap_or_else(|_| panic!("{} must be a number", name)),
+            initial_delay: Duration::from_millis(initial_delay),
+        })
+    }
+}
+
+/// Sends a second attempt when the first is slower than the configured
+/// percentile of recent calls, and keeps whichever answers first.
+#[derive(Clone, Debug)]
+pub struct Hedge {
+    name: String,
+    config: HedgeConfig,
+    latencies: Arc<Mutex<VecDeque<Duration>>>,
+}
+
+impl Hedge {
+    pub fn new(name: &str, config: HedgeConfig) -> Hedge {
+        Hedge {
+            name: name.to_string(),
+            config,
+            latencies: Arc::new(Mutex::new(VecDeque::with_capacity(WINDOW))),
+        }
+    }
+
+    pub fn delay(&self) -> Duration {
+        let latencies = self.latencies.lock().unwrap();
+        if latencies.len() < MIN_SAMPLES {
+            return self.config.initial_delay;
+        }
+        let mut sorted: Vec<Duration> = latencies.iter().copied().collect();
+        sorted.sort();
+        let rank = ((self.config.percentile / 100.0) * sorted.len() as f64).ceil() as usize;
+        sorted[rank.clamp(1, sorted.len()) - 1]
+    }
+
+    fn observe(&self, latency: Duration) {
+        let mut latencies = self.latencies.lock().unwrap();
+        if latencies.len() == WINDOW {
+            latencies.pop_front();
+        }
+        latencies.push_back(latency);
+    }
+
+    /// Runs `attempt`, starting it again if the first try outlasts the delay.
+    /// The attempt that loses is dropped, which cancels it. Only the latency
+    /// of the winning attempt itself is kept for later delays, and only when
+    /// `succeeded` says its result is a success, so neither fast failures
+    /// nor the time hedging saved pull the delay down.
+    pub async fn run<F, Fut, T>(&self, attempt: F, succeeded: impl Fn(&T) -> bool) -> T
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = T>,
+    {
+        let timed = || {
+            let started = Instant::now();
+            let attempt = attempt();
+            async move { (attempt.await, started.elapsed()) }
+        };
+        let delay = self.delay();
+        let first = timed();
+        tokio::pin!(first);
+
+        let (result, latency) = tokio::select! {
+            result = &mut first => result,
+            _ = tokio::time::sleep(delay) => {
+                metrics::increment("upstream_hedges_total", &[("upstream", &self.name)]);
+                Span::current().record("hedged", true);
+                tracing::info!("Hedging call to {} after {:?}", self.name, delay);
+
+                let second = timed();
+                tokio::pin!(second);
+                tokio::select! {
+                    result = &mut first => result,
+                    result = &mut second => {
+                        metrics::increment("upstream_hedge_wins_total", &[("upstream", &self.name)]);
+                        result
+                    }
+                }
+            }
+        };
+        if succeeded(&result) {
+            self.observe(latency);
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn hedge(name: &str, initial_delay_ms: u64) -> Hedge {
+        Hedge::new(
+            name,
+            HedgeConfig {
+                percentile: 90.0,
+                initial_delay: Duration::from_millis(initial_delay_ms),
+            },
+        )
+    }
+
+    #[test]
+    fn delay_follows_the_configured_percentile() {
+        let hedge = hedge("delay-test", 100);
+        assert_eq!(Duration::from_millis(100), hedge.delay());
+
+        for ms in 1..=100 {
+            hedge.observe(Duration::from_millis(ms));
+        }
+        assert_eq!(Duration::from_millis(90), hedge.delay());
+
+        // Only the most recent calls count.
+        for _ in 0..WINDOW {
+            hedge.observe(Duration::from_millis(5));
+        }
+        assert_eq!(Duration::from_millis(5), hedge.delay());
+    }
+
+    #[tokio::test]
+    async fn slow_first_attempts_are_hedged() {
+        let hedge = hedge("hedge-test", 20);
+        let attempts = AtomicUsize::new(0);
+
+        let started = Instant::now();
+        let winner = hedge
+            .run(
+                || {
+                    let n = attempts.fetch_add(1, Ordering::SeqCst);
+                    async move {
+                        if n == 0 {
+                            tokio::time::sleep(Duration::from_secs(5)).await;
+                        }
+                        n
+                    }
+                },
+                |_| true,
+            )
+            .await;
+
+        assert_eq!(1, winner);
+        assert!(started.elapsed() < Duration::from_secs(1));
+        let labels = [("upstream", "hedge-test")];
+        assert_eq!(1, metrics::value("upstream_hedges_total", &labels));
+        assert_eq!(1, metrics::value("upstream_hedge_wins_total", &labels));
+    }
+
+    #[tokio::test]
+    async fn keeps_the_latency_of_successful_attempts_only() {
+        let hedge = hedge("latency-test", 50);
+        let attempts = AtomicUsize::new(0);
+        let run = |fail: bool| {
+            let attempts = &attempts;
+            hedge.run(
+                move || {
+                    let n = attempts.fetch_add(1, Ordering::SeqCst);
+                    async move {
+                        if n == 0 {
+                            tokio::time::sleep(Duration::from_secs(5)).await;
+                        }
+                        !fail
+                    }
+                },
+                |ok| *ok,
+            )
+        };
+
+        // The hedge wins at once, so what the caller waited is not its latency.
+        assert!(run(false).await);
+        let latencies = hedge.latencies.lock().unwrap().clone();
+        assert_eq!(1, latencies.len());
+        assert!(latencies[0] < Duration::from_millis(50), "{:?}", latencies);
+
+        assert!(!run(true).await);
+        assert_eq!(1, hedge.latencies.lock().unwrap().len());
+    }
+
+    #[tokio::test]
+    async fn fast_attempts_are_not_hedged() {
+        let hedge = hedge("no-hedge-test", 1_000);
+        let attempts = AtomicUsize::new(0);
+
+        hedge
+            .run(
+                || {
+                    attempts.fetch_add(1, Ordering::SeqCst);
+                    async {}
+                },
+                |_| true,
+            )
+            .await;
+
+        assert_eq!(1, attempts.load(Ordering::SeqCst));
+        assert_eq!(
+            0,
+            metrics::value("upstream_hedges_total", &[("upstream", "no-hedge-test")])
+        );
+    }
+}
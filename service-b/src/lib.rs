@@ -0,0 +1,621 @@
+use std::collections::HashMap;
+
+use axum::{
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    routing::get,
+    Json, Router,
+};
+use opentelemetry::propagation::TextMapPropagator;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use service_common::fault;
+use service_models::v1::{
+    service_a::Model as ServiceAModel,
+    service_b::{ExternalModel, Prefix},
+    service_c::{ExternalModel as ServiceCModel, LocalTime},
+    service_d::{ForecastResponse, WeatherResponse as ServiceDModel},
+    HealthCheck,
+};
+use tracing::instrument;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use utoipa::OpenApi;
+
+mod bulkhead;
+mod config;
+mod hedge;
+pub mod metrics;
+pub mod recording;
+mod upstream;
+
+pub use bulkhead::{Bulkhead, BulkheadConfig};
+pub use config::Config;
+pub use hedge::{Hedge, HedgeConfig};
+pub use upstream::{Transport, Upstream};
+
+#[derive(OpenApi)]
+#[openapi(
+    paths(handler, health),
+    components(schemas(ExternalModel, ServiceDModel, ForecastResponse, LocalTime, HealthCheck))
+)]
+struct ApiDoc;
+
+#[derive(Clone, Debug)]
+pub struct AppState {
+    pub service_a: Upstream,
+    pub service_c: Upstream,
+    pub service_d: Upstream,
+}
+
+impl AppState {
+    pub fn from_config(config: &Config) -> AppState {
+        // Each upstream gets its own client, and so its own connection pool.
+        let upstream =
+            |name: &str, url: &str, hedge: &Option<HedgeConfig>, bulkhead: &BulkheadConfig| {
+                let http_client = config
+                    .upstream_tls
+                    .http_client()
+                    .expect("invalid upstream TLS configuration");
+                let upstream = Upstream::http(http_client, url)
+                    .with_name(name)
+                    .with_bulkhead(Bulkhead::new(name, bulkhead.clone()));
+                match hedge {
+                    Some(hedge) => upstream.with_hedge(Hedge::new(name, hedge.clone())),
+                    None => upstream,
+                }
+            };
+        AppState {
+            service_a: upstream(
+                "service-a",
+                &config.service_a_url,
+                &config.service_a_hedge,
+                &config.service_a_bulkhead,
+            ),
+            service_c: upstream(
+                "service-c",
+                &config.service_c_url,
+                &config.service_c_hedge,
+                &config.service_c_bulkhead,
+            ),
+            service_d: upstream(
+                "service-d",
+                &config.service_d_url,
+                &config.service_d_hedge,
+                &config.service_d_bulkhead,
+            ),
+        }
+    }
+}
+
+/// Builds the service's routes with state derived from `config`. The router
+/// can be served on its own or nested under a prefix in another axum app.
+pub fn build_router(config: &Config) -> Router {
+    let mut app = app(AppState::from_config(config));
+    if let Some(recording) = &config.recording {
+        let recorder =
+            recording::Recorder::open(recording).expect("unable to open the recording file");
+        app = recording::record(app, recorder);
+    }
+    match &config.faults {
+        Some(faults) => fault::inject(app, faults.clone()),
+        None => app,
+    }
+}
+
+pub fn app(app_state: AppState) -> Router {
+    let app = Router::new()
+        .route("/", get(handler))
+        .route("/health", get(health))
+        .route("/openapi.json", get(openapi))
+        .route("/metrics", get(|| async { metrics::render() }))
+        .with_state(app_state);
+    #[cfg(feature = "swagger-ui")]
+    let app = app.merge(
+        utoipa_swagger_ui::SwaggerUi::new("/swagger-ui")
+            .config(utoipa_swagger_ui::Config::from("/openapi.json")),
+    );
+    app
+}
+
+#[utoipa::path(
+    get,
+    operation_id = "get_aggregate",
+    path = "/",
+    params(Prefix),
+    responses(
+        (status = 200, description = "Aggregated upstream responses", body = ExternalModel),
+        (status = 400, description = "No zip was given, or an upstream rejected the request or returned an unexpected body"),
+        (status = 500, description = "An upstream could not be reached")
+    )
+)]
+#[tracing::instrument(name = "GET /", skip(state, headers))]
+async fn handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(q): Query<Prefix>,
+) -> Result<impl IntoResponse, StatusCode> {
+    // Callers such as the load generator may start the trace themselves.
+    if let Some(traceparent) = headers.get("traceparent").and_then(|v| v.to_str().ok()) {
+        let mut fields: HashMap<String, String> = HashMap::new();
+        fields.insert("traceparent".to_string(), String::from(traceparent));
+
+        let propagator = TraceContextPropagator::new();
+        let context = propagator.extract(&fields);
+        tracing::Span::current().set_parent(context);
+    }
+    // The weather is not optional, so there is nothing to answer without one.
+    let Some(zip) = q
+        .zip
+        .as_deref()
+        .map(str::trim)
+        .filter(|zip| !zip.is_empty())
+    else {
+        tracing::error!("A zip is required");
+        return Err(StatusCode::BAD_REQUEST);
+    };
+    let service_a_model_response = get_service_a(&state.service_a, q.clone()).await?;
+    let service_c_model_response = get_service_c(&state.service_c, zip).await?;
+    let service_d_model_response = get_service_d(&state.service_d, zip).await?;
+    let forecast = match q.days {
+        Some(days) => Some(get_service_d_forecast(&state.service_d, zip, days).await?),
+        None => None,
+    };
+    let external_model = ExternalModel {
+        key_one: service_a_model_response.key_one,
+        key_two: service_a_model_response.key_two,
+        key_time: service_c_model_response.key_time,
+        weather: service_d_model_response,
+        forecast,
+        local_time: service_c_model_response.local,
+    };
+    Ok(Json(external_model))
+}
+
+/// Asks for the local time at `zip` too. The local time is only a nicety,
+/// so when service-c cannot place `zip` the time is asked for without it.
+#[instrument(name = "http-service-c", skip(upstream), fields(hedged))]
+async fn get_service_c(upstream: &Upstream, zip: &str) -> Result<ServiceCModel, StatusCode> {
+    match upstream.get("/time", &[("zip", zip)]).await {
+        Err(StatusCode::BAD_REQUEST) => {
+            tracing::warn!("No local time for zip={}", zip);
+            upstream.get("/time", &[]).await
+        }
+        result => result,
+    }
+}
+
+#[instrument(name = "http-service-d", skip(upstream), fields(hedged))]
+async fn get_service_d(upstream: &Upstream, zip: &str) -> Result<ServiceDModel, StatusCode> {
+    upstream.get("/weather", &[("zip", zip)]).await
+}
+
+#[instrument(name = "http-service-d-forecast", skip(upstream), fields(hedged))]
+async fn get_service_d_forecast(
+    upstream: &Upstream,
+    zip: &str,
+    days: u8,
+) -> Result<ForecastResponse, StatusCode> {
+    upstream
+        .get("/forecast", &[("zip", zip), ("days", &days.to_string())])
+        .await
+}
+
+#[instrument(name = "http-service-a", skip(upstream), fields(hedged))]
+async fn get_service_a(upstream: &Upstream, q: Prefix) -> Result<ServiceAModel, StatusCode> {
+    let prefix = q.name.unwrap_or_else(|| String::from("Unknown"));
+    upstream.get("/route", &[("p", &prefix)]).await
+}
+
+#[utoipa::path(
+    get,
+    operation_id = "get_health",
+    path = "/health",
+    responses((status = 200, description = "Service is healthy", body = HealthCheck))
+)]
+async fn health() -> Result<impl IntoResponse, StatusCode> {
+    let healthy = HealthCheck {
+        status: String::from("Healthy"),
+    };
+
+    Ok(Json(healthy))
+}
+
+async fn openapi() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::{to_bytes, Body};
+    use axum::http::Request;
+    use contract_testing::{Contract, Interaction, MockProvider};
+    use serde_json::json;
+    use service_common::tls::ClientTlsConfig;
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+    use std::time::Duration;
+    use tower::ServiceExt;
+
+    fn config(upstream: &str) -> Config {
+        Config {
+            bind_address: String::from("127.0.0.1:0"),
+            tracing_enabled: false,
+            agent_address: None,
+            tls: None,
+            faults: None,
+            upstream_tls: ClientTlsConfig::default(),
+            service_a_url: upstream.to_string(),
+            service_c_url: upstream.to_string(),
+            service_d_url: upstream.to_string(),
+            recording: None,
+            service_a_hedge: None,
+            service_c_hedge: None,
+            service_d_hedge: None,
+            service_a_bulkhead: BulkheadConfig::default(),
+            service_c_bulkhead: BulkheadConfig::default(),
+            service_d_bulkhead: BulkheadConfig::default(),
+        }
+    }
+
+    /// One server standing in for service-a, service-c and service-d.
+    async fn upstreams() -> String {
+        let router = Router::new()
+            .route(
+                "/route",
+                get(|| async { Json(json!({"key_one": "one", "key_two": "two"})) }),
+            )
+            .route(
+                "/time",
+                get(|Query(q): Query<HashMap<String, String>>| async move {
+                    match q.get("zip").map(String::as_str) {
+                        Some("09001") => StatusCode::UNPROCESSABLE_ENTITY.into_response(),
+                        Some(_) => Json(json!({
+                            "key_time": "2024-06-01T12:00:00Z",
+                            "local": {
+                                "time_zone": "America/Chicago",
+                                "local_time": "2024-06-01T07:00:00-05:00",
+                                "utc_offset": "-05:00",
+                                "utc_offset_seconds": -18000,
+                                "dst": true,
+                                "abbreviation": "CDT"
+                            }
+                        }))
+                        .into_response(),
+                        None => Json(json!({"key_time": "2024-06-01T12:00:00Z"})).into_response(),
+                    }
+                }),
+            )
+            .route(
+                "/weather",
+                get(|| async {
+                    Json(json!({
+                        "city": "Roanoke",
+                        "state": "Texas",
+                        "celcius": 20.0,
+                        "farenheight": 68.0
+                    }))
+                }),
+            )
+            .route(
+                "/forecast",
+                get(|| async {
+                    Json(json!({
+                        "city": "Roanoke",
+                        "state": "Texas",
+                        "days": [{
+                            "date": "2024-06-01",
+                            "high_celsius": 31.0,
+                            "low_celsius": 21.0,
+                            "high_fahrenheit": 87.8,
+                            "low_fahrenheit": 69.8,
+                            "precipitation_chance": 20,
+                            "condition": "Partly cloudy"
+                        }]
+                    }))
+                }),
+            );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+        url
+    }
+
+    async fn call(router: Router, uri: &str) -> (StatusCode, serde_json::Value) {
+        let request = Request::builder().uri(uri).body(Body::empty()).unwrap();
+        let response = router.oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or_default())
+    }
+
+    fn upstream(provider: &MockProvider) -> Upstream {
+        Upstream::http(reqwest::Client::new(), provider.url())
+    }
+
+    fn prefix(name: Option<&str>, zip: Option<&str>) -> Prefix {
+        Prefix {
+            name: name.map(String::from),
+            zip: zip.map(String::from),
+            days: None,
+        }
+    }
+
+    #[test]
+    fn fake_1() {
+        let s = "one";
+        assert_eq!("one", s);
+    }
+
+    #[test]
+    fn openapi_spec_matches_committed_file() {
+        service_common::openapi::assert_matches_committed(
+            &ApiDoc::openapi().to_pretty_json().unwrap(),
+            concat!(env!("CARGO_MANIFEST_DIR"), "/openapi.json"),
+        );
+    }
+
+    #[tokio::test]
+    async fn service_a_contract() {
+        let contract = Contract::new("service-b", "service-a").interaction(
+            Interaction::new("a request for a named route")
+                .get("/route")
+                .query("p", "Ben")
+                .will_respond_with(
+                    200,
+                    Some(json!({"key_one": "(Ben)Field 1", "key_two": "(Ben)Field 2"})),
+                ),
+        );
+        let provider = MockProvider::start(&contract).await;
+
+        let model = get_service_a(&upstream(&provider), prefix(Some("Ben"), None))
+            .await
+            .unwrap();
+        assert_eq!("(Ben)Field 1", model.key_one);
+        assert_eq!("(Ben)Field 2", model.key_two);
+
+        provider.assert_all_exercised();
+        contract.assert_matches_committed();
+    }
+
+    #[tokio::test]
+    async fn service_c_contract() {
+        let contract = Contract::new("service-b", "service-c")
+            .interaction(
+                Interaction::new("a request for the current time")
+                    .get("/time")
+                    .will_respond_with(200, Some(json!({"key_time": "2024-06-01T12:00:00Z"})))
+                    .match_type("/key_time"),
+            )
+            .interaction(
+                Interaction::new("a request for the local time at a known zip")
+                    .get("/time")
+                    .query("zip", "76262")
+                    .will_respond_with(
+                        200,
+                        Some(json!({
+                            "key_time": "2024-06-01T12:00:00Z",
+                            "local": {
+                                "time_zone": "America/Chicago",
+                                "local_time": "2024-06-01T07:00:00-05:00",
+                                "utc_offset": "-05:00",
+                                "utc_offset_seconds": -18000,
+                                "dst": true,
+                                "abbreviation": "CDT"
+                            }
+                        })),
+                    )
+                    .match_type("/key_time")
+                    .match_type("/local/local_time")
+                    .match_type("/local/utc_offset")
+                    .match_type("/local/utc_offset_seconds")
+                    .match_type("/local/dst")
+                    .match_type("/local/abbreviation"),
+            )
+            .interaction(
+                Interaction::new("a request for the local time at a zip with no known time zone")
+                    .get("/time")
+                    .query("zip", "09001")
+                    .will_respond_with(422, None),
+            );
+        let provider = MockProvider::start(&contract).await;
+        let upstream = upstream(&provider);
+
+        let model = get_service_c(&upstream, "09001").await.unwrap();
+        assert_eq!(
+            "2024-06-01T12:00:00Z",
+            model
+                .key_time
+                .to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+        );
+        assert_eq!(None, model.local);
+
+        let model = get_service_c(&upstream, "76262").await.unwrap();
+        assert_eq!("America/Chicago", model.local.unwrap().time_zone);
+
+        provider.assert_all_exercised();
+        contract.assert_matches_committed();
+    }
+
+    #[tokio::test]
+    async fn service_d_contract() {
+        let contract = Contract::new("service-b", "service-d")
+            .interaction(
+                Interaction::new("a request for the weather at a known zip")
+                    .given("weather for 76262 is available")
+                    .get("/weather")
+                    .query("zip", "76262")
+                    .will_respond_with(
+                        200,
+                        Some(json!({
+                            "city": "Roanoke",
+                            "state": "Texas",
+                            "celcius": 20.0,
+                            "farenheight": 68.0
+                        })),
+                    )
+                    .match_type("/celcius")
+                    .match_type("/farenheight"),
+            )
+            .interaction(
+                Interaction::new("a request for the forecast at a known zip")
+                    .given("a forecast for 76262 is available")
+                    .get("/forecast")
+                    .query("zip", "76262")
+                    .query("days", "1")
+                    .will_respond_with(
+                        200,
+                        Some(json!({
+                            "city": "Roanoke",
+                            "state": "Texas",
+                            "days": [{
+                                "date": "2024-06-01",
+                                "high_celsius": 31.0,
+                                "low_celsius": 21.0,
+                                "high_fahrenheit": 87.8,
+                                "low_fahrenheit": 69.8,
+                                "precipitation_chance": 20,
+                                "condition": "Partly cloudy"
+                            }]
+                        })),
+                    )
+                    .match_type("/days/0/date")
+                    .match_type("/days/0/high_celsius")
+                    .match_type("/days/0/low_celsius")
+                    .match_type("/days/0/high_fahrenheit")
+                    .match_type("/days/0/low_fahrenheit")
+                    .match_type("/days/0/precipitation_chance")
+                    .match_type("/days/0/condition"),
+            )
+            .interaction(
+                Interaction::new("a request for the weather at a location the provider rejects")
+                    .given("the weather provider rejects the location")
+                    .get("/weather")
+                    .query("zip", "00000")
+                    .will_respond_with(400, None),
+            );
+        let provider = MockProvider::start(&contract).await;
+        let upstream = upstream(&provider);
+
+        let model = get_service_d(&upstream, "76262").await.unwrap();
+        assert_eq!("Roanoke", model.city);
+        assert_eq!(68.0, model.farenheight);
+
+        let forecast = get_service_d_forecast(&upstream, "76262", 1).await.unwrap();
+        assert_eq!(1, forecast.days.len());
+        assert_eq!("Roanoke", forecast.city);
+
+        let rejected = get_service_d(&upstream, "00000").await;
+        assert_eq!(StatusCode::BAD_REQUEST, rejected.unwrap_err());
+
+        provider.assert_all_exercised();
+        contract.assert_matches_committed();
+    }
+
+    #[tokio::test]
+    async fn aggregates_the_upstreams() {
+        let router = build_router(&config(&upstreams().await));
+        let (status, body) = call(router, "/?name=Ben&zip=76262").await;
+        assert_eq!(StatusCode::OK, status);
+        assert_eq!(
+            json!({
+                "key_one": "one",
+                "key_two": "two",
+                "key_time": "2024-06-01T12:00:00Z",
+                "weather": {"city": "Roanoke", "state": "Texas", "celcius": 20.0, "farenheight": 68.0},
+                "local_time": {
+                    "time_zone": "America/Chicago",
+                    "local_time": "2024-06-01T07:00:00-05:00",
+                    "utc_offset": "-05:00",
+                    "utc_offset_seconds": -18000,
+                    "dst": true,
+                    "abbreviation": "CDT"
+                }
+            }),
+            body
+        );
+    }
+
+    #[tokio::test]
+    async fn includes_the_local_time_when_service_c_can_place_the_zip() {
+        let router = build_router(&config(&upstreams().await));
+        let (status, body) = call(router, "/?name=Ben&zip=76262-1234").await;
+        assert_eq!(StatusCode::OK, status);
+        assert_eq!("America/Chicago", body["local_time"]["time_zone"]);
+
+        let router = build_router(&config(&upstreams().await));
+        let (status, body) = call(router, "/?name=Ben&zip=09001").await;
+        assert_eq!(StatusCode::OK, status);
+        assert_eq!("2024-06-01T12:00:00Z", body["key_time"]);
+        assert_eq!(None, body.get("local_time"));
+    }
+
+    #[tokio::test]
+    async fn includes_the_forecast_when_days_are_asked_for() {
+        let router = build_router(&config(&upstreams().await));
+        let (status, body) = call(router, "/?name=Ben&zip=76262&days=1").await;
+        assert_eq!(StatusCode::OK, status);
+        assert_eq!("Partly cloudy", body["forecast"]["days"][0]["condition"]);
+    }
+
+    #[tokio::test]
+    async fn hedges_slow_upstreams_when_configured() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let slow_once = Router::new().route(
+            "/route",
+            get(move || async move {
+                if calls.fetch_add(1, Ordering::SeqCst) == 0 {
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                }
+                Json(json!({"key_one": "one", "key_two": "two"}))
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let service_a = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, slow_once).await.unwrap() });
+
+        let config = Config {
+            service_a_url: service_a,
+            service_a_hedge: Some(HedgeConfig {
+                percentile: 95.0,
+                initial_delay: Duration::from_millis(20),
+            }),
+            ..config(&upstreams().await)
+        };
+        let router = build_router(&config);
+        let (status, body) = call(router.clone(), "/?name=Ben&zip=76262").await;
+        assert_eq!(StatusCode::OK, status);
+        assert_eq!("one", body["key_one"]);
+
+        let request = Request::builder()
+            .uri("/metrics")
+            .body(Body::empty())
+            .unwrap();
+        let response = router.oneshot(request).await.unwrap();
+        let metrics = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let metrics = String::from_utf8(metrics.to_vec()).unwrap();
+        assert!(metrics.contains("upstream_hedges_total{upstream=\"service-a\"} 1\n"));
+        assert!(metrics.contains("upstream_hedge_wins_total{upstream=\"service-a\"} 1\n"));
+    }
+
+    #[tokio::test]
+    async fn requires_a_zip() {
+        // Nothing listens here, so any upstream call would answer 500.
+        let router = build_router(&config("http://127.0.0.1:9"));
+        for uri in ["/?name=Ben", "/?name=Ben&zip=%20"] {
+            let request = Request::builder().uri(uri).body(Body::empty()).unwrap();
+            let response = router.clone().oneshot(request).await.unwrap();
+            assert_eq!(StatusCode::BAD_REQUEST, response.status(), "{}", uri);
+        }
+    }
+
+    #[tokio::test]
+    async fn can_be_nested_under_a_prefix() {
+        let router = Router::new().nest("/service-b", build_router(&config("http://127.0.0.1:9")));
+        let (status, body) = call(router, "/service-b/health").await;
+        assert_eq!(StatusCode::OK, status);
+        assert_eq!("Healthy", body["status"]);
+    }
+}
@@ -0,0 +1,53 @@
+//! Process wide counters and gauges, served at `/metrics` in the Prometheus
+//! text format.
+use std::{
+    collections::BTreeMap,
+    sync::{Mutex, OnceLock},
+};
+
+static REGISTRY: OnceLock<Mutex<BTreeMap<String, i64>>> = OnceLock::new();
+
+fn registry() -> &'static Mutex<BTreeMap<String, i64>> {
+    REGISTRY.get_or_init(|| Mutex::new(BTreeMap::new()))
+}
+
+fn key(name: &str, labels: &[(&str, &str)]) -> String {
+    if labels.is_empty() {
+        return name.to_string();
+    }
+    let labels: Vec<String> = labels
+        .iter()
+        .map(|(k, v)| format!("{}=\"{}\"", k, v))
+        .collect();
+    format!("{}{{{}}}", name, labels.join(","))
+}
+
+pub fn add(name: &str, labels: &[(&str, &str)], delta: i64) {
+    *registry()
+        .lock()
+        .unwrap()
+        .entry(key(name, labels))
+        .or_default() += delta;
+}
+
+pub fn increment(name: &str, labels: &[(&str, &str)]) {
+    add(name, labels, 1);
+}
+
+pub fn value(name: &str, labels: &[(&str, &str)]) -> i64 {
+    registry()
+        .lock()
+        .unwrap()
+        .get(&key(name, labels))
+        .copied()
+        .unwrap_or_default()
+}
+
+pub fn render() -> String {
+    registry()
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(key, value)| format!("{} {}\n", key, value))
+        .collect()
+}
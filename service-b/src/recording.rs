@@ -0,0 +1,556 @@
+//! Captures inbound requests together with the upstream responses behind
+//! them, one JSON line per request, so the aggregation can be replayed
+//! offline.
+use std::{
+    collections::BTreeMap,
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use axum::{
+    body::{to_bytes, Body, Bytes},
+    extract::{Request, State},
+    http::{HeaderMap, StatusCode, Uri},
+    middleware::{self, Next},
+    response::Response,
+    Router,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tower::ServiceExt;
+
+use crate::{AppState, Upstream};
+
+const REDACTED: &str = "[REDACTED]";
+const DEFAULT_REDACT: &str = "authorization,cookie";
+
+/// Built from `RECORDING_PATH` and `RECORDING_REDACT`, a comma separated list
+/// of header, query parameter and JSON field names whose values are masked.
+/// The values of redacted query parameters and headers are also masked
+/// wherever else they turn up, e.g. forwarded upstream or echoed in a body.
+#[derive(Clone, Debug)]
+pub struct RecordingConfig {
+    pub path: PathBuf,
+    pub redact: Vec<String>,
+}
+
+impl RecordingConfig {
+    pub fn from_env() -> Option<RecordingConfig> {
+        let path = std::env::var("RECORDING_PATH").ok()?;
+        let redact = std::env::var("RECORDING_REDACT").unwrap_or(String::from(DEFAULT_REDACT));
+        Some(RecordingConfig {
+            path: PathBuf::from(path),
+            redact: redact
+                .split(',')
+                .map(|name| name.trim().to_lowercase())
+                .filter(|name| !name.is_empty())
+                .collect(),
+        })
+    }
+}
+
+/// One upstream call made while serving a recorded request.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Exchange {
+    /// The upstream's name, e.g. `service-d`.
+    pub upstream: String,
+    #[serde(default = "default_method")]
+    pub method: String,
+    pub path_and_query: String,
+    #[serde(default)]
+    pub status: Option<u16>,
+    #[serde(default)]
+    pub body: String,
+    /// The status service-b gave up with when no usable response came back.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<u16>,
+}
+
+/// Recordings made before the method was kept only hold GETs.
+fn default_method() -> String {
+    String::from("GET")
+}
+
+impl Exchange {
+    pub fn path(&self) -> &str {
+        self.path_and_query.split('?').next().unwrap_or_default()
+    }
+
+    /// Whether this is the recorded answer to `method` `path_and_query` on
+    /// `upstream`. A redacted query value matches anything, on either side,
+    /// since replayed requests carry the redacted values of the recording.
+    pub fn answers(&self, upstream: &str, method: &str, path_and_query: &str) -> bool {
+        let split = |pq: &str| {
+            let (path, query) = pq.split_once('?').unwrap_or((pq, ""));
+            let pairs: Vec<(String, String)> = url::form_urlencoded::parse(query.as_bytes())
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect();
+            (path.to_string(), pairs)
+        };
+        let (recorded_path, recorded_query) = split(&self.path_and_query);
+        let (path, query) = split(path_and_query);
+        self.upstream == upstream
+            && self.method.eq_ignore_ascii_case(method)
+            && recorded_path == path
+            && recorded_query.len() == query.len()
+            && recorded_query
+                .iter()
+                .zip(&query)
+                .all(|((rk, rv), (k, v))| rk == k && (rv == v || rv == REDACTED || v == REDACTED))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Recording {
+    pub recorded_at: DateTime<Utc>,
+    pub method: String,
+    pub uri: String,
+    pub headers: BTreeMap<String, String>,
+    pub status: u16,
+    pub body: Value,
+    pub upstream: Vec<Exchange>,
+}
+
+tokio::task_local! {
+    static EXCHANGES: Arc<Mutex<Vec<Exchange>>>;
+}
+
+/// Notes an upstream call against the request being recorded, if any.
+pub(crate) fn capture(
+    upstream: &str,
+    method: &str,
+    path_and_query: &str,
+    response: &Result<(StatusCode, Bytes), StatusCode>,
+) {
+    let exchange = match response {
+        Ok((status, body)) => Exchange {
+            upstream: upstream.to_string(),
+            method: method.to_string(),
+            path_and_query: path_and_query.to_string(),
+            status: Some(status.as_u16()),
+            body: String::from_utf8_lossy(body).to_string(),
+            error: None,
+        },
+        Err(error) => Exchange {
+            upstream: upstream.to_string(),
+            method: method.to_string(),
+            path_and_query: path_and_query.to_string(),
+            status: None,
+            body: String::new(),
+            error: Some(error.as_u16()),
+        },
+    };
+    let _ = EXCHANGES.try_with(|exchanges| exchanges.lock().unwrap().push(exchange));
+}
+
+#[derive(Clone, Debug)]
+pub struct Recorder {
+    file: Arc<Mutex<File>>,
+    redact: Arc<Vec<String>>,
+}
+
+impl Recorder {
+    /// Appends to the file at `config.path`, creating it if needed.
+    pub fn open(config: &RecordingConfig) -> io::Result<Recorder> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&config.path)?;
+        Ok(Recorder {
+            file: Arc::new(Mutex::new(file)),
+            redact: Arc::new(config.redact.clone()),
+        })
+    }
+
+    fn masks(&self, name: &str) -> bool {
+        self.redact.iter().any(|r| r.eq_ignore_ascii_case(name))
+    }
+
+    /// The values of the redacted query parameters and headers of a request,
+    /// longest first so that none is left partly masked by a shorter one.
+    fn secrets(&self, uri: &Uri, headers: &HeaderMap) -> Vec<String> {
+        let query = uri.query().unwrap_or_default();
+        let mut secrets: Vec<String> = url::form_urlencoded::parse(query.as_bytes())
+            .filter(|(k, _)| self.masks(k))
+            .map(|(_, v)| v.to_string())
+            .chain(
+                headers
+                    .iter()
+                    .filter(|(name, _)| self.masks(name.as_str()))
+                    .map(|(_, value)| String::from_utf8_lossy(value.as_bytes()).to_string()),
+            )
+            .filter(|secret| !secret.trim().is_empty())
+            .collect();
+        secrets.sort_by(|a, b| b.len().cmp(&a.len()).then(a.cmp(b)));
+        secrets.dedup();
+        secrets
+    }
+
+    fn redact_query(&self, query: &str, secrets: &[String]) -> String {
+        let pairs = url::form_urlencoded::parse(query.as_bytes()).map(|(k, v)| {
+            let v = if self.masks(&k) {
+                String::from(REDACTED)
+            } else {
+                scrub(&v, secrets)
+            };
+            (k.to_string(), v)
+        });
+        url::form_urlencoded::Serializer::new(String::new())
+            .extend_pairs(pairs)
+            .finish()
+    }
+
+    fn redact_path_and_query(&self, path_and_query: &str, secrets: &[String]) -> String {
+        match path_and_query.split_once('?') {
+            Some((path, query)) => format!(
+                "{}?{}",
+                scrub(path, secrets),
+                self.redact_query(query, secrets)
+            ),
+            None => scrub(path_and_query, secrets),
+        }
+    }
+
+    fn redact_headers(&self, headers: &HeaderMap) -> BTreeMap<String, String> {
+        headers
+            .iter()
+            .map(|(name, value)| {
+                let value = if self.masks(name.as_str()) {
+                    String::from(REDACTED)
+                } else {
+                    String::from_utf8_lossy(value.as_bytes()).to_string()
+                };
+                (name.to_string(), value)
+            })
+            .collect()
+    }
+
+    fn redact_json(&self, value: &mut Value, secrets: &[String]) {
+        match value {
+            Value::Object(fields) => {
+                for (name, field) in fields.iter_mut() {
+                    if self.masks(name) {
+                        *field = Value::String(String::from(REDACTED));
+                    } else {
+                        self.redact_json(field, secrets);
+                    }
+                }
+            }
+            Value::Array(items) => items
+                .iter_mut()
+                .for_each(|item| self.redact_json(item, secrets)),
+            Value::String(text) => *text = scrub(text, secrets),
+            _ => {}
+        }
+    }
+
+    fn redact_body(&self, body: &[u8], secrets: &[String]) -> Value {
+        let mut value = serde_json::from_slice::<Value>(body)
+            .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(body).to_string()));
+        self.redact_json(&mut value, secrets);
+        value
+    }
+
+    fn redact_exchange(&self, mut exchange: Exchange, secrets: &[String]) -> Exchange {
+        exchange.path_and_query = self.redact_path_and_query(&exchange.path_and_query, secrets);
+        exchange.body = match serde_json::from_str::<Value>(&exchange.body) {
+            Ok(mut value) => {
+                self.redact_json(&mut value, secrets);
+                value.to_string()
+            }
+            Err(_) => scrub(&exchange.body, secrets),
+        };
+        exchange
+    }
+
+    fn write(&self, recording: &Recording) -> io::Result<()> {
+        let line = serde_json::to_string(recording)?;
+        writeln!(self.file.lock().unwrap(), "{}", line)
+    }
+}
+
+/// Masks every occurrence of `secrets` in `text`.
+fn scrub(text: &str, secrets: &[String]) -> String {
+    secrets.iter().fold(text.to_string(), |text, secret| {
+        text.replace(secret, REDACTED)
+    })
+}
+
+/// Records every request `router` serves.
+pub fn record(router: Router, recorder: Recorder) -> Router {
+    router.layer(middleware::from_fn_with_state(recorder, record_request))
+}
+
+async fn record_request(
+    State(recorder): State<Recorder>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let method = request.method().to_string();
+    let secrets = recorder.secrets(request.uri(), request.headers());
+    let uri = recorder.redact_path_and_query(&request.uri().to_string(), &secrets);
+    let headers = recorder.redact_headers(request.headers());
+
+    let exchanges = Arc::new(Mutex::new(vec![]));
+    let response = EXCHANGES.scope(exchanges.clone(), next.run(request)).await;
+    let (parts, body) = response.into_parts();
+    let body = match to_bytes(body, usize::MAX).await {
+        Ok(body) => body,
+        Err(e) => {
+            tracing::error!("Error recording response: {}", e);
+            return Response::from_parts(parts, Body::empty());
+        }
+    };
+
+    let upstream = std::mem::take(&mut *exchanges.lock().unwrap());
+    let recording = Recording {
+        recorded_at: Utc::now(),
+        method,
+        uri,
+        headers,
+        status: parts.status.as_u16(),
+        body: recorder.redact_body(&body, &secrets),
+        upstream: upstream
+            .into_iter()
+            .map(|e| recorder.redact_exchange(e, &secrets))
+            .collect(),
+    };
+    if let Err(e) = recorder.write(&recording) {
+        tracing::error!("Error recording: {}", e);
+    }
+    Response::from_parts(parts, Body::from(body))
+}
+
+/// Reads every recording from a file written by [`Recorder`].
+pub fn load(path: &Path) -> io::Result<Vec<Recording>> {
+    std::fs::read_to_string(path)?
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| serde_json::from_str(line).map_err(io::Error::from))
+        .collect()
+}
+
+impl Recording {
+    /// State that answers every upstream call from this recording.
+    pub fn recorded_upstreams(&self) -> AppState {
+        let exchanges = Arc::new(Mutex::new(self.upstream.clone()));
+        AppState {
+            service_a: Upstream::recorded("service-a", exchanges.clone()),
+            service_c: Upstream::recorded("service-c", exchanges.clone()),
+            service_d: Upstream::recorded("service-d", exchanges),
+        }
+    }
+
+    /// Sends the recorded request to `router` and describes any difference
+    /// from the recorded response.
+    pub async fn replay(&self, router: Router) -> Result<(), String> {
+        let mut request = Request::builder()
+            .method(self.method.as_str())
+            .uri(self.uri.parse::<Uri>().map_err(|e| e.to_string())?);
+        for (name, value) in &self.headers {
+            request = request.header(name, value);
+        }
+        let request = request.body(Body::empty()).map_err(|e| e.to_string())?;
+
+        let response = router.oneshot(request).await.map_err(|e| e.to_string())?;
+        let status = response.status().as_u16();
+        let body = to_bytes(response.into_body(), usize::MAX)
+            .await
+            .map_err(|e| e.to_string())?;
+        let body = serde_json::from_slice::<Value>(&body)
+            .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(&body).to_string()));
+
+        if status != self.status {
+            return Err(format!("status {}, recorded {}", status, self.status));
+        }
+        if body != self.body {
+            return Err(format!("body {}, recorded {}", body, self.body));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app;
+    use axum::{http::HeaderValue, routing::get, Json};
+    use serde_json::json;
+
+    fn config(path: &Path) -> RecordingConfig {
+        RecordingConfig {
+            path: path.to_path_buf(),
+            redact: vec![String::from("name"), String::from("authorization")],
+        }
+    }
+
+    fn upstreams() -> AppState {
+        let service_a = Router::new().route(
+            "/route",
+            get(|| async { Json(json!({"key_one": "(Ben)Field 1", "key_two": "(Ben)Field 2"})) }),
+        );
+        let service_c = Router::new().route(
+            "/time",
+            get(|| async { Json(json!({"key_time": "2024-06-01T12:00:00Z"})) }),
+        );
+        let service_d = Router::new().route(
+            "/weather",
+            get(|| async {
+                Json(json!({"city": "Roanoke", "state": "Texas", "celcius": 20.0, "farenheight": 68.0}))
+            }),
+        );
+        AppState {
+            service_a: Upstream::in_process("service-a", service_a),
+            service_c: Upstream::in_process("service-c", service_c),
+            service_d: Upstream::in_process("service-d", service_d),
+        }
+    }
+
+    #[test]
+    fn redacts_configured_names() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let recorder = Recorder::open(&config(file.path())).unwrap();
+
+        assert_eq!(
+            "/?name=%5BREDACTED%5D&zip=76262",
+            recorder.redact_path_and_query("/?name=Ben&zip=76262", &[])
+        );
+        let mut headers = HeaderMap::new();
+        headers.insert("Authorization", HeaderValue::from_static("Bearer secret"));
+        headers.insert("accept", HeaderValue::from_static("*/*"));
+        let headers = recorder.redact_headers(&headers);
+        assert_eq!(REDACTED, headers["authorization"]);
+        assert_eq!("*/*", headers["accept"]);
+        assert_eq!(
+            json!({"user": {"name": REDACTED, "zip": "76262"}, "list": [{"name": REDACTED}]}),
+            recorder.redact_body(
+                br#"{"user": {"name": "Ben", "zip": "76262"}, "list": [{"name": "Ben"}]}"#,
+                &[]
+            )
+        );
+        assert_eq!(json!("not json"), recorder.redact_body(b"not json", &[]));
+    }
+
+    #[test]
+    fn redacts_the_values_of_redacted_names_wherever_they_appear() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let recorder = Recorder::open(&config(file.path())).unwrap();
+        let uri = Uri::from_static("/?name=Ben%20Jones&zip=76262");
+        let mut headers = HeaderMap::new();
+        headers.insert("authorization", HeaderValue::from_static("Bearer secret"));
+        let secrets = recorder.secrets(&uri, &headers);
+        assert_eq!(vec!["Bearer secret", "Ben Jones"], secrets);
+
+        assert_eq!(
+            "/route?p=%5BREDACTED%5D&q=Hi+%5BREDACTED%5D",
+            recorder.redact_path_and_query("/route?p=Ben+Jones&q=Hi+Ben+Jones", &secrets)
+        );
+        assert_eq!(
+            json!({"greeting": "([REDACTED])Field 1"}),
+            recorder.redact_body(br#"{"greeting": "(Ben Jones)Field 1"}"#, &secrets)
+        );
+        assert_eq!(
+            "plain [REDACTED] text",
+            recorder
+                .redact_exchange(exchange("service-a", "/", "plain Ben Jones text"), &secrets)
+                .body
+        );
+    }
+
+    fn exchange(upstream: &str, path_and_query: &str, body: &str) -> Exchange {
+        Exchange {
+            upstream: upstream.to_string(),
+            method: default_method(),
+            path_and_query: path_and_query.to_string(),
+            status: Some(200),
+            body: body.to_string(),
+            error: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn replays_the_exchange_recorded_for_the_same_call() {
+        let exchanges = Arc::new(Mutex::new(vec![
+            exchange(
+                "service-c",
+                "/weather?zip=76262",
+                r#"{"from": "service-c"}"#,
+            ),
+            exchange("service-d", "/weather?zip=10001", r#"{"from": "10001"}"#),
+            exchange("service-d", "/weather?zip=76262", r#"{"from": "76262"}"#),
+            exchange(
+                "service-d",
+                "/weather?zip=%5BREDACTED%5D",
+                r#"{"from": "redacted"}"#,
+            ),
+        ]));
+        let service_d = Upstream::recorded("service-d", exchanges);
+
+        let answer = |zip: &'static str| {
+            let service_d = service_d.clone();
+            async move {
+                service_d
+                    .get::<Value>("/weather", &[("zip", zip)])
+                    .await
+                    .map(|body| body["from"].clone())
+            }
+        };
+        assert_eq!(Ok(json!("76262")), answer("76262").await);
+        assert_eq!(Ok(json!("10001")), answer("10001").await);
+        assert_eq!(Ok(json!("redacted")), answer("76262").await);
+        assert_eq!(
+            Err(StatusCode::INTERNAL_SERVER_ERROR),
+            answer("76262").await
+        );
+    }
+
+    #[tokio::test]
+    async fn replays_recorded_upstream_responses() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let recorder = Recorder::open(&config(file.path())).unwrap();
+        let router = record(app(upstreams()), recorder);
+        let request = axum::http::Request::get("/?name=Ben&zip=76262")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(
+            StatusCode::OK,
+            router.oneshot(request).await.unwrap().status()
+        );
+
+        let recordings = load(file.path()).unwrap();
+        assert_eq!(1, recordings.len());
+        let recording = &recordings[0];
+        assert_eq!("/?name=%5BREDACTED%5D&zip=76262", recording.uri);
+        assert_eq!(
+            vec!["/route", "/time", "/weather"],
+            recording
+                .upstream
+                .iter()
+                .map(|e| e.path())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            "/route?p=%5BREDACTED%5D",
+            recording.upstream[0].path_and_query
+        );
+        assert_eq!("service-a", recording.upstream[0].upstream);
+        let line = std::fs::read_to_string(file.path()).unwrap();
+        assert!(!line.contains("Ben"), "{}", line);
+
+        // Served entirely from the file, with nothing listening upstream.
+        recording
+            .replay(app(recording.recorded_upstreams()))
+            .await
+            .unwrap();
+
+        let mut changed = recording.clone();
+        changed.upstream[2].status = Some(500);
+        assert_eq!(
+            Err(String::from("status 400, recorded 200")),
+            changed.replay(app(changed.recorded_upstreams())).await
+        );
+    }
+}
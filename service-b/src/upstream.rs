@@ -0,0 +1,224 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use axum::{
+    body::{to_bytes, Body, Bytes},
+    http::{HeaderMap, HeaderName, HeaderValue, Request, StatusCode},
+    Router,
+};
+use opentelemetry::propagation::TextMapPropagator;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use reqwest::Client;
+use serde::de::DeserializeOwned;
+use tower::ServiceExt;
+use tracing::Span;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+use crate::{
+    bulkhead::Bulkhead,
+    hedge::Hedge,
+    recording::{self, Exchange},
+};
+
+/// How service-b reaches one of the services it aggregates.
+#[derive(Clone, Debug)]
+pub enum Transport {
+    Http(Client),
+    /// Calls the upstream's router directly when it is hosted in the same
+    /// process. Trace context is still passed in the request headers so the
+    /// upstream's spans hang off the same parents as they would over HTTP.
+    InProcess(Router),
+    /// Answers from exchanges captured by a recording, each used once, in
+    /// the order they were recorded. An exchange only answers the upstream,
+    /// method, path and query it was recorded for.
+    Recorded(Arc<Mutex<Vec<Exchange>>>),
+}
+
+#[derive(Clone, Debug)]
+pub struct Upstream {
+    /// Recorded with each exchange, e.g. `service-d`. The base url unless
+    /// given with `with_name`.
+    pub name: String,
+    pub base_url: String,
+    pub transport: Transport,
+    pub hedge: Option<Hedge>,
+    pub bulkhead: Option<Bulkhead>,
+}
+
+impl Upstream {
+    pub fn http(client: Client, base_url: &str) -> Upstream {
+        Upstream {
+            name: base_url.to_string(),
+            base_url: base_url.to_string(),
+            transport: Transport::Http(client),
+            hedge: None,
+            bulkhead: None,
+        }
+    }
+
+    pub fn in_process(name: &str, router: Router) -> Upstream {
+        Upstream {
+            name: name.to_string(),
+            base_url: format!("in-process://{}", name),
+            transport: Transport::InProcess(router),
+            hedge: None,
+            bulkhead: None,
+        }
+    }
+
+    pub fn recorded(name: &str, exchanges: Arc<Mutex<Vec<Exchange>>>) -> Upstream {
+        Upstream {
+            name: name.to_string(),
+            base_url: format!("recorded://{}", name),
+            transport: Transport::Recorded(exchanges),
+            hedge: None,
+            bulkhead: None,
+        }
+    }
+
+    pub fn with_name(self, name: &str) -> Upstream {
+        Upstream {
+            name: name.to_string(),
+            ..self
+        }
+    }
+
+    pub fn with_hedge(self, hedge: Hedge) -> Upstream {
+        Upstream {
+            hedge: Some(hedge),
+            ..self
+        }
+    }
+
+    pub fn with_bulkhead(self, bulkhead: Bulkhead) -> Upstream {
+        Upstream {
+            bulkhead: Some(bulkhead),
+            ..self
+        }
+    }
+
+    /// Sends a GET carrying the current trace context. Unreachable upstreams
+    /// map to a 500, anything else unexpected to a 400.
+    pub async fn get<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &[(&str, &str)],
+    ) -> Result<T, StatusCode> {
+        let path_and_query = if query.is_empty() {
+            path.to_string()
+        } else {
+            let query = url::form_urlencoded::Serializer::new(String::new())
+                .extend_pairs(query)
+                .finish();
+            format!("{}?{}", path, query)
+        };
+        tracing::info!("(Request)={}{}", self.base_url, path_and_query);
+
+        let response = match &self.hedge {
+            Some(hedge) => {
+                hedge
+                    .run(
+                        || self.isolated_fetch(&path_and_query),
+                        |response| matches!(response, Ok((status, _)) if status.is_success()),
+                    )
+                    .await
+            }
+            None => self.isolated_fetch(&path_and_query).await,
+        };
+        recording::capture(&self.name, "GET", &path_and_query, &response);
+        let (status, body) = response?;
+        if !status.is_success() {
+            tracing::error!("Bad request={:?}", status);
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        serde_json::from_slice(&body).map_err(|e| {
+            tracing::error!("Error parsing: {}", e);
+            StatusCode::BAD_REQUEST
+        })
+    }
+
+    /// Every attempt, hedges included, takes its own slot in the bulkhead.
+    async fn isolated_fetch(
+        &self,
+        path_and_query: &str,
+    ) -> Result<(StatusCode, Bytes), StatusCode> {
+        match &self.bulkhead {
+            Some(bulkhead) => bulkhead.run(self.fetch(path_and_query)).await,
+            None => self.fetch(path_and_query).await,
+        }
+    }
+
+    async fn fetch(&self, path_and_query: &str) -> Result<(StatusCode, Bytes), StatusCode> {
+        match &self.transport {
+            Transport::Http(client) => {
+                let url = format!("{}{}", self.base_url, path_and_query);
+                let response = client
+                    .get(url.as_str())
+                    .headers(trace_headers())
+                    .send()
+                    .await
+                    .map_err(|e| {
+                        tracing::error!("Error requesting: {}", e);
+                        StatusCode::INTERNAL_SERVER_ERROR
+                    })?;
+                let status = response.status();
+                // A body cut off part way is treated like one that won't parse.
+                let body = response.bytes().await.map_err(|e| {
+                    tracing::error!("Error parsing: {}", e);
+                    StatusCode::BAD_REQUEST
+                })?;
+                Ok((status, body))
+            }
+            Transport::InProcess(router) => {
+                let mut request = Request::get(path_and_query).body(Body::empty()).unwrap();
+                *request.headers_mut() = trace_headers();
+                let response = router.clone().oneshot(request).await.unwrap();
+                let status = response.status();
+                let body = to_bytes(response.into_body(), usize::MAX)
+                    .await
+                    .map_err(|e| {
+                        tracing::error!("Error requesting: {}", e);
+                        StatusCode::INTERNAL_SERVER_ERROR
+                    })?;
+                Ok((status, body))
+            }
+            Transport::Recorded(exchanges) => {
+                let mut exchanges = exchanges.lock().unwrap();
+                let Some(i) = exchanges
+                    .iter()
+                    .position(|e| e.answers(&self.name, "GET", path_and_query))
+                else {
+                    tracing::error!("Nothing recorded for {} {}", self.name, path_and_query);
+                    return Err(StatusCode::INTERNAL_SERVER_ERROR);
+                };
+                let exchange = exchanges.remove(i);
+                let status =
+                    |s| StatusCode::from_u16(s).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+                match (exchange.error, exchange.status) {
+                    (Some(error), _) => Err(status(error)),
+                    (None, Some(s)) => Ok((status(s), Bytes::from(exchange.body))),
+                    (None, None) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+                }
+            }
+        }
+    }
+}
+
+fn trace_headers() -> HeaderMap {
+    let ctx = Span::current().context();
+    let propagator = TraceContextPropagator::new();
+    let mut fields = HashMap::new();
+
+    propagator.inject_context(&ctx, &mut fields);
+    fields
+        .into_iter()
+        .map(|(k, v)| {
+            (
+                HeaderName::try_from(k).unwrap(),
+                HeaderValue::try_from(v).unwrap(),
+            )
+        })
+        .collect()
+}
@@ -0,0 +1,204 @@
+//! Writes a time out in the format named by the `format` query parameter.
+//! Custom strftime patterns are checked before use, since chrono panics
+//! while formatting with an invalid one.
+use std::fmt;
+
+use chrono::{
+    format::{Item, StrftimeItems},
+    DateTime, Datelike,
+};
+use chrono_tz::Tz;
+use service_models::v1::service_c::{FormattedTime, TimeComponents};
+
+/// Longer patterns are refused rather than formatted.
+const MAX_PATTERN_LEN: usize = 128;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TimeFormat {
+    Rfc3339,
+    Rfc2822,
+    Unix,
+    UnixMillis,
+    UnixNanos,
+    /// e.g. `2024-W22-6`.
+    IsoWeek,
+    Strftime(String),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum FormatError {
+    Unknown(String),
+    InvalidPattern(String),
+    TooLong,
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FormatError::Unknown(name) => write!(f, "{:?} is not a time format", name),
+            FormatError::InvalidPattern(pattern) => {
+                write!(f, "{:?} is not a valid strftime pattern", pattern)
+            }
+            FormatError::TooLong => {
+                write!(f, "patterns are limited to {} bytes", MAX_PATTERN_LEN)
+            }
+        }
+    }
+}
+
+impl std::error::Error for FormatError {}
+
+impl TimeFormat {
+    /// A format's name, or anything containing `%` as a strftime pattern.
+    pub fn parse(input: &str) -> Result<TimeFormat, FormatError> {
+        match input {
+            "rfc3339" => Ok(TimeFormat::Rfc3339),
+            "rfc2822" => Ok(TimeFormat::Rfc2822),
+            "unix" => Ok(TimeFormat::Unix),
+            "unix_millis" => Ok(TimeFormat::UnixMillis),
+            "unix_nanos" => Ok(TimeFormat::UnixNanos),
+            "iso_week" => Ok(TimeFormat::IsoWeek),
+            pattern if pattern.contains('%') => {
+                if pattern.len() > MAX_PATTERN_LEN {
+                    return Err(FormatError::TooLong);
+                }
+                if StrftimeItems::new(pattern).any(|item| item == Item::Error) {
+                    return Err(FormatError::InvalidPattern(pattern.to_string()));
+                }
+                Ok(TimeFormat::Strftime(pattern.to_string()))
+            }
+            name => Err(FormatError::Unknown(name.to_string())),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            TimeFormat::Rfc3339 => "rfc3339",
+            TimeFormat::Rfc2822 => "rfc2822",
+            TimeFormat::Unix => "unix",
+            TimeFormat::UnixMillis => "unix_millis",
+            TimeFormat::UnixNanos => "unix_nanos",
+            TimeFormat::IsoWeek => "iso_week",
+            TimeFormat::Strftime(pattern) => pattern,
+        }
+    }
+
+    pub fn format(&self, at: DateTime<Tz>) -> FormattedTime {
+        let value = match self {
+            TimeFormat::Rfc3339 => at.to_rfc3339(),
+            TimeFormat::Rfc2822 => at.to_rfc2822(),
+            TimeFormat::Unix => at.timestamp().to_string(),
+            TimeFormat::UnixMillis => at.timestamp_millis().to_string(),
+            // Out of range only after 2262.
+            TimeFormat::UnixNanos => at
+                .timestamp_nanos_opt()
+                .map(|nanos| nanos.to_string())
+                .unwrap_or_default(),
+            TimeFormat::IsoWeek => at.format("%G-W%V-%u").to_string(),
+            TimeFormat::Strftime(pattern) => at.format(pattern).to_string(),
+        };
+        FormattedTime {
+            format: self.name().to_string(),
+            value,
+            components: TimeComponents {
+                year: at.year(),
+                month: at.month(),
+                day: at.day(),
+                weekday: weekday_name(at.weekday()),
+                ordinal: at.ordinal(),
+            },
+        }
+    }
+}
+
+fn weekday_name(weekday: chrono::Weekday) -> String {
+    let name = match weekday {
+        chrono::Weekday::Mon => "Monday",
+        chrono::Weekday::Tue => "Tuesday",
+        chrono::Weekday::Wed => "Wednesday",
+        chrono::Weekday::Thu => "Thursday",
+        chrono::Weekday::Fri => "Friday",
+        chrono::Weekday::Sat => "Saturday",
+        chrono::Weekday::Sun => "Sunday",
+    };
+    name.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(zone: Tz) -> DateTime<Tz> {
+        zone.with_ymd_and_hms(2024, 6, 1, 7, 30, 15).unwrap()
+    }
+
+    fn format(input: &str, zone: Tz) -> String {
+        TimeFormat::parse(input).unwrap().format(at(zone)).value
+    }
+
+    #[test]
+    fn writes_each_named_format() {
+        assert_eq!("2024-06-01T07:30:15+00:00", format("rfc3339", Tz::UTC));
+        assert_eq!(
+            "2024-06-01T07:30:15-05:00",
+            format("rfc3339", Tz::America__Chicago)
+        );
+        assert_eq!(
+            "Sat, 1 Jun 2024 07:30:15 -0500",
+            format("rfc2822", Tz::America__Chicago)
+        );
+        assert_eq!("1717227015", format("unix", Tz::UTC));
+        assert_eq!("1717227015000", format("unix_millis", Tz::UTC));
+        assert_eq!("1717227015000000000", format("unix_nanos", Tz::UTC));
+        assert_eq!("2024-W22-6", format("iso_week", Tz::UTC));
+    }
+
+    #[test]
+    fn writes_strftime_patterns() {
+        assert_eq!(
+            "01/06/2024 07:30 CDT",
+            format("%d/%m/%Y %H:%M %Z", Tz::America__Chicago)
+        );
+        let formatted = TimeFormat::parse("%A").unwrap().format(at(Tz::UTC));
+        assert_eq!("%A", formatted.format);
+        assert_eq!("Saturday", formatted.value);
+    }
+
+    #[test]
+    fn includes_the_components() {
+        let formatted = TimeFormat::Unix.format(at(Tz::Asia__Tokyo));
+        assert_eq!(
+            TimeComponents {
+                year: 2024,
+                month: 6,
+                day: 1,
+                weekday: String::from("Saturday"),
+                ordinal: 153,
+            },
+            formatted.components
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_formats_and_invalid_patterns() {
+        assert_eq!(
+            Err(FormatError::Unknown(String::from("rfc9999"))),
+            TimeFormat::parse("rfc9999")
+        );
+        for pattern in ["%Q", "%Y-%", "%.9"] {
+            assert!(
+                matches!(
+                    TimeFormat::parse(pattern),
+                    Err(FormatError::InvalidPattern(_))
+                ),
+                "{}",
+                pattern
+            );
+        }
+        assert_eq!(
+            Err(FormatError::TooLong),
+            TimeFormat::parse(&"%Y".repeat(MAX_PATTERN_LEN))
+        );
+    }
+}
@@ -0,0 +1,465 @@
+use axum::{
+    extract::{Query, State, WebSocketUpgrade},
+    http::{HeaderMap, StatusCode},
+    response::{
+        sse::{KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    routing::get,
+    Json, Router,
+};
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use format::TimeFormat;
+use opentelemetry::propagation::TextMapPropagator;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use service_common::fault;
+use service_models::v1::{
+    service_c::{
+        ExternalModel, FormattedTime, LocalTime, TimeComponents, TimeQuery, TimeStreamQuery,
+    },
+    HealthCheck,
+};
+use std::collections::HashMap;
+use tracing::instrument;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use utoipa::OpenApi;
+
+mod config;
+mod format;
+mod stream;
+mod zone;
+
+pub use config::Config;
+pub use stream::{StreamConfig, Streams};
+
+#[derive(OpenApi)]
+#[openapi(
+    paths(handler, stream_time, stream_time_ws, health),
+    components(schemas(ExternalModel, LocalTime, FormattedTime, TimeComponents, HealthCheck))
+)]
+struct ApiDoc;
+
+#[derive(Clone, Debug)]
+pub struct AppState {
+    pub has_apm: bool,
+    pub streams: Streams,
+}
+
+impl AppState {
+    pub fn from_config(config: &Config) -> AppState {
+        AppState {
+            has_apm: config.tracing_enabled,
+            streams: Streams::new(config.streams),
+        }
+    }
+}
+
+/// Builds the service's routes with state derived from `config`. The router
+/// can be served on its own or nested under a prefix in another axum app.
+pub fn build_router(config: &Config) -> Router {
+    let app = app(AppState::from_config(config));
+    match &config.faults {
+        Some(faults) => fault::inject(app, faults.clone()),
+        None => app,
+    }
+}
+
+pub fn app(app_state: AppState) -> Router {
+    let app = Router::new()
+        .route("/time", get(handler))
+        .route("/time/stream", get(stream_time))
+        .route("/time/ws", get(stream_time_ws))
+        .route("/", get(health))
+        .route("/openapi.json", get(openapi))
+        .with_state(app_state);
+    #[cfg(feature = "swagger-ui")]
+    let app = app.merge(
+        utoipa_swagger_ui::SwaggerUi::new("/swagger-ui")
+            .config(utoipa_swagger_ui::Config::from("/openapi.json")),
+    );
+    app
+}
+
+#[utoipa::path(
+    get,
+    operation_id = "get_time",
+    path = "/time",
+    params(TimeQuery),
+    responses(
+        (status = 200, description = "The current UTC time, and the local time when a zone, zip or coordinates are given", body = ExternalModel),
+        (status = 400, description = "More than one of tz, zip and lat/lon, or only one of lat and lon"),
+        (status = 422, description = "tz is not an IANA time zone, zip is not a known US zip, lat/lon is out of range, or format is not a format or valid strftime pattern")
+    )
+)]
+#[instrument(name = "GET /time")]
+async fn handler(
+    State(state): State<AppState>,
+    Query(query): Query<TimeQuery>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, axum::http::StatusCode> {
+    let zone = requested_zone(&query)?;
+    let format = requested_format(query.format.as_deref())?;
+    let m = time_at(Utc::now(), zone, format.as_ref());
+
+    continue_trace(&state, &headers);
+    tracing::info!("(Request)={:?}|(Headers)={:?}", m, headers);
+    Ok(Json(m))
+}
+
+#[utoipa::path(
+    get,
+    operation_id = "get_time_stream",
+    path = "/time/stream",
+    params(TimeStreamQuery),
+    responses(
+        (status = 200, description = "Server-Sent Events named tick, each an ExternalModel as JSON, with a heartbeat comment when idle. Ticks a slow client is not ready for are skipped", content_type = "text/event-stream", body = ExternalModel),
+        (status = 422, description = "interval_ms is out of range, tz is not an IANA time zone, or format is not a format or valid strftime pattern"),
+        (status = 503, description = "Every stream connection is in use")
+    )
+)]
+#[instrument(name = "GET /time/stream", skip_all)]
+async fn stream_time(
+    State(state): State<AppState>,
+    Query(query): Query<TimeStreamQuery>,
+    headers: HeaderMap,
+) -> Result<Response, StatusCode> {
+    continue_trace(&state, &headers);
+    let clock = state.streams.open(&query, "sse")?;
+    let keep_alive = KeepAlive::new()
+        .interval(state.streams.config.heartbeat)
+        .text("heartbeat");
+    Ok(Sse::new(stream::events(clock))
+        .keep_alive(keep_alive)
+        .into_response())
+}
+
+#[utoipa::path(
+    get,
+    operation_id = "get_time_ws",
+    path = "/time/ws",
+    params(TimeStreamQuery),
+    responses(
+        (status = 101, description = "A WebSocket sending each tick as an ExternalModel in a text message, and a ping when idle. A client that stops reading for a heartbeat is disconnected"),
+        (status = 422, description = "interval_ms is out of range, tz is not an IANA time zone, or format is not a format or valid strftime pattern"),
+        (status = 503, description = "Every stream connection is in use")
+    )
+)]
+#[instrument(name = "GET /time/ws", skip_all)]
+async fn stream_time_ws(
+    State(state): State<AppState>,
+    Query(query): Query<TimeStreamQuery>,
+    headers: HeaderMap,
+    upgrade: WebSocketUpgrade,
+) -> Result<Response, StatusCode> {
+    continue_trace(&state, &headers);
+    let clock = state.streams.open(&query, "websocket")?;
+    let heartbeat = state.streams.config.heartbeat;
+    Ok(upgrade.on_upgrade(move |socket| stream::websocket(socket, clock, heartbeat)))
+}
+
+/// What `GET /time` answers with at `key_time`.
+fn time_at(
+    key_time: DateTime<Utc>,
+    zone: Option<Tz>,
+    format: Option<&TimeFormat>,
+) -> ExternalModel {
+    ExternalModel {
+        key_time,
+        local: zone.map(|zone| zone::local_time(zone, key_time)),
+        formatted: format
+            .map(|format| format.format(key_time.with_timezone(&zone.unwrap_or(Tz::UTC)))),
+    }
+}
+
+/// Continues the caller's trace when one is passed along.
+fn continue_trace(state: &AppState, headers: &HeaderMap) {
+    let traceparent = headers.get("traceparent").and_then(|v| v.to_str().ok());
+    if let (true, Some(traceparent)) = (state.has_apm, traceparent) {
+        let mut fields: HashMap<String, String> = HashMap::new();
+        fields.insert("traceparent".to_string(), String::from(traceparent));
+
+        let propagator = TraceContextPropagator::new();
+        let context = propagator.extract(&fields);
+        let span = tracing::Span::current();
+        span.set_parent(context);
+    }
+}
+
+fn requested_format(format: Option<&str>) -> Result<Option<TimeFormat>, StatusCode> {
+    match format.map(TimeFormat::parse) {
+        Some(Ok(format)) => Ok(Some(format)),
+        Some(Err(e)) => {
+            tracing::error!("Invalid format: {}", e);
+            Err(StatusCode::UNPROCESSABLE_ENTITY)
+        }
+        None => Ok(None),
+    }
+}
+
+/// The zone `query` names, none when it asks for UTC only.
+fn requested_zone(query: &TimeQuery) -> Result<Option<Tz>, StatusCode> {
+    let zone = match (&query.tz, &query.zip, query.lat, query.lon) {
+        (None, None, None, None) => return Ok(None),
+        (Some(tz), None, None, None) => zone::named(tz),
+        (None, Some(zip), None, None) => zone::for_zip(zip),
+        (None, None, Some(lat), Some(lon)) => zone::for_coordinates(lat, lon),
+        _ => {
+            tracing::error!("Give one of tz, zip or lat and lon");
+            return Err(StatusCode::BAD_REQUEST);
+        }
+    };
+    zone.map(Some).map_err(|e| {
+        tracing::error!("Invalid time zone: {}", e);
+        StatusCode::UNPROCESSABLE_ENTITY
+    })
+}
+
+#[utoipa::path(
+    get,
+    operation_id = "get_health",
+    path = "/",
+    responses((status = 200, description = "Service is healthy", body = HealthCheck))
+)]
+async fn health() -> Result<impl IntoResponse, StatusCode> {
+    let healthy = HealthCheck {
+        status: String::from("Healthy"),
+    };
+
+    Ok(Json(healthy))
+}
+
+async fn openapi() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::{to_bytes, Body};
+    use axum::http::Request;
+    use chrono::Datelike;
+    use contract_testing::{verify, Contract};
+    use futures_util::StreamExt;
+    use tower::ServiceExt;
+
+    fn config() -> Config {
+        Config {
+            bind_address: String::from("127.0.0.1:0"),
+            tracing_enabled: false,
+            agent_address: None,
+            tls: None,
+            faults: None,
+            streams: StreamConfig::default(),
+        }
+    }
+
+    async fn call(router: Router, uri: &str) -> (StatusCode, serde_json::Value) {
+        let request = Request::builder().uri(uri).body(Body::empty()).unwrap();
+        let response = router.oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or_default())
+    }
+
+    #[test]
+    fn fake_1() {
+        let s = "one";
+        assert_eq!("one", s);
+    }
+
+    #[test]
+    fn openapi_spec_matches_committed_file() {
+        service_common::openapi::assert_matches_committed(
+            &ApiDoc::openapi().to_pretty_json().unwrap(),
+            concat!(env!("CARGO_MANIFEST_DIR"), "/openapi.json"),
+        );
+    }
+
+    #[tokio::test]
+    async fn honours_service_b_contract() {
+        let contract = Contract::load("service-b", "service-c").unwrap();
+        let result = verify(&contract, |_| async {
+            app(AppState::from_config(&config()))
+        })
+        .await;
+        if let Err(e) = result {
+            panic!("{}", e);
+        }
+    }
+
+    #[tokio::test]
+    async fn returns_the_current_time() {
+        let before = Utc::now();
+        let (status, body) = call(build_router(&config()), "/time").await;
+        assert_eq!(StatusCode::OK, status);
+
+        let model: ExternalModel = serde_json::from_value(body).unwrap();
+        assert!(model.key_time >= before && model.key_time <= Utc::now());
+    }
+
+    #[tokio::test]
+    async fn returns_the_local_time_of_a_zone_zip_or_coordinates() {
+        for (uri, time_zone) in [
+            ("/time?tz=America/Chicago", "America/Chicago"),
+            ("/time?zip=98101", "America/Los_Angeles"),
+            ("/time?lat=48.85&lon=2.35", "Europe/Paris"),
+        ] {
+            let (status, body) = call(build_router(&config()), uri).await;
+            assert_eq!(StatusCode::OK, status, "{}", uri);
+
+            let model: ExternalModel = serde_json::from_value(body).unwrap();
+            let local = model.local.unwrap();
+            assert_eq!(time_zone, local.time_zone);
+            assert_eq!(model.key_time, local.local_time);
+            assert_eq!(
+                local.utc_offset_seconds,
+                local.local_time.offset().local_minus_utc()
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn rejects_what_does_not_name_one_zone() {
+        for (uri, expected) in [
+            ("/time?tz=America/Gotham", StatusCode::UNPROCESSABLE_ENTITY),
+            ("/time?zip=seattle", StatusCode::UNPROCESSABLE_ENTITY),
+            ("/time?zip=00001", StatusCode::UNPROCESSABLE_ENTITY),
+            ("/time?lat=91&lon=0", StatusCode::UNPROCESSABLE_ENTITY),
+            ("/time?lat=33.0", StatusCode::BAD_REQUEST),
+            ("/time?tz=UTC&zip=76262", StatusCode::BAD_REQUEST),
+            ("/time?lat=north&lon=0", StatusCode::BAD_REQUEST),
+        ] {
+            let (status, _) = call(build_router(&config()), uri).await;
+            assert_eq!(expected, status, "{}", uri);
+        }
+    }
+
+    #[tokio::test]
+    async fn formats_the_time_in_the_requested_zone() {
+        let (status, body) = call(
+            build_router(&config()),
+            "/time?tz=Asia/Tokyo&format=%25Y-%25m-%25dT%25H%3A%25M%20%25Z",
+        )
+        .await;
+        assert_eq!(StatusCode::OK, status);
+
+        let model: ExternalModel = serde_json::from_value(body).unwrap();
+        let local = model.local.unwrap().local_time;
+        let formatted = model.formatted.unwrap();
+        assert_eq!("%Y-%m-%dT%H:%M %Z", formatted.format);
+        assert_eq!(
+            local.format("%Y-%m-%dT%H:%M JST").to_string(),
+            formatted.value
+        );
+        assert_eq!(local.ordinal(), formatted.components.ordinal);
+
+        let (status, body) = call(build_router(&config()), "/time?format=unix").await;
+        assert_eq!(StatusCode::OK, status);
+        let model: ExternalModel = serde_json::from_value(body).unwrap();
+        assert_eq!(
+            model.key_time.timestamp().to_string(),
+            model.formatted.unwrap().value
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_unknown_formats() {
+        for uri in ["/time?format=rfc9999", "/time?format=%25Q"] {
+            let (status, _) = call(build_router(&config()), uri).await;
+            assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, status, "{}", uri);
+        }
+    }
+
+    /// The JSON of each of the first `count` events in an SSE body.
+    async fn ticks(body: Body, count: usize) -> Vec<ExternalModel> {
+        let mut chunks = body.into_data_stream();
+        let mut text = String::new();
+        let mut ticks = Vec::new();
+        while ticks.len() < count {
+            let chunk = chunks.next().await.unwrap().unwrap();
+            text.push_str(std::str::from_utf8(&chunk).unwrap());
+            while let Some((event, rest)) = text.split_once("\n\n") {
+                if let Some(data) = event.lines().find_map(|l| l.strip_prefix("data: ")) {
+                    ticks.push(serde_json::from_str(data).unwrap());
+                }
+                text = rest.to_string();
+            }
+        }
+        ticks
+    }
+
+    #[tokio::test]
+    async fn streams_ticks_as_server_sent_events() {
+        let request = Request::builder()
+            .uri("/time/stream?interval_ms=100&tz=America/Chicago&format=unix")
+            .body(Body::empty())
+            .unwrap();
+        let response = build_router(&config()).oneshot(request).await.unwrap();
+        assert_eq!(StatusCode::OK, response.status());
+        assert_eq!(
+            "text/event-stream",
+            response.headers()["content-type"].to_str().unwrap()
+        );
+
+        let ticks = ticks(response.into_body(), 2).await;
+        assert!(ticks[0].key_time < ticks[1].key_time);
+        let local = ticks[1].local.as_ref().unwrap();
+        assert_eq!("America/Chicago", local.time_zone);
+        assert_eq!(
+            ticks[1].key_time.timestamp().to_string(),
+            ticks[1].formatted.as_ref().unwrap().value
+        );
+    }
+
+    #[tokio::test]
+    async fn streams_ticks_over_a_websocket() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let router = build_router(&config());
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+
+        let url = format!("ws://{}/time/ws?interval_ms=100&tz=Asia/Tokyo", address);
+        let (mut socket, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+        for _ in 0..2 {
+            let message = socket.next().await.unwrap().unwrap();
+            let tick: ExternalModel = serde_json::from_str(message.to_text().unwrap()).unwrap();
+            assert_eq!("Asia/Tokyo", tick.local.unwrap().time_zone);
+        }
+        socket.close(None).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn limits_open_streams() {
+        let router = build_router(&Config {
+            streams: StreamConfig {
+                max_connections: 1,
+                ..Default::default()
+            },
+            ..config()
+        });
+        let request = || {
+            Request::builder()
+                .uri("/time/stream")
+                .body(Body::empty())
+                .unwrap()
+        };
+        let open = router.clone().oneshot(request()).await.unwrap();
+        assert_eq!(StatusCode::OK, open.status());
+
+        let refused = router.clone().oneshot(request()).await.unwrap();
+        assert_eq!(StatusCode::SERVICE_UNAVAILABLE, refused.status());
+
+        drop(open);
+        let reopened = router.oneshot(request()).await.unwrap();
+        assert_eq!(StatusCode::OK, reopened.status());
+    }
+
+    #[tokio::test]
+    async fn can_be_nested_under_a_prefix() {
+        let router = Router::new().nest("/service-c", build_router(&config()));
+        let (status, body) = call(router, "/service-c").await;
+        assert_eq!(StatusCode::OK, status);
+        assert_eq!("Healthy", body["status"]);
+    }
+}
@@ -0,0 +1,285 @@
+//! Pushes the time to clients at the interval they choose, as Server-Sent
+//! Events on `/time/stream` or text messages on the `/time/ws` WebSocket.
+//!
+//! Ticks are never queued for a slow client: a tick that is due while the
+//! previous one is still being written is skipped and counted instead. A
+//! WebSocket that stops reading for a whole heartbeat is closed. Each stream
+//! holds one of a fixed number of connection slots while it is open, and
+//! gets a span recording its ticks when it closes.
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::ws::{Message, WebSocket},
+    http::StatusCode,
+    response::sse::Event,
+};
+use chrono::Utc;
+use chrono_tz::Tz;
+use futures_util::Stream;
+use service_models::v1::service_c::{ExternalModel, TimeStreamQuery};
+use tokio::{
+    sync::{OwnedSemaphorePermit, Semaphore},
+    time::{Interval, MissedTickBehavior},
+};
+use tracing::{field, Span};
+
+use crate::{format::TimeFormat, zone};
+
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(1);
+const MIN_INTERVAL: Duration = Duration::from_millis(100);
+const MAX_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// How many streams may be open at once and how often an idle one is
+/// sent a heartbeat.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StreamConfig {
+    pub max_connections: usize,
+    pub heartbeat: Duration,
+}
+
+impl Default for StreamConfig {
+    fn default() -> Self {
+        StreamConfig {
+            max_connections: 100,
+            heartbeat: Duration::from_secs(15),
+        }
+    }
+}
+
+impl StreamConfig {
+    /// Reads `TIME_STREAM_MAX_CONNECTIONS` and `TIME_STREAM_HEARTBEAT_SECS`,
+    /// keeping the default for whichever is unset.
+    pub fn from_env() -> StreamConfig {
+        let default = StreamConfig::default();
+        let read = |name: &str| {
+            std::env::var(name).ok().map(|v| match v.parse::<u64>() {
+                Ok(n) if n > 0 => n,
+                _ => panic!("{} must be a positive number", name),
+            })
+        };
+        StreamConfig {
+            max_connections: read("TIME_STREAM_MAX_CONNECTIONS")
+                .map(|n| n as usize)
+                .unwrap_or(default.max_connections),
+            heartbeat: read("TIME_STREAM_HEARTBEAT_SECS")
+                .map(Duration::from_secs)
+                .unwrap_or(default.heartbeat),
+        }
+    }
+}
+
+/// The connection slots shared by every stream.
+#[derive(Clone, Debug)]
+pub struct Streams {
+    pub config: StreamConfig,
+    slots: Arc<Semaphore>,
+}
+
+impl Streams {
+    pub fn new(config: StreamConfig) -> Streams {
+        Streams {
+            config,
+            slots: Arc::new(Semaphore::new(config.max_connections)),
+        }
+    }
+
+    /// Takes a slot for a stream of `query`'s ticks. Answers 422 when the
+    /// query is invalid and 503 when every slot is taken.
+    pub fn open(&self, query: &TimeStreamQuery, transport: &str) -> Result<Clock, StatusCode> {
+        let period = query
+            .interval_ms
+            .map(Duration::from_millis)
+            .unwrap_or(DEFAULT_INTERVAL);
+        if !(MIN_INTERVAL..=MAX_INTERVAL).contains(&period) {
+            tracing::error!("Invalid interval: {:?}", period);
+            return Err(StatusCode::UNPROCESSABLE_ENTITY);
+        }
+        let zone = match query.tz.as_deref().map(zone::named) {
+            Some(Ok(zone)) => Some(zone),
+            Some(Err(e)) => {
+                tracing::error!("Invalid time zone: {}", e);
+                return Err(StatusCode::UNPROCESSABLE_ENTITY);
+            }
+            None => None,
+        };
+        let format = crate::requested_format(query.format.as_deref())?;
+        let permit = self.slots.clone().try_acquire_owned().map_err(|_| {
+            tracing::error!(
+                "All {} stream connections are in use",
+                self.config.max_connections
+            );
+            StatusCode::SERVICE_UNAVAILABLE
+        })?;
+
+        let span = tracing::info_span!(
+            "time-stream",
+            transport,
+            interval_ms = period.as_millis() as u64,
+            time_zone = zone.map(|zone| zone.name()).unwrap_or("UTC"),
+            ticks = field::Empty,
+            skipped = field::Empty,
+        );
+        let mut interval = tokio::time::interval(period);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        Ok(Clock {
+            interval,
+            period,
+            zone,
+            format,
+            last: None,
+            ticks: 0,
+            skipped: 0,
+            span,
+            _permit: permit,
+        })
+    }
+}
+
+/// One open stream's ticks. Dropping it frees its connection slot and
+/// closes its span.
+pub struct Clock {
+    interval: Interval,
+    period: Duration,
+    zone: Option<Tz>,
+    format: Option<TimeFormat>,
+    /// When the previous tick was due.
+    last: Option<Instant>,
+    ticks: u64,
+    skipped: u64,
+    span: Span,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl Clock {
+    /// Waits for the next tick. Cancel safe.
+    pub async fn tick(&mut self) -> ExternalModel {
+        let due = self.interval.tick().await.into_std();
+        if let Some(last) = self.last {
+            self.skipped += missed(last, due, self.period);
+        }
+        self.last = Some(due);
+        self.ticks += 1;
+        crate::time_at(Utc::now(), self.zone, self.format.as_ref())
+    }
+
+    pub fn ticks(&self) -> u64 {
+        self.ticks
+    }
+}
+
+impl Drop for Clock {
+    fn drop(&mut self) {
+        // Signed, as unsigned values reach the exporter as strings.
+        self.span.record("ticks", self.ticks as i64);
+        self.span.record("skipped", self.skipped as i64);
+        tracing::info!(parent: &self.span, ticks = self.ticks, skipped = self.skipped, "Stream closed");
+    }
+}
+
+/// Ticks due between `last` and `due` that were skipped.
+fn missed(last: Instant, due: Instant, period: Duration) -> u64 {
+    let elapsed = due.saturating_duration_since(last).as_nanos();
+    (elapsed / period.as_nanos()).saturating_sub(1) as u64
+}
+
+/// `clock`'s ticks as `tick` events numbered from 1.
+pub fn events(clock: Clock) -> impl Stream<Item = Result<Event, axum::Error>> {
+    futures_util::stream::unfold(clock, |mut clock| async move {
+        let tick = clock.tick().await;
+        let event = Event::default()
+            .event("tick")
+            .id(clock.ticks().to_string())
+            .json_data(&tick);
+        Some((event, clock))
+    })
+}
+
+/// Sends `clock`'s ticks as text messages and pings every `heartbeat` until
+/// the client leaves or stops reading.
+pub async fn websocket(mut socket: WebSocket, mut clock: Clock, heartbeat: Duration) {
+    let mut heartbeats = tokio::time::interval_at((Instant::now() + heartbeat).into(), heartbeat);
+    heartbeats.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    loop {
+        let message = tokio::select! {
+            tick = clock.tick() => match serde_json::to_string(&tick) {
+                Ok(text) => Message::Text(text),
+                Err(e) => {
+                    tracing::error!(parent: &clock.span, "Error serializing a tick: {}", e);
+                    break;
+                }
+            },
+            _ = heartbeats.tick() => Message::Ping(Vec::new()),
+            received = socket.recv() => match received {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => continue,
+            },
+        };
+        match tokio::time::timeout(heartbeat, socket.send(message)).await {
+            Ok(Ok(())) => {}
+            Ok(Err(_)) => break,
+            Err(_) => {
+                tracing::warn!(parent: &clock.span, "Closing a stream that stopped reading");
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_the_ticks_skipped_between_two() {
+        let start = Instant::now();
+        let period = Duration::from_millis(100);
+        assert_eq!(0, missed(start, start + period, period));
+        assert_eq!(2, missed(start, start + period * 3, period));
+        assert_eq!(0, missed(start, start, period));
+    }
+
+    #[tokio::test]
+    async fn refuses_streams_past_the_limit() {
+        let streams = Streams::new(StreamConfig {
+            max_connections: 1,
+            ..Default::default()
+        });
+        let query = TimeStreamQuery::default();
+        let first = streams.open(&query, "sse").unwrap();
+        assert_eq!(
+            Some(StatusCode::SERVICE_UNAVAILABLE),
+            streams.open(&query, "sse").err()
+        );
+        drop(first);
+        assert!(streams.open(&query, "sse").is_ok());
+    }
+
+    #[tokio::test]
+    async fn rejects_invalid_queries() {
+        let streams = Streams::new(StreamConfig::default());
+        for query in [
+            TimeStreamQuery {
+                interval_ms: Some(10),
+                ..Default::default()
+            },
+            TimeStreamQuery {
+                tz: Some(String::from("America/Gotham")),
+                ..Default::default()
+            },
+            TimeStreamQuery {
+                format: Some(String::from("%Q")),
+                ..Default::default()
+            },
+        ] {
+            assert_eq!(
+                Some(StatusCode::UNPROCESSABLE_ENTITY),
+                streams.open(&query, "sse").err(),
+                "{:?}",
+                query
+            );
+        }
+    }
+}
@@ -0,0 +1,232 @@
+//! Finds the time zone of a US zip or of coordinates with the bundled
+//! `zones.json`, and reads offsets from the tz database chrono-tz compiles
+//! in, so no lookup leaves the process.
+use std::{fmt, sync::OnceLock};
+
+use chrono::{DateTime, Offset, Utc};
+use chrono_tz::{OffsetComponents, OffsetName, Tz};
+use serde::Deserialize;
+use service_models::v1::service_c::LocalTime;
+
+const BUNDLED_ZONES: &str = include_str!("../zones.json");
+/// Coordinates further than this from every reference place get the
+/// nautical zone of their longitude instead.
+const NEAR_KM: f64 = 800.0;
+
+#[derive(Debug, PartialEq)]
+pub enum ZoneError {
+    UnknownZone(String),
+    InvalidZip(String),
+    /// A well formed zip outside every bundled prefix range.
+    UnknownZip(String),
+    OutOfRange {
+        lat: f64,
+        lon: f64,
+    },
+}
+
+impl fmt::Display for ZoneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ZoneError::UnknownZone(name) => write!(f, "{:?} is not an IANA time zone", name),
+            ZoneError::InvalidZip(zip) => write!(f, "{:?} is not a US zip", zip),
+            ZoneError::UnknownZip(zip) => write!(f, "no time zone is known for zip {:?}", zip),
+            ZoneError::OutOfRange { lat, lon } => {
+                write!(f, "{},{} is outside latitude/longitude range", lat, lon)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ZoneError {}
+
+/// An IANA name such as `America/Chicago`, matched exactly.
+pub fn named(name: &str) -> Result<Tz, ZoneError> {
+    name.trim()
+        .parse()
+        .map_err(|_| ZoneError::UnknownZone(name.to_string()))
+}
+
+/// `76262` or `76262-1234`. Zones are kept per three digit prefix, so zips
+/// near a zone boundary may get the zone of most of their area.
+pub fn for_zip(zip: &str) -> Result<Tz, ZoneError> {
+    let zip = zip.trim();
+    let digits = |s: &str, n: usize| s.len() == n && s.bytes().all(|b| b.is_ascii_digit());
+    let five = match zip.split_once('-') {
+        None if digits(zip, 5) => zip,
+        Some((five, plus_four)) if digits(five, 5) && digits(plus_four, 4) => five,
+        _ => return Err(ZoneError::InvalidZip(zip.to_string())),
+    };
+    let prefix = &five[..3];
+    zones()
+        .zip_prefixes
+        .iter()
+        .find(|range| range.from.as_str() <= prefix && prefix <= range.to.as_str())
+        .map(|range| range.zone)
+        .ok_or_else(|| ZoneError::UnknownZip(zip.to_string()))
+}
+
+/// The zone of the nearest reference place, or for open ocean and places
+/// far from any city, the `Etc/GMT` zone of the longitude.
+pub fn for_coordinates(lat: f64, lon: f64) -> Result<Tz, ZoneError> {
+    if !(-90.0..=90.0).contains(&lat) || !(-180.0..=180.0).contains(&lon) {
+        return Err(ZoneError::OutOfRange { lat, lon });
+    }
+    let nearest = zones()
+        .places
+        .iter()
+        .map(|p| (p, distance_km(lat, lon, p.lat, p.lon)))
+        .filter(|(_, km)| *km <= NEAR_KM)
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(p, _)| p.zone);
+    Ok(nearest.unwrap_or_else(|| nautical(lon)))
+}
+
+/// `Etc/GMT` zones are named with the opposite sign, `Etc/GMT+5` being
+/// five hours behind UTC.
+fn nautical(lon: f64) -> Tz {
+    let hours = (lon / 15.0).round() as i32;
+    let name = match hours {
+        0 => String::from("Etc/GMT"),
+        h if h > 0 => format!("Etc/GMT-{}", h),
+        h => format!("Etc/GMT+{}", -h),
+    };
+    name.parse().expect("every whole hour has an Etc/GMT zone")
+}
+
+/// `at` as the clocks in `zone` show it.
+pub fn local_time(zone: Tz, at: DateTime<Utc>) -> LocalTime {
+    let local = at.with_timezone(&zone);
+    let offset = local.offset();
+    let fixed = offset.fix();
+    LocalTime {
+        time_zone: zone.name().to_string(),
+        local_time: local.fixed_offset(),
+        utc_offset: fixed.to_string(),
+        utc_offset_seconds: fixed.local_minus_utc(),
+        dst: !offset.dst_offset().is_zero(),
+        abbreviation: offset.abbreviation().to_string(),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ZipPrefixes {
+    from: String,
+    to: String,
+    #[serde(deserialize_with = "zone")]
+    zone: Tz,
+}
+
+#[derive(Debug, Deserialize)]
+struct Place {
+    #[serde(deserialize_with = "zone")]
+    zone: Tz,
+    lat: f64,
+    lon: f64,
+}
+
+#[derive(Debug, Deserialize)]
+struct Zones {
+    zip_prefixes: Vec<ZipPrefixes>,
+    places: Vec<Place>,
+}
+
+fn zone<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Tz, D::Error> {
+    let name = String::deserialize(deserializer)?;
+    name.parse().map_err(serde::de::Error::custom)
+}
+
+static ZONES: OnceLock<Zones> = OnceLock::new();
+
+/// The zones compiled into the crate from `zones.json`.
+fn zones() -> &'static Zones {
+    ZONES.get_or_init(|| serde_json::from_str(BUNDLED_ZONES).expect("bundled zones are valid"))
+}
+
+/// Equirectangular approximation, close enough to pick the nearest city.
+fn distance_km(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    const EARTH_RADIUS_KM: f64 = 6371.0;
+    let x = (lon2 - lon1).to_radians() * ((lat1 + lat2) / 2.0).to_radians().cos();
+    let y = (lat2 - lat1).to_radians();
+    (x * x + y * y).sqrt() * EARTH_RADIUS_KM
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn bundled_zones_are_valid() {
+        assert!(!zones().zip_prefixes.is_empty());
+        assert!(!zones().places.is_empty());
+    }
+
+    #[test]
+    fn finds_the_zone_of_a_zip() {
+        assert_eq!(Ok(Tz::America__Chicago), for_zip("76262"));
+        assert_eq!(Ok(Tz::America__Chicago), for_zip(" 76262-1234 "));
+        assert_eq!(Ok(Tz::America__New_York), for_zip("10001"));
+        assert_eq!(Ok(Tz::America__Denver), for_zip("79901"));
+        assert_eq!(Ok(Tz::America__Phoenix), for_zip("85001"));
+        assert_eq!(Ok(Tz::America__Los_Angeles), for_zip("98101"));
+        assert_eq!(Ok(Tz::Pacific__Honolulu), for_zip("96813"));
+        assert!(matches!(for_zip("00001"), Err(ZoneError::UnknownZip(_))));
+        for zip in ["7626", "76262-12", "seattle, wa", ""] {
+            assert!(
+                matches!(for_zip(zip), Err(ZoneError::InvalidZip(_))),
+                "{}",
+                zip
+            );
+        }
+    }
+
+    #[test]
+    fn finds_the_zone_of_coordinates() {
+        assert_eq!(Ok(Tz::America__Chicago), for_coordinates(33.0, -97.23));
+        assert_eq!(Ok(Tz::Europe__Paris), for_coordinates(48.85, 2.35));
+        assert_eq!(Ok(Tz::Etc__GMTPlus10), for_coordinates(0.0, -150.0));
+        assert_eq!(Ok(Tz::Etc__GMT), for_coordinates(-60.0, 0.0));
+        assert!(matches!(
+            for_coordinates(91.0, 0.0),
+            Err(ZoneError::OutOfRange { .. })
+        ));
+    }
+
+    #[test]
+    fn names_zones_exactly() {
+        assert_eq!(Ok(Tz::America__Chicago), named("America/Chicago"));
+        assert!(matches!(
+            named("America/Gotham"),
+            Err(ZoneError::UnknownZone(_))
+        ));
+    }
+
+    #[test]
+    fn describes_daylight_saving_time() {
+        let summer = local_time(
+            Tz::America__Chicago,
+            Utc.with_ymd_and_hms(2024, 6, 1, 17, 0, 0).unwrap(),
+        );
+        assert_eq!("2024-06-01T12:00:00-05:00", summer.local_time.to_rfc3339());
+        assert_eq!("-05:00", summer.utc_offset);
+        assert_eq!(-18000, summer.utc_offset_seconds);
+        assert!(summer.dst);
+        assert_eq!("CDT", summer.abbreviation);
+
+        let winter = local_time(
+            Tz::America__Chicago,
+            Utc.with_ymd_and_hms(2024, 12, 1, 18, 0, 0).unwrap(),
+        );
+        assert_eq!("-06:00", winter.utc_offset);
+        assert!(!winter.dst);
+        assert_eq!("CST", winter.abbreviation);
+
+        let phoenix = local_time(
+            Tz::America__Phoenix,
+            Utc.with_ymd_and_hms(2024, 6, 1, 17, 0, 0).unwrap(),
+        );
+        assert!(!phoenix.dst);
+        assert_eq!("MST", phoenix.abbreviation);
+    }
+}
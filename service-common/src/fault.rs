@@ -0,0 +1,301 @@
+use std::{
+    collections::HashMap,
+    io,
+    str::ParseBoolError,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    http::{header, HeaderMap, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use opentelemetry::propagation::TextMapPropagator;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use serde::{Deserialize, Serialize};
+use tracing::Instrument;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+pub const ADMIN_PATH: &str = "/admin/faults";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Fault {
+    /// Holds the request for `ms` before handing it on.
+    Latency { ms: u64 },
+    /// Answers with `status` without calling the handler.
+    Abort { status: u16 },
+    /// Sends the headers then fails the body, so the connection is cut.
+    Drop,
+    /// Truncates the handler's response body.
+    Corrupt,
+}
+
+impl Fault {
+    fn name(&self) -> &'static str {
+        match self {
+            Fault::Latency { .. } => "latency",
+            Fault::Abort { .. } => "abort",
+            Fault::Drop => "drop",
+            Fault::Corrupt => "corrupt",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FaultRule {
+    /// Path prefixes the rule applies to, every route when empty.
+    #[serde(default)]
+    pub routes: Vec<String>,
+    #[serde(default = "always")]
+    pub percentage: f64,
+    pub fault: Fault,
+}
+
+fn always() -> f64 {
+    100.0
+}
+
+impl FaultRule {
+    fn matches(&self, path: &str) -> bool {
+        self.routes.is_empty() || self.routes.iter().any(|r| path.starts_with(r.as_str()))
+    }
+}
+
+/// The fault rules a service is running with. Cloning shares the rules, so
+/// changes made through the admin endpoint apply everywhere.
+#[derive(Clone, Debug, Default)]
+pub struct FaultInjector {
+    rules: Arc<RwLock<Vec<FaultRule>>>,
+}
+
+impl FaultInjector {
+    pub fn new(rules: Vec<FaultRule>) -> FaultInjector {
+        FaultInjector {
+            rules: Arc::new(RwLock::new(rules)),
+        }
+    }
+
+    /// Built when `FAULT_INJECTION_ENABLED` is true, starting from the JSON
+    /// array of rules in `FAULT_RULES`, if any.
+    pub fn from_env() -> Option<FaultInjector> {
+        let enabled: Result<bool, ParseBoolError> = std::env::var("FAULT_INJECTION_ENABLED")
+            .unwrap_or_default()
+            .parse();
+        if !enabled.unwrap_or_default() {
+            return None;
+        }
+        let rules = match std::env::var("FAULT_RULES") {
+            Ok(rules) => serde_json::from_str(&rules).expect("FAULT_RULES must be a list of rules"),
+            Err(_) => vec![],
+        };
+        Some(FaultInjector::new(rules))
+    }
+
+    pub fn rules(&self) -> Vec<FaultRule> {
+        self.rules.read().unwrap().clone()
+    }
+
+    pub fn set_rules(&self, rules: Vec<FaultRule>) {
+        *self.rules.write().unwrap() = rules;
+    }
+
+    fn pick(&self, path: &str) -> Option<Fault> {
+        self.rules
+            .read()
+            .unwrap()
+            .iter()
+            .find(|r| r.matches(path) && rand::random::<f64>() * 100.0 < r.percentage)
+            .map(|r| r.fault.clone())
+    }
+}
+
+/// Runs `router` behind `injector` and mounts the admin endpoint, which is
+/// itself left alone by the faults.
+pub fn inject(router: Router, injector: FaultInjector) -> Router {
+    let admin = Router::new()
+        .route(
+            ADMIN_PATH,
+            get(get_rules).put(put_rules).delete(delete_rules),
+        )
+        .with_state(injector.clone());
+    router
+        .layer(middleware::from_fn_with_state(injector, apply))
+        .merge(admin)
+}
+
+async fn apply(State(injector): State<FaultInjector>, request: Request, next: Next) -> Response {
+    let path = request.uri().path().to_string();
+    let Some(fault) = injector.pick(&path) else {
+        return next.run(request).await;
+    };
+
+    let span = tracing::info_span!("fault-injection", fault = fault.name(), path = path);
+    span.set_parent(caller_context(request.headers()));
+    async move {
+        tracing::warn!(fault = fault.name(), "Injecting {:?}", fault);
+        match fault {
+            Fault::Latency { ms } => {
+                tokio::time::sleep(Duration::from_millis(ms)).await;
+                next.run(request).await
+            }
+            Fault::Abort { status } => StatusCode::from_u16(status)
+                .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+                .into_response(),
+            Fault::Drop => {
+                let body = futures_util::stream::once(async {
+                    Err::<&[u8], _>(io::Error::new(
+                        io::ErrorKind::ConnectionAborted,
+                        "injected fault",
+                    ))
+                });
+                Response::new(Body::from_stream(body))
+            }
+            Fault::Corrupt => {
+                let (mut parts, body) = next.run(request).await.into_parts();
+                let bytes = to_bytes(body, usize::MAX).await.unwrap_or_default();
+                parts.headers.remove(header::CONTENT_LENGTH);
+                Response::from_parts(parts, Body::from(bytes.slice(..bytes.len() / 2)))
+            }
+        }
+    }
+    .instrument(span)
+    .await
+}
+
+fn caller_context(headers: &HeaderMap) -> opentelemetry::Context {
+    let fields: HashMap<String, String> = headers
+        .get("traceparent")
+        .and_then(|v| v.to_str().ok())
+        .map(|v| HashMap::from([(String::from("traceparent"), String::from(v))]))
+        .unwrap_or_default();
+    TraceContextPropagator::new().extract(&fields)
+}
+
+async fn get_rules(State(injector): State<FaultInjector>) -> Json<Vec<FaultRule>> {
+    Json(injector.rules())
+}
+
+async fn put_rules(
+    State(injector): State<FaultInjector>,
+    Json(rules): Json<Vec<FaultRule>>,
+) -> StatusCode {
+    injector.set_rules(rules);
+    StatusCode::NO_CONTENT
+}
+
+async fn delete_rules(State(injector): State<FaultInjector>) -> StatusCode {
+    injector.set_rules(vec![]);
+    StatusCode::NO_CONTENT
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+    use tower::ServiceExt;
+
+    fn faulty(rules: Vec<FaultRule>) -> (Router, FaultInjector) {
+        let injector = FaultInjector::new(rules);
+        let router = Router::new()
+            .route("/route", get(|| async { Json(vec!["one", "two"]) }))
+            .route("/health", get(|| async { "Healthy" }));
+        (inject(router, injector.clone()), injector)
+    }
+
+    fn rule(routes: &[&str], percentage: f64, fault: Fault) -> FaultRule {
+        FaultRule {
+            routes: routes.iter().map(|r| r.to_string()).collect(),
+            percentage,
+            fault,
+        }
+    }
+
+    async fn call(router: Router, request: Request) -> (StatusCode, Result<Vec<u8>, String>) {
+        let response = router.oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX)
+            .await
+            .map(|b| b.to_vec())
+            .map_err(|e| e.to_string());
+        (status, body)
+    }
+
+    fn get_request(uri: &str) -> Request {
+        Request::get(uri).body(Body::empty()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn faults_only_hit_matching_routes() {
+        let (router, _) = faulty(vec![rule(&["/route"], 100.0, Fault::Abort { status: 503 })]);
+
+        assert_eq!(
+            StatusCode::SERVICE_UNAVAILABLE,
+            call(router.clone(), get_request("/route")).await.0
+        );
+        assert_eq!(StatusCode::OK, call(router, get_request("/health")).await.0);
+    }
+
+    #[tokio::test]
+    async fn zero_percent_never_fires() {
+        let (router, _) = faulty(vec![rule(&[], 0.0, Fault::Abort { status: 500 })]);
+
+        for _ in 0..20 {
+            assert_eq!(
+                StatusCode::OK,
+                call(router.clone(), get_request("/route")).await.0
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn applies_each_kind_of_fault() {
+        let (router, _) = faulty(vec![rule(&[], 100.0, Fault::Latency { ms: 50 })]);
+        let started = Instant::now();
+        assert_eq!(StatusCode::OK, call(router, get_request("/route")).await.0);
+        assert!(started.elapsed() >= Duration::from_millis(50));
+
+        let (router, _) = faulty(vec![rule(&[], 100.0, Fault::Corrupt)]);
+        let (status, body) = call(router, get_request("/route")).await;
+        assert_eq!(StatusCode::OK, status);
+        assert!(serde_json::from_slice::<Vec<String>>(&body.unwrap()).is_err());
+
+        let (router, _) = faulty(vec![rule(&[], 100.0, Fault::Drop)]);
+        assert!(call(router, get_request("/route")).await.1.is_err());
+    }
+
+    #[tokio::test]
+    async fn rules_can_be_changed_through_the_admin_endpoint() {
+        let (router, injector) = faulty(vec![]);
+        let request = Request::put(ADMIN_PATH)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(
+                r#"[{"routes": ["/route"], "fault": {"type": "abort", "status": 502}}]"#,
+            ))
+            .unwrap();
+        assert_eq!(
+            StatusCode::NO_CONTENT,
+            call(router.clone(), request).await.0
+        );
+        assert_eq!(
+            vec![rule(&["/route"], 100.0, Fault::Abort { status: 502 })],
+            injector.rules()
+        );
+        assert_eq!(
+            StatusCode::BAD_GATEWAY,
+            call(router.clone(), get_request("/route")).await.0
+        );
+
+        let request = Request::delete(ADMIN_PATH).body(Body::empty()).unwrap();
+        assert_eq!(
+            StatusCode::NO_CONTENT,
+            call(router.clone(), request).await.0
+        );
+        assert_eq!(StatusCode::OK, call(router, get_request("/route")).await.0);
+    }
+}
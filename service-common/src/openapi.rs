@@ -0,0 +1,17 @@
+//! Keeps each service's committed `openapi.json`, and the schema snapshots
+//! of service-models, in step with the code.
+
+/// Asserts `spec`, a pretty printed OpenAPI document or schema, matches the
+/// file at `path`. Rewrites the file first when `UPDATE_OPENAPI` is set.
+pub fn assert_matches_committed(spec: &str, path: &str) {
+    if std::env::var("UPDATE_OPENAPI").is_ok() {
+        std::fs::write(path, format!("{}\n", spec)).unwrap();
+    }
+    let committed = std::fs::read_to_string(path).unwrap();
+    assert_eq!(
+        committed.trim_end(),
+        spec,
+        "{} is stale, regenerate it with UPDATE_OPENAPI=1 cargo test",
+        path
+    );
+}
@@ -0,0 +1,408 @@
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::{Duration, SystemTime},
+};
+
+use axum::Router;
+use hyper_util::{
+    rt::{TokioExecutor, TokioIo},
+    server::conn::auto,
+    service::TowerToHyperService,
+};
+use rustls::{
+    pki_types::{CertificateDer, PrivateKeyDer},
+    server::WebPkiClientVerifier,
+    RootCertStore, ServerConfig,
+};
+use tokio::net::TcpListener;
+use tokio_rustls::TlsAcceptor;
+
+const DEFAULT_RELOAD_INTERVAL_SECS: u64 = 30;
+const DEFAULT_HANDSHAKE_TIMEOUT_SECS: u64 = 10;
+
+/// Server side TLS settings. Built from `TLS_CERT_PATH`, `TLS_KEY_PATH`,
+/// `TLS_CLIENT_CA_PATH`, `TLS_RELOAD_INTERVAL_SECS` and
+/// `TLS_HANDSHAKE_TIMEOUT_SECS`.
+#[derive(Clone, Debug)]
+pub struct TlsConfig {
+    pub cert_path: PathBuf,
+    pub key_path: PathBuf,
+    pub client_ca_path: Option<PathBuf>,
+    pub reload_interval: Duration,
+    /// Connections that have not finished the handshake by then are dropped.
+    pub handshake_timeout: Duration,
+}
+
+/// Settings for outbound calls to TLS enabled peers. Built from
+/// `UPSTREAM_CA_BUNDLE_PATH`, `UPSTREAM_CERT_PATH` and `UPSTREAM_KEY_PATH`.
+#[derive(Clone, Debug, Default)]
+pub struct ClientTlsConfig {
+    pub ca_bundle_path: Option<PathBuf>,
+    pub cert_path: Option<PathBuf>,
+    pub key_path: Option<PathBuf>,
+}
+
+#[derive(Debug)]
+pub enum TlsError {
+    Io(PathBuf, io::Error),
+    NoPrivateKey(PathBuf),
+    Rustls(rustls::Error),
+    ClientVerifier(String),
+    Client(reqwest::Error),
+}
+
+impl fmt::Display for TlsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TlsError::Io(path, e) => write!(f, "error reading {}: {}", path.display(), e),
+            TlsError::NoPrivateKey(path) => write!(f, "no private key in {}", path.display()),
+            TlsError::Rustls(e) => write!(f, "invalid certificate: {}", e),
+            TlsError::ClientVerifier(e) => write!(f, "invalid client CA: {}", e),
+            TlsError::Client(e) => write!(f, "error building http client: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for TlsError {}
+
+impl TlsConfig {
+    /// Returns `None` when `TLS_CERT_PATH` is not set, in which case the
+    /// service listens on plain HTTP.
+    pub fn from_env() -> Option<TlsConfig> {
+        let cert_path = std::env::var("TLS_CERT_PATH").ok()?;
+        let key_path = std::env::var("TLS_KEY_PATH").expect("TLS_KEY_PATH is required");
+        let reload_interval = std::env::var("TLS_RELOAD_INTERVAL_SECS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(DEFAULT_RELOAD_INTERVAL_SECS);
+        let handshake_timeout = std::env::var("TLS_HANDSHAKE_TIMEOUT_SECS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(DEFAULT_HANDSHAKE_TIMEOUT_SECS);
+
+        Some(TlsConfig {
+            cert_path: PathBuf::from(cert_path),
+            key_path: PathBuf::from(key_path),
+            client_ca_path: std::env::var("TLS_CLIENT_CA_PATH").ok().map(PathBuf::from),
+            reload_interval: Duration::from_secs(reload_interval),
+            handshake_timeout: Duration::from_secs(handshake_timeout),
+        })
+    }
+
+    fn server_config(&self) -> Result<ServerConfig, TlsError> {
+        let certs = read_certs(&self.cert_path)?;
+        let key = read_key(&self.key_path)?;
+        let builder = ServerConfig::builder();
+        let builder = match &self.client_ca_path {
+            Some(path) => {
+                let mut roots = RootCertStore::empty();
+                for cert in read_certs(path)? {
+                    roots.add(cert).map_err(TlsError::Rustls)?;
+                }
+                let verifier = WebPkiClientVerifier::builder(Arc::new(roots))
+                    .build()
+                    .map_err(|e| TlsError::ClientVerifier(e.to_string()))?;
+                builder.with_client_cert_verifier(verifier)
+            }
+            None => builder.with_no_client_auth(),
+        };
+        let mut config = builder
+            .with_single_cert(certs, key)
+            .map_err(TlsError::Rustls)?;
+        config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+        Ok(config)
+    }
+
+    fn fingerprint(&self) -> Vec<Option<SystemTime>> {
+        [
+            Some(&self.cert_path),
+            Some(&self.key_path),
+            self.client_ca_path.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+        .map(|p| fs::metadata(p).and_then(|m| m.modified()).ok())
+        .collect()
+    }
+}
+
+impl ClientTlsConfig {
+    pub fn from_env() -> ClientTlsConfig {
+        ClientTlsConfig {
+            ca_bundle_path: std::env::var("UPSTREAM_CA_BUNDLE_PATH")
+                .ok()
+                .map(PathBuf::from),
+            cert_path: std::env::var("UPSTREAM_CERT_PATH").ok().map(PathBuf::from),
+            key_path: std::env::var("UPSTREAM_KEY_PATH").ok().map(PathBuf::from),
+        }
+    }
+
+    /// Builds a `reqwest::Client` that trusts the CA bundle (in addition to
+    /// the built in roots) and presents a client certificate when both a
+    /// certificate and key are configured.
+    pub fn http_client(&self) -> Result<reqwest::Client, TlsError> {
+        let mut builder = reqwest::Client::builder().use_rustls_tls();
+        if let Some(path) = &self.ca_bundle_path {
+            let pem = read_file(path)?;
+            for cert in reqwest::Certificate::from_pem_bundle(&pem).map_err(TlsError::Client)? {
+                builder = builder.add_root_certificate(cert);
+            }
+        }
+        if let (Some(cert_path), Some(key_path)) = (&self.cert_path, &self.key_path) {
+            let mut pem = read_file(cert_path)?;
+            pem.extend(read_file(key_path)?);
+            let identity = reqwest::Identity::from_pem(&pem).map_err(TlsError::Client)?;
+            builder = builder.identity(identity);
+        }
+        builder.build().map_err(TlsError::Client)
+    }
+}
+
+/// Serves `app` on `listener`, terminating TLS when a config is supplied.
+pub async fn serve(listener: TcpListener, app: Router, tls: Option<TlsConfig>) -> io::Result<()> {
+    match tls {
+        Some(config) => serve_tls(listener, app, config).await,
+        None => axum::serve(listener, app).await,
+    }
+}
+
+async fn serve_tls(listener: TcpListener, app: Router, config: TlsConfig) -> io::Result<()> {
+    let server_config = config.server_config().map_err(io::Error::other)?;
+    let handshake_timeout = config.handshake_timeout;
+    let current = Arc::new(RwLock::new(Arc::new(server_config)));
+    tokio::spawn(watch(config, current.clone()));
+
+    loop {
+        let (stream, remote) = match listener.accept().await {
+            Ok(c) => c,
+            Err(e) => {
+                tracing::error!("Error accepting: {}", e);
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                continue;
+            }
+        };
+        let acceptor = TlsAcceptor::from(current.read().unwrap().clone());
+        let app = app.clone();
+        tokio::spawn(async move {
+            let stream =
+                match tokio::time::timeout(handshake_timeout, acceptor.accept(stream)).await {
+                    Ok(Ok(s)) => s,
+                    Ok(Err(e)) => {
+                        tracing::warn!("TLS handshake with {} failed: {}", remote, e);
+                        return;
+                    }
+                    Err(_) => {
+                        tracing::warn!("TLS handshake with {} timed out", remote);
+                        return;
+                    }
+                };
+            let service = TowerToHyperService::new(app);
+            if let Err(e) = auto::Builder::new(TokioExecutor::new())
+                .serve_connection_with_upgrades(TokioIo::new(stream), service)
+                .await
+            {
+                tracing::debug!("Error serving {}: {}", remote, e);
+            }
+        });
+    }
+}
+
+/// Polls the certificate files and swaps in a fresh `ServerConfig` when any
+/// of them change. New connections pick up the new config; a bad file keeps
+/// the previous one in place.
+async fn watch(config: TlsConfig, current: Arc<RwLock<Arc<ServerConfig>>>) {
+    let mut last = config.fingerprint();
+    let mut interval = tokio::time::interval(config.reload_interval);
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        let fingerprint = config.fingerprint();
+        if fingerprint == last {
+            continue;
+        }
+        match config.server_config() {
+            Ok(server_config) => {
+                *current.write().unwrap() = Arc::new(server_config);
+                last = fingerprint;
+                tracing::info!(
+                    "Reloaded TLS certificate from {}",
+                    config.cert_path.display()
+                );
+            }
+            Err(e) => tracing::error!("Error reloading TLS certificate: {}", e),
+        }
+    }
+}
+
+fn read_file(path: &Path) -> Result<Vec<u8>, TlsError> {
+    fs::read(path).map_err(|e| TlsError::Io(path.to_path_buf(), e))
+}
+
+fn read_certs(path: &Path) -> Result<Vec<CertificateDer<'static>>, TlsError> {
+    let pem = read_file(path)?;
+    rustls_pemfile::certs(&mut pem.as_slice())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| TlsError::Io(path.to_path_buf(), e))
+}
+
+fn read_key(path: &Path) -> Result<PrivateKeyDer<'static>, TlsError> {
+    let pem = read_file(path)?;
+    rustls_pemfile::private_key(&mut pem.as_slice())
+        .map_err(|e| TlsError::Io(path.to_path_buf(), e))?
+        .ok_or_else(|| TlsError::NoPrivateKey(path.to_path_buf()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::routing::get;
+    use rcgen::{BasicConstraints, Certificate, CertificateParams, ExtendedKeyUsagePurpose, IsCa};
+
+    struct Pki {
+        ca: Certificate,
+    }
+
+    impl Pki {
+        fn new() -> Pki {
+            let mut params = CertificateParams::new(vec![]);
+            params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+            Pki {
+                ca: Certificate::from_params(params).unwrap(),
+            }
+        }
+
+        fn ca_pem(&self) -> String {
+            self.ca.serialize_pem().unwrap()
+        }
+
+        fn issue(&self, name: &str, usage: ExtendedKeyUsagePurpose) -> (String, String) {
+            let mut params = CertificateParams::new(vec![name.to_string()]);
+            params.extended_key_usages = vec![usage];
+            let cert = Certificate::from_params(params).unwrap();
+            (
+                cert.serialize_pem_with_signer(&self.ca).unwrap(),
+                cert.serialize_private_key_pem(),
+            )
+        }
+    }
+
+    async fn start(config: TlsConfig) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let app = Router::new().route("/health", get(|| async { "Healthy" }));
+        tokio::spawn(serve(listener, app, Some(config)));
+        format!("https://localhost:{}/health", port)
+    }
+
+    fn write(dir: &Path, name: &str, contents: &str) -> PathBuf {
+        let path = dir.join(name);
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[tokio::test]
+    async fn requires_client_certificate_when_client_ca_configured() {
+        let dir = tempfile::tempdir().unwrap();
+        let pki = Pki::new();
+        let (cert, key) = pki.issue("localhost", ExtendedKeyUsagePurpose::ServerAuth);
+        let (client_cert, client_key) = pki.issue("service-b", ExtendedKeyUsagePurpose::ClientAuth);
+        let ca_path = write(dir.path(), "ca.pem", &pki.ca_pem());
+        let url = start(TlsConfig {
+            cert_path: write(dir.path(), "cert.pem", &cert),
+            key_path: write(dir.path(), "key.pem", &key),
+            client_ca_path: Some(ca_path.clone()),
+            reload_interval: Duration::from_secs(30),
+            handshake_timeout: Duration::from_secs(10),
+        })
+        .await;
+
+        let anonymous = ClientTlsConfig {
+            ca_bundle_path: Some(ca_path.clone()),
+            ..Default::default()
+        };
+        assert!(anonymous
+            .http_client()
+            .unwrap()
+            .get(&url)
+            .send()
+            .await
+            .is_err());
+
+        let identified = ClientTlsConfig {
+            ca_bundle_path: Some(ca_path),
+            cert_path: Some(write(dir.path(), "client.pem", &client_cert)),
+            key_path: Some(write(dir.path(), "client-key.pem", &client_key)),
+        };
+        let response = identified
+            .http_client()
+            .unwrap()
+            .get(&url)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!("Healthy", response.text().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn drops_connections_that_never_finish_the_handshake() {
+        use tokio::io::AsyncReadExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let (cert, key) = Pki::new().issue("localhost", ExtendedKeyUsagePurpose::ServerAuth);
+        let url = start(TlsConfig {
+            cert_path: write(dir.path(), "cert.pem", &cert),
+            key_path: write(dir.path(), "key.pem", &key),
+            client_ca_path: None,
+            reload_interval: Duration::from_secs(30),
+            handshake_timeout: Duration::from_millis(100),
+        })
+        .await;
+
+        let port = reqwest::Url::parse(&url).unwrap().port().unwrap();
+        let mut stream = tokio::net::TcpStream::connect(("127.0.0.1", port))
+            .await
+            .unwrap();
+        let read = tokio::time::timeout(Duration::from_secs(5), stream.read(&mut [0; 1]))
+            .await
+            .expect("the connection was still open after 5s");
+        assert!(matches!(read, Ok(0) | Err(_)), "{:?}", read);
+    }
+
+    #[tokio::test]
+    async fn reloads_certificate_when_files_change() {
+        let dir = tempfile::tempdir().unwrap();
+        let old_pki = Pki::new();
+        let (cert, key) = old_pki.issue("localhost", ExtendedKeyUsagePurpose::ServerAuth);
+        let cert_path = write(dir.path(), "cert.pem", &cert);
+        let key_path = write(dir.path(), "key.pem", &key);
+        let url = start(TlsConfig {
+            cert_path: cert_path.clone(),
+            key_path: key_path.clone(),
+            client_ca_path: None,
+            reload_interval: Duration::from_millis(50),
+            handshake_timeout: Duration::from_secs(10),
+        })
+        .await;
+
+        let new_pki = Pki::new();
+        let client = ClientTlsConfig {
+            ca_bundle_path: Some(write(dir.path(), "new-ca.pem", &new_pki.ca_pem())),
+            ..Default::default()
+        }
+        .http_client()
+        .unwrap();
+        assert!(client.get(&url).send().await.is_err());
+
+        // Make sure the modification time moves even on coarse grained filesystems.
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        let (cert, key) = new_pki.issue("localhost", ExtendedKeyUsagePurpose::ServerAuth);
+        fs::write(&key_path, key).unwrap();
+        fs::write(&cert_path, cert).unwrap();
+        tokio::time::sleep(Duration::from_millis(300)).await;
+
+        let response = client.get(&url).send().await.unwrap();
+        assert_eq!("Healthy", response.text().await.unwrap());
+    }
+}
@@ -0,0 +1,741 @@
+//! Webhook subscriptions to a location's weather alerts. Subscriptions are
+//! kept in memory. Once the first one is made a background task polls the
+//! provider for every subscribed location and POSTs each subscription the
+//! alerts it has not been sent yet, signed with its secret.
+//!
+//! Unless configured otherwise, webhooks may only be on public addresses:
+//! a url naming a private, loopback or link-local address is refused, and
+//! deliveries go through a client that will not connect to one whatever
+//! the webhook's host resolves to later, nor follow redirects.
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt,
+    net::{IpAddr, SocketAddr},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use futures_util::{stream, StreamExt};
+use hmac::{Hmac, Mac};
+use reqwest::{
+    dns::{Addrs, Name, Resolve, Resolving},
+    header, redirect, Client, Url,
+};
+use service_models::v1::service_d::{
+    Alert, AlertNotification, AlertSubscription, AlertSubscriptionRequest,
+};
+use sha2::{Digest, Sha256};
+use tokio::time::MissedTickBehavior;
+use tracing::{instrument, Instrument, Span};
+
+use crate::AppState;
+
+/// Carries `sha256=` and the hex HMAC-SHA256 of the body, keyed by the
+/// subscription's secret. Unsubscribing takes the same of the subscription's
+/// id, proving the caller holds the secret.
+pub const SIGNATURE_HEADER: &str = "x-signature-256";
+/// How long a webhook has to answer a single delivery attempt.
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often alerts are polled, how hard a delivery is retried and which
+/// webhooks are accepted.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AlertsConfig {
+    pub poll_interval: Duration,
+    /// Attempts at a delivery before it is left for the next poll.
+    pub delivery_attempts: u32,
+    /// Wait before the first retry, doubled for each one after it.
+    pub retry_backoff: Duration,
+    /// Subscriptions kept at once. Past it new ones are refused.
+    pub max_subscriptions: usize,
+    /// Provider lookups, and separately webhook deliveries, a poll runs at
+    /// once.
+    pub concurrency: usize,
+    /// Lets webhooks be on private, loopback and link-local addresses, for
+    /// deployments whose receivers are internal.
+    pub allow_private_webhooks: bool,
+}
+
+impl Default for AlertsConfig {
+    fn default() -> Self {
+        AlertsConfig {
+            poll_interval: Duration::from_secs(60),
+            delivery_attempts: 5,
+            retry_backoff: Duration::from_secs(1),
+            max_subscriptions: 1000,
+            concurrency: 8,
+            allow_private_webhooks: false,
+        }
+    }
+}
+
+impl AlertsConfig {
+    /// Reads `WEATHER_ALERTS_POLL_SECS`, `WEATHER_ALERTS_DELIVERY_ATTEMPTS`,
+    /// `WEATHER_ALERTS_RETRY_BACKOFF_MS`, `WEATHER_ALERTS_MAX_SUBSCRIPTIONS`,
+    /// `WEATHER_ALERTS_CONCURRENCY` and `WEATHER_ALERTS_ALLOW_PRIVATE_WEBHOOKS`,
+    /// keeping the default for whichever is unset.
+    pub fn from_env() -> AlertsConfig {
+        let default = AlertsConfig::default();
+        let read = |name: &str| {
+            std::env::var(name).ok().map(|v| match v.parse::<u64>() {
+                Ok(n) if n > 0 => n,
+                _ => panic!("{} must be a positive number", name),
+            })
+        };
+        AlertsConfig {
+            poll_interval: read("WEATHER_ALERTS_POLL_SECS")
+                .map(Duration::from_secs)
+                .unwrap_or(default.poll_interval),
+            delivery_attempts: read("WEATHER_ALERTS_DELIVERY_ATTEMPTS")
+                .map(|n| n as u32)
+                .unwrap_or(default.delivery_attempts),
+            retry_backoff: read("WEATHER_ALERTS_RETRY_BACKOFF_MS")
+                .map(Duration::from_millis)
+                .unwrap_or(default.retry_backoff),
+            max_subscriptions: read("WEATHER_ALERTS_MAX_SUBSCRIPTIONS")
+                .map(|n| n as usize)
+                .unwrap_or(default.max_subscriptions),
+            concurrency: read("WEATHER_ALERTS_CONCURRENCY")
+                .map(|n| n as usize)
+                .unwrap_or(default.concurrency),
+            allow_private_webhooks: std::env::var("WEATHER_ALERTS_ALLOW_PRIVATE_WEBHOOKS")
+                .map(|v| {
+                    v.parse()
+                        .expect("WEATHER_ALERTS_ALLOW_PRIVATE_WEBHOOKS must be true or false")
+                })
+                .unwrap_or(default.allow_private_webhooks),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum WebhookError {
+    /// Not an http or https url with a host.
+    InvalidUrl(String),
+    Unresolvable(String),
+    /// The host is, or resolves to, a private, loopback or link-local
+    /// address.
+    NotPublic(String),
+}
+
+impl fmt::Display for WebhookError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WebhookError::InvalidUrl(url) => write!(f, "{:?} is not an http or https url", url),
+            WebhookError::Unresolvable(host) => write!(f, "unable to resolve {}", host),
+            WebhookError::NotPublic(host) => write!(f, "{} is not a public address", host),
+        }
+    }
+}
+
+impl std::error::Error for WebhookError {}
+
+#[derive(Debug, PartialEq)]
+pub enum UnsubscribeError {
+    NotFound(String),
+    /// The signature is not the subscription's HMAC of its id.
+    BadSignature(String),
+}
+
+impl fmt::Display for UnsubscribeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UnsubscribeError::NotFound(id) => write!(f, "there is no subscription {}", id),
+            UnsubscribeError::BadSignature(id) => {
+                write!(f, "bad signature for subscription {}", id)
+            }
+        }
+    }
+}
+
+impl std::error::Error for UnsubscribeError {}
+
+/// Whether a webhook may be delivered to `ip`. IPv4 addresses mapped into
+/// IPv6 are judged as IPv4.
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            let [a, b, ..] = v4.octets();
+            !(v4.is_private()
+                || v4.is_loopback()
+                || v4.is_link_local()
+                || v4.is_unspecified()
+                || v4.is_broadcast()
+                || v4.is_multicast()
+                // 0.0.0.0/8 and the 100.64.0.0/10 carrier-grade NAT range.
+                || a == 0
+                || (a == 100 && (64..128).contains(&b)))
+        }
+        IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => is_public(IpAddr::V4(v4)),
+            None => {
+                !(v6.is_loopback()
+                    || v6.is_unspecified()
+                    || v6.is_multicast()
+                    || v6.is_unique_local()
+                    || v6.is_unicast_link_local())
+            }
+        },
+    }
+}
+
+/// The public addresses `host` resolves to. Fails when it resolves to none
+/// or to any that is not public.
+async fn resolve_public(host: &str, port: u16) -> Result<Vec<SocketAddr>, WebhookError> {
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, port))
+        .await
+        .map_err(|_| WebhookError::Unresolvable(host.to_string()))?
+        .collect();
+    if addrs.is_empty() {
+        return Err(WebhookError::Unresolvable(host.to_string()));
+    }
+    if addrs.iter().any(|addr| !is_public(addr.ip())) {
+        return Err(WebhookError::NotPublic(host.to_string()));
+    }
+    Ok(addrs)
+}
+
+/// Lets the delivery client connect to public addresses only, so a host
+/// checked at subscription cannot be pointed somewhere private later.
+struct PublicOnly;
+
+impl Resolve for PublicOnly {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let addrs = resolve_public(name.as_str(), 0).await?;
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+#[derive(Debug)]
+struct Subscription {
+    subscription: AlertSubscription,
+    /// The provider's query for the location.
+    query: String,
+    secret: String,
+    /// Ids of the active alerts already sent.
+    delivered: HashSet<String>,
+}
+
+/// A delivery owed to one subscription.
+struct Target {
+    id: String,
+    location: String,
+    webhook_url: String,
+    secret: String,
+    delivered: HashSet<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Alerts {
+    pub config: AlertsConfig,
+    /// Sends deliveries, and only to public addresses unless private
+    /// webhooks are allowed.
+    client: Client,
+    subscriptions: Arc<Mutex<BTreeMap<String, Subscription>>>,
+    polling: Arc<AtomicBool>,
+}
+
+impl Alerts {
+    pub fn new(config: AlertsConfig) -> Alerts {
+        let client = Client::builder().redirect(redirect::Policy::none());
+        let client = if config.allow_private_webhooks {
+            client
+        } else {
+            client.dns_resolver(Arc::new(PublicOnly))
+        };
+        Alerts {
+            config,
+            client: client.build().expect("unable to build the webhook client"),
+            subscriptions: Arc::new(Mutex::new(BTreeMap::new())),
+            polling: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Checks `webhook_url` is an http or https url and, unless private
+    /// webhooks are allowed, that its host is a public address.
+    pub async fn check_webhook(&self, webhook_url: &str) -> Result<(), WebhookError> {
+        let invalid = || WebhookError::InvalidUrl(webhook_url.to_string());
+        let url = Url::parse(webhook_url).map_err(|_| invalid())?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(invalid());
+        }
+        let host = url.host_str().ok_or_else(invalid)?;
+        if self.config.allow_private_webhooks {
+            return Ok(());
+        }
+        // IPv6 hosts keep their brackets.
+        match host.trim_start_matches('[').trim_end_matches(']').parse() {
+            Ok(ip) if is_public(ip) => Ok(()),
+            Ok(_) => Err(WebhookError::NotPublic(host.to_string())),
+            Err(_) => {
+                let port = url.port_or_known_default().unwrap_or_default();
+                resolve_public(host, port).await.map(|_| ())
+            }
+        }
+    }
+
+    /// Registers `request`'s webhook for the alerts of `query`, the
+    /// provider's query for its location. Returns `None` once
+    /// `max_subscriptions` are kept.
+    pub fn subscribe(
+        &self,
+        request: AlertSubscriptionRequest,
+        query: String,
+    ) -> Option<AlertSubscription> {
+        static SUBSCRIPTIONS: AtomicU64 = AtomicU64::new(0);
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos()
+            .to_string();
+        let count = SUBSCRIPTIONS.fetch_add(1, Ordering::Relaxed).to_string();
+        let subscription = AlertSubscription {
+            id: digest(&[&nanos, &count, &request.webhook_url]),
+            location: request.location,
+            webhook_url: request.webhook_url,
+        };
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+        if subscriptions.len() >= self.config.max_subscriptions {
+            return None;
+        }
+        subscriptions.insert(
+            subscription.id.clone(),
+            Subscription {
+                subscription: subscription.clone(),
+                query,
+                secret: request.secret,
+                delivered: HashSet::new(),
+            },
+        );
+        Some(subscription)
+    }
+
+    /// Removes subscription `id` when `signature` is its [`signature`] of
+    /// the id.
+    pub fn unsubscribe(&self, id: &str, signature: &str) -> Result<(), UnsubscribeError> {
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+        let subscription = subscriptions
+            .get(id)
+            .ok_or_else(|| UnsubscribeError::NotFound(id.to_string()))?;
+        if !verify(&subscription.secret, id.as_bytes(), signature) {
+            return Err(UnsubscribeError::BadSignature(id.to_string()));
+        }
+        subscriptions.remove(id);
+        Ok(())
+    }
+
+    /// Claims the background poll. Returns false once it has been started.
+    pub fn start_polling(&self) -> bool {
+        !self.polling.swap(true, Ordering::SeqCst)
+    }
+
+    /// Every subscription, grouped by the provider query it polls.
+    fn targets(&self) -> BTreeMap<String, Vec<Target>> {
+        let mut targets: BTreeMap<String, Vec<Target>> = BTreeMap::new();
+        for s in self.subscriptions.lock().unwrap().values() {
+            targets.entry(s.query.clone()).or_default().push(Target {
+                id: s.subscription.id.clone(),
+                location: s.subscription.location.clone(),
+                webhook_url: s.subscription.webhook_url.clone(),
+                secret: s.secret.clone(),
+                delivered: s.delivered.clone(),
+            });
+        }
+        targets
+    }
+
+    /// Records that subscription `id` has been sent every alert in `active`.
+    /// Alerts no longer active are forgotten.
+    fn delivered(&self, id: &str, active: HashSet<String>) {
+        if let Some(s) = self.subscriptions.lock().unwrap().get_mut(id) {
+            s.delivered = active;
+        }
+    }
+}
+
+/// A short hex digest of `parts`, used for ids.
+pub fn digest(parts: &[&str]) -> String {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update(part.as_bytes());
+        hasher.update([0]);
+    }
+    hex::encode(&hasher.finalize()[..8])
+}
+
+/// The `x-signature-256` value of `body` for a subscription with `secret`.
+/// Receivers compute the same to check a delivery came from service-d.
+pub fn signature(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("hmac takes keys of any size");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Whether `signature` is the [`signature`] of `body` for `secret`,
+/// compared in constant time.
+fn verify(secret: &str, body: &[u8], signature: &str) -> bool {
+    let Some(Ok(expected)) = signature.strip_prefix("sha256=").map(hex::decode) else {
+        return false;
+    };
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("hmac takes keys of any size");
+    mac.update(body);
+    mac.verify_slice(&expected).is_ok()
+}
+
+/// Polls the alerts of every subscribed location until the process exits.
+pub async fn poll(state: AppState) {
+    let mut ticks = tokio::time::interval(state.alerts.config.poll_interval);
+    ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        ticks.tick().await;
+        poll_once(&state).await;
+    }
+}
+
+#[instrument(name = "alert-poll", skip_all, fields(locations, deliveries))]
+async fn poll_once(state: &AppState) {
+    let targets = state.alerts.targets();
+    Span::current().record("locations", targets.len());
+    let concurrency = state.alerts.config.concurrency;
+
+    let polled: Vec<_> = stream::iter(targets)
+        .map(|(query, targets)| async move {
+            let found = crate::cached_alerts(state, query.clone()).await;
+            (query, targets, found)
+        })
+        .buffer_unordered(concurrency)
+        .collect()
+        .await;
+
+    let mut deliveries = Vec::new();
+    for (query, targets, found) in polled {
+        let found = match found {
+            Ok((found, _)) => found,
+            Err(status) => {
+                tracing::error!("Unable to poll alerts for {}: {}", query, status);
+                continue;
+            }
+        };
+        let active: HashSet<String> = found.alerts.iter().map(|a| a.id.clone()).collect();
+        for target in targets {
+            let alerts: Vec<Alert> = found
+                .alerts
+                .iter()
+                .filter(|a| !target.delivered.contains(&a.id))
+                .cloned()
+                .collect();
+            if alerts.is_empty() {
+                state.alerts.delivered(&target.id, active.clone());
+                continue;
+            }
+            let notification = AlertNotification {
+                subscription_id: target.id.clone(),
+                location: target.location.clone(),
+                city: found.city.clone(),
+                state: found.state.clone(),
+                alerts,
+            };
+            let active = active.clone();
+            deliveries.push(async move {
+                if deliver(
+                    &state.alerts.client,
+                    &state.alerts.config,
+                    &target,
+                    &notification,
+                )
+                .await
+                {
+                    state.alerts.delivered(&target.id, active);
+                }
+            });
+        }
+    }
+    Span::current().record("deliveries", deliveries.len());
+    stream::iter(deliveries)
+        .buffer_unordered(concurrency)
+        .collect::<Vec<()>>()
+        .await;
+}
+
+/// POSTs `notification` to `target`'s webhook, retrying failures with
+/// exponential backoff. Returns whether the webhook accepted it.
+async fn deliver(
+    client: &Client,
+    config: &AlertsConfig,
+    target: &Target,
+    notification: &AlertNotification,
+) -> bool {
+    let span = tracing::info_span!(
+        "alert-delivery",
+        subscription = %target.id,
+        alerts = notification.alerts.len(),
+        attempts = tracing::field::Empty
+    );
+    async move {
+        let body = serde_json::to_vec(notification).unwrap_or_default();
+        let signature = signature(&target.secret, &body);
+        let mut backoff = config.retry_backoff;
+        for attempt in 1..=config.delivery_attempts {
+            Span::current().record("attempts", attempt);
+            let response = client
+                .post(&target.webhook_url)
+                .header(header::CONTENT_TYPE, "application/json")
+                .header(SIGNATURE_HEADER, &signature)
+                .timeout(DELIVERY_TIMEOUT)
+                .body(body.clone())
+                .send()
+                .await;
+            match response {
+                Ok(r) if r.status().is_success() => return true,
+                Ok(r) => tracing::error!("Webhook answered {} on attempt {}", r.status(), attempt),
+                Err(e) => tracing::error!("Webhook failed on attempt {}: {}", attempt, e),
+            }
+            if attempt < config.delivery_attempts {
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+            }
+        }
+        false
+    }
+    .instrument(span)
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        body::Bytes,
+        http::{HeaderMap, StatusCode},
+        routing::post,
+        Router,
+    };
+    use service_models::v1::service_d::AlertSeverity;
+    use std::sync::atomic::AtomicUsize;
+
+    fn alert(id: &str) -> Alert {
+        Alert {
+            id: id.to_string(),
+            event: String::from("Tornado Warning"),
+            headline: String::from("Tornado Warning issued for Tarrant"),
+            severity: AlertSeverity::Extreme,
+            areas: vec![String::from("Tarrant")],
+            description: None,
+            instruction: None,
+            effective: None,
+            expires: None,
+        }
+    }
+
+    /// A webhook that fails the first `failures` deliveries and records the
+    /// bodies and signatures of the rest.
+    async fn webhook(
+        failures: usize,
+        received: Arc<Mutex<Vec<(Bytes, String)>>>,
+    ) -> (String, Arc<AtomicUsize>) {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counted = calls.clone();
+        let receiver = Router::new().route(
+            "/hook",
+            post(move |headers: HeaderMap, body: Bytes| async move {
+                if counted.fetch_add(1, Ordering::SeqCst) < failures {
+                    return StatusCode::SERVICE_UNAVAILABLE;
+                }
+                let signature = headers[SIGNATURE_HEADER].to_str().unwrap().to_string();
+                received.lock().unwrap().push((body, signature));
+                StatusCode::NO_CONTENT
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, receiver).await.unwrap() });
+        (url, calls)
+    }
+
+    fn target(webhook_url: String) -> Target {
+        Target {
+            id: String::from("sub"),
+            location: String::from("76262"),
+            webhook_url,
+            secret: String::from("s3cret"),
+            delivered: HashSet::new(),
+        }
+    }
+
+    fn notification() -> AlertNotification {
+        AlertNotification {
+            subscription_id: String::from("sub"),
+            location: String::from("76262"),
+            city: String::from("Roanoke"),
+            state: String::from("Texas"),
+            alerts: vec![alert("a")],
+        }
+    }
+
+    fn config(delivery_attempts: u32) -> AlertsConfig {
+        AlertsConfig {
+            delivery_attempts,
+            retry_backoff: Duration::from_millis(10),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn retries_until_the_webhook_accepts_a_signed_delivery() {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let (url, calls) = webhook(2, received.clone()).await;
+
+        assert!(deliver(&Client::new(), &config(3), &target(url), &notification()).await);
+        assert_eq!(3, calls.load(Ordering::SeqCst));
+        let (body, sent) = received.lock().unwrap().pop().unwrap();
+        assert_eq!(signature("s3cret", &body), sent);
+        assert_eq!(
+            notification(),
+            serde_json::from_slice::<AlertNotification>(&body).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_the_configured_attempts() {
+        let (url, calls) = webhook(usize::MAX, Arc::new(Mutex::new(Vec::new()))).await;
+        assert!(!deliver(&Client::new(), &config(2), &target(url), &notification()).await);
+        assert_eq!(2, calls.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn signs_with_hmac_sha256() {
+        // RFC 4231 test case 2.
+        assert_eq!(
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843",
+            signature("Jefe", b"what do ya want for nothing?")
+        );
+    }
+
+    #[tokio::test]
+    async fn refuses_webhooks_on_private_addresses() {
+        let alerts = Alerts::new(AlertsConfig::default());
+        for url in [
+            "http://127.0.0.1:9/hook",
+            "http://10.0.0.7/hook",
+            "http://192.168.1.1/hook",
+            "http://169.254.169.254/latest/meta-data",
+            "http://100.64.0.1/hook",
+            "http://[::1]/hook",
+            "http://[fd00::1]/hook",
+            "http://[::ffff:127.0.0.1]/hook",
+            "http://localhost:8080/hook",
+        ] {
+            assert!(
+                matches!(
+                    alerts.check_webhook(url).await,
+                    Err(WebhookError::NotPublic(_))
+                ),
+                "{}",
+                url
+            );
+        }
+        assert!(alerts
+            .check_webhook("https://203.0.113.7/hook")
+            .await
+            .is_ok());
+        assert!(matches!(
+            alerts.check_webhook("ftp://203.0.113.7/hook").await,
+            Err(WebhookError::InvalidUrl(_))
+        ));
+
+        let alerts = Alerts::new(AlertsConfig {
+            allow_private_webhooks: true,
+            ..Default::default()
+        });
+        assert!(alerts
+            .check_webhook("http://127.0.0.1:9/hook")
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn will_not_deliver_to_hosts_resolving_to_private_addresses() {
+        let (url, calls) = webhook(0, Arc::new(Mutex::new(Vec::new()))).await;
+        let url = url.replace("127.0.0.1", "localhost");
+        let alerts = Alerts::new(config(1));
+        assert!(
+            !deliver(
+                &alerts.client,
+                &alerts.config,
+                &target(url),
+                &notification()
+            )
+            .await
+        );
+        assert_eq!(0, calls.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn tracks_subscriptions() {
+        let alerts = Alerts::new(AlertsConfig {
+            max_subscriptions: 2,
+            ..Default::default()
+        });
+        let request = |location: &str| AlertSubscriptionRequest {
+            location: location.to_string(),
+            webhook_url: String::from("http://127.0.0.1:9/hook"),
+            secret: String::from("s3cret"),
+        };
+        let first = alerts
+            .subscribe(request("76262"), String::from("76262"))
+            .unwrap();
+        let second = alerts
+            .subscribe(request("Roanoke, TX"), String::from("76262"))
+            .unwrap();
+        assert_ne!(first.id, second.id);
+        assert_eq!(
+            None,
+            alerts.subscribe(request("10001"), String::from("10001"))
+        );
+        assert_eq!(1, alerts.targets().len());
+        assert_eq!(2, alerts.targets()["76262"].len());
+
+        alerts.delivered(&first.id, HashSet::from([String::from("a")]));
+        let first_signature = signature("s3cret", first.id.as_bytes());
+        for wrong in [
+            "",
+            "sha256=",
+            "sha256=zz",
+            &signature("guess", first.id.as_bytes()),
+        ] {
+            assert_eq!(
+                Err(UnsubscribeError::BadSignature(first.id.clone())),
+                alerts.unsubscribe(&first.id, wrong),
+                "{}",
+                wrong
+            );
+        }
+        assert_eq!(
+            Err(UnsubscribeError::BadSignature(first.id.clone())),
+            alerts.unsubscribe(&first.id, &signature("s3cret", second.id.as_bytes()))
+        );
+        assert_eq!(Ok(()), alerts.unsubscribe(&first.id, &first_signature));
+        assert_eq!(
+            Err(UnsubscribeError::NotFound(first.id.clone())),
+            alerts.unsubscribe(&first.id, &first_signature)
+        );
+        let targets = alerts.targets();
+        assert_eq!(
+            vec![second.id],
+            targets["76262"]
+                .iter()
+                .map(|t| t.id.clone())
+                .collect::<Vec<_>>()
+        );
+
+        assert!(alerts.start_polling());
+        assert!(!alerts.start_polling());
+    }
+}
@@ -0,0 +1,578 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    path::PathBuf,
+    sync::{Arc, Mutex, Weak},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use redis::{aio::ConnectionManager, AsyncCommands};
+use rusqlite::{Connection, OptionalExtension};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::OnceCell;
+
+/// How often entries past their retention are dropped from the memory and
+/// sqlite stores. Redis expires its own.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Where cached weather is kept. Only the memory backend loses its entries
+/// on restart.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CacheBackend {
+    Memory,
+    Sqlite { path: PathBuf },
+    Redis { url: String },
+}
+
+impl CacheBackend {
+    /// `WEATHER_CACHE_BACKEND` is `memory` (the default), `sqlite`, which
+    /// needs `WEATHER_CACHE_PATH`, or `redis`, which needs
+    /// `WEATHER_CACHE_REDIS_URL`.
+    pub fn from_env() -> CacheBackend {
+        match std::env::var("WEATHER_CACHE_BACKEND").as_deref() {
+            Err(_) | Ok("memory") => CacheBackend::Memory,
+            Ok("sqlite") => CacheBackend::Sqlite {
+                path: PathBuf::from(
+                    std::env::var("WEATHER_CACHE_PATH").expect("WEATHER_CACHE_PATH is required"),
+                ),
+            },
+            Ok("redis") => CacheBackend::Redis {
+                url: std::env::var("WEATHER_CACHE_REDIS_URL")
+                    .expect("WEATHER_CACHE_REDIS_URL is required"),
+            },
+            Ok(other) => panic!("unknown WEATHER_CACHE_BACKEND {}", other),
+        }
+    }
+}
+
+/// Built from `WEATHER_CACHE_TTL_SECS`, which turns caching on, and the
+/// optional `WEATHER_CACHE_STALE_WHILE_REVALIDATE_SECS` and
+/// `WEATHER_CACHE_STALE_IF_ERROR_SECS`. Both stale windows start once the
+/// entry stops being fresh.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CacheConfig {
+    pub backend: CacheBackend,
+    pub ttl: Duration,
+    pub stale_while_revalidate: Duration,
+    pub stale_if_error: Duration,
+}
+
+impl CacheConfig {
+    pub fn from_env() -> Option<CacheConfig> {
+        let seconds = |name: &str| {
+            std::env::var(name).ok().map(|v| {
+                Duration::from_secs(
+                    v.parse()
+                        .unwrap_or_else(|_| panic!("{} must be a number", name)),
+                )
+            })
+        };
+        Some(CacheConfig {
+            ttl: seconds("WEATHER_CACHE_TTL_SECS")?,
+            backend: CacheBackend::from_env(),
+            stale_while_revalidate: seconds("WEATHER_CACHE_STALE_WHILE_REVALIDATE_SECS")
+                .unwrap_or_default(),
+            stale_if_error: seconds("WEATHER_CACHE_STALE_IF_ERROR_SECS").unwrap_or_default(),
+        })
+    }
+
+    /// How long an entry is worth keeping at all.
+    fn retention(&self) -> Duration {
+        self.ttl + self.stale_while_revalidate.max(self.stale_if_error)
+    }
+}
+
+#[derive(Debug)]
+pub enum CacheError {
+    Sqlite(rusqlite::Error),
+    Redis(redis::RedisError),
+    Encoding(serde_json::Error),
+    /// The blocking task running a sqlite call panicked or was cancelled.
+    Task(tokio::task::JoinError),
+}
+
+impl fmt::Display for CacheError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CacheError::Sqlite(e) => write!(f, "sqlite cache error: {}", e),
+            CacheError::Redis(e) => write!(f, "redis cache error: {}", e),
+            CacheError::Encoding(e) => write!(f, "invalid cache entry: {}", e),
+            CacheError::Task(e) => write!(f, "cache task failed: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for CacheError {}
+
+impl From<rusqlite::Error> for CacheError {
+    fn from(e: rusqlite::Error) -> Self {
+        CacheError::Sqlite(e)
+    }
+}
+
+impl From<redis::RedisError> for CacheError {
+    fn from(e: redis::RedisError) -> Self {
+        CacheError::Redis(e)
+    }
+}
+
+impl From<serde_json::Error> for CacheError {
+    fn from(e: serde_json::Error) -> Self {
+        CacheError::Encoding(e)
+    }
+}
+
+impl From<tokio::task::JoinError> for CacheError {
+    fn from(e: tokio::task::JoinError) -> Self {
+        CacheError::Task(e)
+    }
+}
+
+/// What the cache holds for a key, as seen at the time of the lookup.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Lookup<T> {
+    Fresh(Cached<T>),
+    /// Past its ttl but inside the stale-while-revalidate window.
+    Revalidate(Cached<T>),
+    /// Too old to serve unless the provider fails.
+    Expired(Cached<T>),
+    Miss,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cached<T> {
+    pub value: T,
+    pub age: Duration,
+}
+
+impl<T> Cached<T> {
+    /// Whether the entry may still stand in for a failed provider call.
+    pub fn usable_on_error(&self, config: &CacheConfig) -> bool {
+        self.age <= config.ttl + config.stale_if_error
+    }
+}
+
+/// Fetch times are wall clock so entries keep their age across restarts.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Entry {
+    value: Value,
+    fetched_at_ms: u64,
+}
+
+impl Entry {
+    fn age(&self) -> Duration {
+        Duration::from_millis(now_ms().saturating_sub(self.fetched_at_ms))
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+/// The sqlite arm runs each call on the blocking pool. The redis arm shares
+/// one connection manager, connected on first use so opening stays
+/// synchronous, and reconnecting by itself after that.
+#[derive(Clone)]
+enum Store {
+    Memory(Arc<Mutex<HashMap<String, Entry>>>),
+    Sqlite(Arc<Mutex<Connection>>),
+    Redis {
+        client: redis::Client,
+        connection: Arc<OnceCell<ConnectionManager>>,
+    },
+}
+
+impl fmt::Debug for Store {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Store::Memory(_) => write!(f, "Memory"),
+            Store::Sqlite(_) => write!(f, "Sqlite"),
+            Store::Redis { client, .. } => f.debug_tuple("Redis").field(client).finish(),
+        }
+    }
+}
+
+impl Store {
+    fn open(backend: &CacheBackend) -> Result<Store, CacheError> {
+        match backend {
+            CacheBackend::Memory => Ok(Store::Memory(Arc::new(Mutex::new(HashMap::new())))),
+            CacheBackend::Sqlite { path } => {
+                let connection = Connection::open(path)?;
+                connection.execute_batch(
+                    "CREATE TABLE IF NOT EXISTS weather_cache (
+                        key TEXT PRIMARY KEY,
+                        value TEXT NOT NULL,
+                        fetched_at_ms INTEGER NOT NULL
+                    );
+                    CREATE INDEX IF NOT EXISTS weather_cache_fetched_at
+                        ON weather_cache (fetched_at_ms);",
+                )?;
+                Ok(Store::Sqlite(Arc::new(Mutex::new(connection))))
+            }
+            CacheBackend::Redis { url } => Ok(Store::Redis {
+                client: redis::Client::open(url.as_str())?,
+                connection: Arc::new(OnceCell::new()),
+            }),
+        }
+    }
+
+    async fn redis(
+        client: &redis::Client,
+        connection: &OnceCell<ConnectionManager>,
+    ) -> Result<ConnectionManager, CacheError> {
+        let connection = connection
+            .get_or_try_init(|| ConnectionManager::new(client.clone()))
+            .await?;
+        Ok(connection.clone())
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Entry>, CacheError> {
+        match self {
+            Store::Memory(entries) => Ok(entries.lock().unwrap().get(key).cloned()),
+            Store::Sqlite(connection) => {
+                let connection = connection.clone();
+                let key = key.to_string();
+                let row = tokio::task::spawn_blocking(move || {
+                    connection
+                        .lock()
+                        .unwrap()
+                        .query_row(
+                            "SELECT value, fetched_at_ms FROM weather_cache WHERE key = ?1",
+                            [key],
+                            |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?)),
+                        )
+                        .optional()
+                })
+                .await??;
+                row.map(|(value, fetched_at_ms)| {
+                    Ok(Entry {
+                        value: serde_json::from_str(&value)?,
+                        fetched_at_ms: fetched_at_ms as u64,
+                    })
+                })
+                .transpose()
+            }
+            Store::Redis { client, connection } => {
+                let mut connection = Store::redis(client, connection).await?;
+                let value: Option<String> = connection.get(redis_key(key)).await?;
+                Ok(value.map(|v| serde_json::from_str(&v)).transpose()?)
+            }
+        }
+    }
+
+    async fn put(&self, key: &str, entry: Entry, retention: Duration) -> Result<(), CacheError> {
+        match self {
+            Store::Memory(entries) => {
+                entries.lock().unwrap().insert(key.to_string(), entry);
+            }
+            Store::Sqlite(connection) => {
+                let connection = connection.clone();
+                let key = key.to_string();
+                let value = serde_json::to_string(&entry.value)?;
+                tokio::task::spawn_blocking(move || {
+                    connection.lock().unwrap().execute(
+                        "INSERT OR REPLACE INTO weather_cache (key, value, fetched_at_ms)
+                         VALUES (?1, ?2, ?3)",
+                        (key, value, entry.fetched_at_ms as i64),
+                    )
+                })
+                .await??;
+            }
+            Store::Redis { client, connection } => {
+                let mut connection = Store::redis(client, connection).await?;
+                let _: () = connection
+                    .pset_ex(
+                        redis_key(key),
+                        serde_json::to_string(&entry)?,
+                        retention.as_millis().max(1) as u64,
+                    )
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Drops the entries fetched more than `retention` ago, returning how
+    /// many went.
+    async fn evict(&self, retention: Duration) -> Result<usize, CacheError> {
+        let cutoff_ms = now_ms().saturating_sub(retention.as_millis() as u64);
+        match self {
+            Store::Memory(entries) => {
+                let mut entries = entries.lock().unwrap();
+                let before = entries.len();
+                entries.retain(|_, entry| entry.fetched_at_ms >= cutoff_ms);
+                Ok(before - entries.len())
+            }
+            Store::Sqlite(connection) => {
+                let connection = connection.clone();
+                let evicted = tokio::task::spawn_blocking(move || {
+                    connection.lock().unwrap().execute(
+                        "DELETE FROM weather_cache WHERE fetched_at_ms < ?1",
+                        [cutoff_ms as i64],
+                    )
+                })
+                .await??;
+                Ok(evicted)
+            }
+            Store::Redis { .. } => Ok(0),
+        }
+    }
+
+    /// A store the sweeper can hold without keeping it open.
+    fn downgrade(&self) -> Option<WeakStore> {
+        match self {
+            Store::Memory(entries) => Some(WeakStore::Memory(Arc::downgrade(entries))),
+            Store::Sqlite(connection) => Some(WeakStore::Sqlite(Arc::downgrade(connection))),
+            Store::Redis { .. } => None,
+        }
+    }
+}
+
+enum WeakStore {
+    Memory(Weak<Mutex<HashMap<String, Entry>>>),
+    Sqlite(Weak<Mutex<Connection>>),
+}
+
+impl WeakStore {
+    fn upgrade(&self) -> Option<Store> {
+        match self {
+            WeakStore::Memory(entries) => entries.upgrade().map(Store::Memory),
+            WeakStore::Sqlite(connection) => connection.upgrade().map(Store::Sqlite),
+        }
+    }
+}
+
+/// Evicts expired entries every `SWEEP_INTERVAL` until the cache is dropped.
+fn sweep(store: WeakStore, retention: Duration) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            let Some(store) = store.upgrade() else {
+                break;
+            };
+            match store.evict(retention).await {
+                Ok(0) => {}
+                Ok(evicted) => tracing::debug!("Evicted {} weather cache entries", evicted),
+                Err(e) => tracing::error!("Error evicting from the weather cache: {}", e),
+            }
+        }
+    });
+}
+
+fn redis_key(key: &str) -> String {
+    format!("service-d:{}", key)
+}
+
+#[derive(Clone, Debug)]
+pub struct WeatherCache {
+    pub config: CacheConfig,
+    store: Store,
+    refreshing: Arc<Mutex<HashSet<String>>>,
+}
+
+impl WeatherCache {
+    /// Memory and sqlite caches start sweeping out expired entries, so this
+    /// must be called from within a tokio runtime.
+    pub fn open(config: CacheConfig) -> Result<WeatherCache, CacheError> {
+        let store = Store::open(&config.backend)?;
+        if let Some(weak) = store.downgrade() {
+            sweep(weak, config.retention());
+        }
+        Ok(WeatherCache {
+            store,
+            config,
+            refreshing: Arc::new(Mutex::new(HashSet::new())),
+        })
+    }
+
+    /// A backend that cannot be read, or an entry that no longer fits `T`,
+    /// is treated as a miss so the provider is still asked.
+    pub async fn lookup<T: DeserializeOwned>(&self, key: &str) -> Lookup<T> {
+        let entry = match self.store.get(key).await {
+            Ok(Some(entry)) => entry,
+            Ok(None) => return Lookup::Miss,
+            Err(e) => {
+                tracing::error!("Error reading the weather cache: {}", e);
+                return Lookup::Miss;
+            }
+        };
+        let age = entry.age();
+        let cached = match serde_json::from_value(entry.value) {
+            Ok(value) => Cached { value, age },
+            Err(e) => {
+                tracing::error!("Error reading the weather cache: {}", e);
+                return Lookup::Miss;
+            }
+        };
+        if cached.age <= self.config.ttl {
+            Lookup::Fresh(cached)
+        } else if cached.age <= self.config.ttl + self.config.stale_while_revalidate {
+            Lookup::Revalidate(cached)
+        } else {
+            Lookup::Expired(cached)
+        }
+    }
+
+    pub async fn store<T: Serialize>(&self, key: &str, value: &T) {
+        let entry = match serde_json::to_value(value) {
+            Ok(value) => Entry {
+                value,
+                fetched_at_ms: now_ms(),
+            },
+            Err(e) => {
+                tracing::error!("Error writing the weather cache: {}", e);
+                return;
+            }
+        };
+        if let Err(e) = self.store.put(key, entry, self.config.retention()).await {
+            tracing::error!("Error writing the weather cache: {}", e);
+        }
+    }
+
+    /// Claims the background refresh for `key`. Returns false when one is
+    /// already running so a burst of stale hits only refreshes once.
+    pub fn start_refresh(&self, key: &str) -> bool {
+        self.refreshing.lock().unwrap().insert(key.to_string())
+    }
+
+    pub fn finish_refresh(&self, key: &str) {
+        self.refreshing.lock().unwrap().remove(key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use service_models::v1::service_d::WeatherResponse;
+
+    fn weather(city: &str) -> WeatherResponse {
+        WeatherResponse {
+            city: city.to_string(),
+            state: String::from("Texas"),
+            celcius: 20.0,
+            farenheight: 68.0,
+            ..Default::default()
+        }
+    }
+
+    fn cache(backend: CacheBackend, ttl_ms: u64, stale_while_revalidate_ms: u64) -> WeatherCache {
+        WeatherCache::open(CacheConfig {
+            backend,
+            ttl: Duration::from_millis(ttl_ms),
+            stale_while_revalidate: Duration::from_millis(stale_while_revalidate_ms),
+            stale_if_error: Duration::from_secs(60),
+        })
+        .unwrap()
+    }
+
+    async fn ages_through_each_window(backend: CacheBackend) {
+        let cache = cache(backend, 200, 200);
+        assert_eq!(Lookup::Miss, cache.lookup::<WeatherResponse>("76262").await);
+
+        cache.store("76262", &weather("Roanoke")).await;
+        assert!(matches!(
+            cache.lookup::<WeatherResponse>("76262").await,
+            Lookup::Fresh(_)
+        ));
+
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        assert!(matches!(
+            cache.lookup::<WeatherResponse>("76262").await,
+            Lookup::Revalidate(_)
+        ));
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        let Lookup::Expired(cached) = cache.lookup("76262").await else {
+            panic!("expected an expired entry");
+        };
+        assert_eq!(weather("Roanoke"), cached.value);
+        assert!(cached.usable_on_error(&cache.config));
+    }
+
+    #[tokio::test]
+    async fn memory_entries_age_through_each_window() {
+        ages_through_each_window(CacheBackend::Memory).await;
+    }
+
+    #[tokio::test]
+    async fn sqlite_entries_age_through_each_window() {
+        let dir = tempfile::tempdir().unwrap();
+        ages_through_each_window(CacheBackend::Sqlite {
+            path: dir.path().join("weather.db"),
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a redis-server, set REDIS_URL to run"]
+    async fn redis_entries_age_through_each_window() {
+        let url = std::env::var("REDIS_URL").unwrap_or(String::from("redis://127.0.0.1/"));
+        let mut connection = redis::Client::open(url.as_str())
+            .unwrap()
+            .get_multiplexed_async_connection()
+            .await
+            .unwrap();
+        let _: () = connection.del(redis_key("76262")).await.unwrap();
+        ages_through_each_window(CacheBackend::Redis { url }).await;
+    }
+
+    #[tokio::test]
+    async fn sqlite_entries_survive_a_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let backend = CacheBackend::Sqlite {
+            path: dir.path().join("weather.db"),
+        };
+        cache(backend.clone(), 60_000, 0)
+            .store("76262", &weather("Roanoke"))
+            .await;
+
+        let Lookup::Fresh(cached) = cache(backend, 60_000, 0).lookup("76262").await else {
+            panic!("expected the entry written before the restart");
+        };
+        assert_eq!(weather("Roanoke"), cached.value);
+    }
+
+    async fn evicts_entries_past_their_retention(backend: CacheBackend) {
+        let cache = cache(backend, 100, 0);
+        let retention = Duration::from_millis(200);
+        cache.store("76262", &weather("Roanoke")).await;
+        assert_eq!(0, cache.store.evict(retention).await.unwrap());
+
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        cache.store("10001", &weather("New York")).await;
+        assert_eq!(1, cache.store.evict(retention).await.unwrap());
+        assert!(cache.store.get("76262").await.unwrap().is_none());
+        assert!(cache.store.get("10001").await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn memory_evicts_entries_past_their_retention() {
+        evicts_entries_past_their_retention(CacheBackend::Memory).await;
+    }
+
+    #[tokio::test]
+    async fn sqlite_evicts_entries_past_their_retention() {
+        let dir = tempfile::tempdir().unwrap();
+        evicts_entries_past_their_retention(CacheBackend::Sqlite {
+            path: dir.path().join("weather.db"),
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn only_one_refresh_runs_per_zip() {
+        let cache = cache(CacheBackend::Memory, 0, 1_000);
+        assert!(cache.start_refresh("76262"));
+        assert!(!cache.start_refresh("76262"));
+        assert!(cache.start_refresh("10001"));
+
+        cache.finish_refresh("76262");
+        assert!(cache.start_refresh("76262"));
+    }
+}
@@ -0,0 +1,85 @@
+use std::str::ParseBoolError;
+
+use service_common::{
+    fault::FaultInjector,
+    tls::{ClientTlsConfig, TlsConfig},
+};
+
+use crate::{alerts::AlertsConfig, cache::CacheConfig, history::HistoryConfig};
+
+/// Everything the service reads from its environment.
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub bind_address: String,
+    pub tracing_enabled: bool,
+    pub agent_address: Option<String>,
+    pub tls: Option<TlsConfig>,
+    pub faults: Option<FaultInjector>,
+    pub upstream_tls: ClientTlsConfig,
+    pub weather_api_url: String,
+    pub weather_api_key: String,
+    pub weather_cache: Option<CacheConfig>,
+    pub weather_batch: BatchConfig,
+    pub weather_alerts: AlertsConfig,
+    pub weather_history: Option<HistoryConfig>,
+}
+
+impl Config {
+    pub fn from_env() -> Config {
+        let tracing_enabled =
+            std::env::var("DD_TRACING_ENABLED").expect("DD_TRACING_ENABLED is required");
+        let use_tracing: Result<bool, ParseBoolError> = tracing_enabled.parse();
+
+        Config {
+            bind_address: std::env::var("BIND_ADDRESS").expect("BIND_ADDRESS is required"),
+            tracing_enabled: use_tracing.unwrap_or_default(),
+            agent_address: std::env::var("AGENT_ADDRESS").ok(),
+            tls: TlsConfig::from_env(),
+            faults: FaultInjector::from_env(),
+            upstream_tls: ClientTlsConfig::from_env(),
+            weather_api_url: std::env::var("WEATHER_API_URL").expect("WEATHER_API_URL Must be Set"),
+            weather_api_key: std::env::var("WEATHER_API_KEY").expect("WEATHER_API_KEY Must be set"),
+            weather_cache: CacheConfig::from_env(),
+            weather_batch: BatchConfig::from_env(),
+            weather_alerts: AlertsConfig::from_env(),
+            weather_history: HistoryConfig::from_env(),
+        }
+    }
+}
+
+/// Limits on `POST /weather/batch`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BatchConfig {
+    /// Provider lookups a single batch runs at once.
+    pub concurrency: usize,
+    /// Locations a single batch may ask for.
+    pub max_locations: usize,
+}
+
+impl Default for BatchConfig {
+    fn default() -> Self {
+        BatchConfig {
+            concurrency: 8,
+            max_locations: 100,
+        }
+    }
+}
+
+impl BatchConfig {
+    /// Reads `WEATHER_BATCH_CONCURRENCY` and `WEATHER_BATCH_MAX_LOCATIONS`,
+    /// keeping the default for whichever is unset.
+    pub fn from_env() -> BatchConfig {
+        let default = BatchConfig::default();
+        let read = |name: &str, default: usize| match std::env::var(name) {
+            Ok(v) => match v.parse::<usize>() {
+                Ok(n) if n > 0 => n,
+                _ => panic!("{} must be a positive number", name),
+            },
+            Err(_) => default,
+        };
+        BatchConfig {
+            concurrency: read("WEATHER_BATCH_CONCURRENCY", default.concurrency),
+            max_locations: read("WEATHER_BATCH_MAX_LOCATIONS", default.max_locations),
+        }
+    }
+}
@@ -0,0 +1,439 @@
+//! Records the current conditions fetched from the provider into a SQLite
+//! file so trends can be queried later. Only provider answers are recorded,
+//! once per observation, whatever the cache serves in between.
+//!
+//! Observations are written, and old ones deleted, by a writer thread fed
+//! through a channel, and queries run on the blocking pool, so SQLite is
+//! never waited on from an async worker.
+use std::{
+    fmt,
+    path::PathBuf,
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, TimeZone, Utc};
+use rusqlite::{Connection, Row};
+use service_models::v1::service_d::{
+    Aggregate, HistoryInterval, WeatherHistoryBucket, WeatherResponse,
+};
+use tokio::sync::oneshot;
+
+/// How often observations past retention are deleted.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Built from `WEATHER_HISTORY_PATH`, which turns recording on, and the
+/// optional `WEATHER_HISTORY_RETENTION_DAYS`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HistoryConfig {
+    pub path: PathBuf,
+    /// Observations older than this are deleted every `SWEEP_INTERVAL`.
+    pub retention: Duration,
+}
+
+impl HistoryConfig {
+    const DEFAULT_RETENTION_DAYS: u64 = 30;
+    /// A century, far past any useful history.
+    const MAX_RETENTION_DAYS: u64 = 36_500;
+
+    pub fn from_env() -> Option<HistoryConfig> {
+        let path = std::env::var("WEATHER_HISTORY_PATH").ok()?;
+        let days = match std::env::var("WEATHER_HISTORY_RETENTION_DAYS") {
+            Ok(v) => match v.parse::<u64>() {
+                Ok(n) if (1..=HistoryConfig::MAX_RETENTION_DAYS).contains(&n) => n,
+                _ => panic!(
+                    "WEATHER_HISTORY_RETENTION_DAYS must be a number from 1 to {}",
+                    HistoryConfig::MAX_RETENTION_DAYS
+                ),
+            },
+            Err(_) => HistoryConfig::DEFAULT_RETENTION_DAYS,
+        };
+        Some(HistoryConfig {
+            path: PathBuf::from(path),
+            retention: Duration::from_secs(
+                days.checked_mul(24 * 60 * 60)
+                    .expect("WEATHER_HISTORY_RETENTION_DAYS is bounded"),
+            ),
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum HistoryError {
+    Sqlite(rusqlite::Error),
+    /// The blocking task running a query panicked or was cancelled.
+    Task(tokio::task::JoinError),
+    /// The writer thread has stopped, so recent observations may be missing.
+    WriterStopped,
+}
+
+impl fmt::Display for HistoryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HistoryError::Sqlite(e) => write!(f, "sqlite history error: {}", e),
+            HistoryError::Task(e) => write!(f, "history task failed: {}", e),
+            HistoryError::WriterStopped => write!(f, "the history writer has stopped"),
+        }
+    }
+}
+
+impl std::error::Error for HistoryError {}
+
+impl From<rusqlite::Error> for HistoryError {
+    fn from(e: rusqlite::Error) -> Self {
+        HistoryError::Sqlite(e)
+    }
+}
+
+impl From<tokio::task::JoinError> for HistoryError {
+    fn from(e: tokio::task::JoinError) -> Self {
+        HistoryError::Task(e)
+    }
+}
+
+#[derive(Debug)]
+struct Observation {
+    location: String,
+    observed_at_ms: i64,
+    celsius: f64,
+    fahrenheit: f64,
+    humidity: Option<f64>,
+    wind_kph: Option<f64>,
+    wind_mph: Option<f64>,
+}
+
+#[derive(Debug)]
+enum Command {
+    Record(Observation),
+    /// Answered once every observation sent before it is written.
+    Flush(oneshot::Sender<()>),
+}
+
+#[derive(Clone, Debug)]
+pub struct WeatherHistory {
+    pub config: HistoryConfig,
+    connection: Arc<Mutex<Connection>>,
+    writer: Sender<Command>,
+}
+
+impl WeatherHistory {
+    pub fn open(config: HistoryConfig) -> rusqlite::Result<WeatherHistory> {
+        let connection = Connection::open(&config.path)?;
+        connection.execute_batch(
+            "CREATE TABLE IF NOT EXISTS weather_history (
+                location TEXT NOT NULL,
+                observed_at_ms INTEGER NOT NULL,
+                celsius REAL NOT NULL,
+                fahrenheit REAL NOT NULL,
+                humidity REAL,
+                wind_kph REAL,
+                wind_mph REAL,
+                PRIMARY KEY (location, observed_at_ms)
+            );
+            CREATE INDEX IF NOT EXISTS weather_history_observed_at
+                ON weather_history (observed_at_ms);",
+        )?;
+        let connection = Arc::new(Mutex::new(connection));
+        let (writer, commands) = mpsc::channel();
+        let written = connection.clone();
+        let retention = config.retention;
+        std::thread::Builder::new()
+            .name(String::from("weather-history"))
+            .spawn(move || write(&written, retention, commands))
+            .expect("unable to start the weather history writer");
+        Ok(WeatherHistory {
+            config,
+            connection,
+            writer,
+        })
+    }
+
+    /// Queues `weather` to be recorded for the provider query `location` at
+    /// the time the provider observed it, or `fetched_at` when it did not
+    /// say. Fetching the same observation again replaces it rather than
+    /// counting it twice.
+    pub fn record(&self, location: &str, weather: &WeatherResponse, fetched_at: DateTime<Utc>) {
+        let observation = Observation {
+            location: location.to_string(),
+            observed_at_ms: weather.observed_at.unwrap_or(fetched_at).timestamp_millis(),
+            celsius: weather.celcius,
+            fahrenheit: weather.farenheight,
+            humidity: weather.humidity.map(f64::from),
+            wind_kph: weather.wind_kph,
+            wind_mph: weather.wind_mph,
+        };
+        if self.writer.send(Command::Record(observation)).is_err() {
+            tracing::error!("Error recording weather history: the writer has stopped");
+        }
+    }
+
+    /// The observations of `location` from `from` up to `to`, aggregated per
+    /// `interval`, oldest first. Includes everything recorded before the
+    /// call.
+    pub async fn query(
+        &self,
+        location: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        interval: HistoryInterval,
+    ) -> Result<Vec<WeatherHistoryBucket>, HistoryError> {
+        let (flushed, written) = oneshot::channel();
+        self.writer
+            .send(Command::Flush(flushed))
+            .map_err(|_| HistoryError::WriterStopped)?;
+        written.await.map_err(|_| HistoryError::WriterStopped)?;
+
+        let connection = self.connection.clone();
+        let location = location.to_string();
+        let buckets = tokio::task::spawn_blocking(move || {
+            query(&connection.lock().unwrap(), &location, from, to, interval)
+        })
+        .await??;
+        Ok(buckets)
+    }
+}
+
+/// Writes what `commands` send until every `WeatherHistory` is dropped,
+/// deleting observations past `retention` every `SWEEP_INTERVAL`.
+fn write(connection: &Mutex<Connection>, retention: Duration, commands: Receiver<Command>) {
+    let mut next_sweep = Instant::now();
+    loop {
+        let now = Instant::now();
+        if now >= next_sweep {
+            if let Err(e) = forget_past(&connection.lock().unwrap(), retention) {
+                tracing::error!("Error deleting old weather history: {}", e);
+            }
+            next_sweep = now + SWEEP_INTERVAL;
+        }
+        match commands.recv_timeout(next_sweep - now) {
+            Ok(Command::Record(observation)) => {
+                if let Err(e) = insert(&connection.lock().unwrap(), &observation) {
+                    tracing::error!("Error recording weather history: {}", e);
+                }
+            }
+            Ok(Command::Flush(flushed)) => {
+                let _ = flushed.send(());
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+}
+
+fn insert(connection: &Connection, observation: &Observation) -> rusqlite::Result<usize> {
+    connection.execute(
+        "INSERT OR REPLACE INTO weather_history
+            (location, observed_at_ms, celsius, fahrenheit, humidity, wind_kph, wind_mph)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        (
+            &observation.location,
+            observation.observed_at_ms,
+            observation.celsius,
+            observation.fahrenheit,
+            observation.humidity,
+            observation.wind_kph,
+            observation.wind_mph,
+        ),
+    )
+}
+
+/// Deletes the observations made more than `retention` ago, returning how
+/// many went. A retention reaching back before the earliest time chrono
+/// can represent forgets nothing.
+fn forget_past(connection: &Connection, retention: Duration) -> rusqlite::Result<usize> {
+    let cutoff = chrono::Duration::from_std(retention)
+        .ok()
+        .and_then(|retention| Utc::now().checked_sub_signed(retention));
+    let Some(cutoff) = cutoff else {
+        tracing::warn!("Weather history retention {:?} is out of range", retention);
+        return Ok(0);
+    };
+    connection.execute(
+        "DELETE FROM weather_history WHERE observed_at_ms < ?1",
+        [cutoff.timestamp_millis()],
+    )
+}
+
+fn query(
+    connection: &Connection,
+    location: &str,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    interval: HistoryInterval,
+) -> rusqlite::Result<Vec<WeatherHistoryBucket>> {
+    let width_ms: i64 = match interval {
+        HistoryInterval::Hour => 60 * 60 * 1000,
+        HistoryInterval::Day => 24 * 60 * 60 * 1000,
+    };
+    let mut statement = connection.prepare_cached(
+        "SELECT observed_at_ms / ?4 * ?4 AS start, COUNT(*),
+            MIN(celsius), MAX(celsius), AVG(celsius),
+            MIN(fahrenheit), MAX(fahrenheit), AVG(fahrenheit),
+            MIN(humidity), MAX(humidity), AVG(humidity),
+            MIN(wind_kph), MAX(wind_kph), AVG(wind_kph),
+            MIN(wind_mph), MAX(wind_mph), AVG(wind_mph)
+         FROM weather_history
+         WHERE location = ?1 AND observed_at_ms >= ?2 AND observed_at_ms < ?3
+         GROUP BY start
+         ORDER BY start",
+    )?;
+    let buckets = statement.query_map(
+        (
+            location,
+            from.timestamp_millis(),
+            to.timestamp_millis(),
+            width_ms,
+        ),
+        |row| {
+            Ok(WeatherHistoryBucket {
+                start: Utc
+                    .timestamp_millis_opt(row.get(0)?)
+                    .single()
+                    .unwrap_or_default(),
+                samples: row.get(1)?,
+                celsius: aggregate(row, 2)?.unwrap_or(EMPTY),
+                fahrenheit: aggregate(row, 5)?.unwrap_or(EMPTY),
+                humidity: aggregate(row, 8)?,
+                wind_kph: aggregate(row, 11)?,
+                wind_mph: aggregate(row, 14)?,
+            })
+        },
+    )?;
+    buckets.collect()
+}
+
+const EMPTY: Aggregate = Aggregate {
+    min: 0.0,
+    max: 0.0,
+    avg: 0.0,
+};
+
+/// The min, max and avg columns starting at `first`, none when every
+/// observation left the measurement out.
+fn aggregate(row: &Row, first: usize) -> rusqlite::Result<Option<Aggregate>> {
+    let min: Option<f64> = row.get(first)?;
+    let max: Option<f64> = row.get(first + 1)?;
+    let avg: Option<f64> = row.get(first + 2)?;
+    Ok(min
+        .zip(max)
+        .zip(avg)
+        .map(|((min, max), avg)| Aggregate { min, max, avg }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history(dir: &tempfile::TempDir, retention_days: u64) -> WeatherHistory {
+        WeatherHistory::open(HistoryConfig {
+            path: dir.path().join("history.db"),
+            retention: Duration::from_secs(retention_days * 24 * 60 * 60),
+        })
+        .unwrap()
+    }
+
+    fn at(day: u32, hour: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 6, day, hour, 0, 0).unwrap()
+    }
+
+    fn weather(celsius: f64, humidity: Option<u8>, observed_at: DateTime<Utc>) -> WeatherResponse {
+        WeatherResponse {
+            city: String::from("Roanoke"),
+            state: String::from("Texas"),
+            celcius: celsius,
+            farenheight: celsius * 9.0 / 5.0 + 32.0,
+            humidity,
+            observed_at: Some(observed_at),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn aggregates_each_interval() {
+        let dir = tempfile::tempdir().unwrap();
+        let history = history(&dir, 30);
+        history.record("76262", &weather(20.0, Some(60), at(1, 6)), at(1, 6));
+        history.record("76262", &weather(30.0, None, at(1, 15)), at(1, 15));
+        history.record("76262", &weather(25.0, Some(40), at(2, 6)), at(2, 6));
+        history.record("10001", &weather(10.0, Some(90), at(1, 6)), at(1, 6));
+
+        let days = history
+            .query("76262", at(1, 0), at(3, 0), HistoryInterval::Day)
+            .await
+            .unwrap();
+        assert_eq!(2, days.len());
+        assert_eq!(at(1, 0), days[0].start);
+        assert_eq!(2, days[0].samples);
+        assert_eq!(
+            Aggregate {
+                min: 20.0,
+                max: 30.0,
+                avg: 25.0
+            },
+            days[0].celsius
+        );
+        assert_eq!(86.0, days[0].fahrenheit.max);
+        assert_eq!(Some(60.0), days[0].humidity.map(|h| h.avg));
+        assert_eq!(None, days[0].wind_kph);
+        assert_eq!(at(2, 0), days[1].start);
+
+        let hours = history
+            .query("76262", at(1, 0), at(2, 0), HistoryInterval::Hour)
+            .await
+            .unwrap();
+        assert_eq!(
+            vec![at(1, 6), at(1, 15)],
+            hours.iter().map(|b| b.start).collect::<Vec<_>>()
+        );
+        assert_eq!(None, hours[1].humidity);
+    }
+
+    #[tokio::test]
+    async fn records_an_observation_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let history = history(&dir, 30);
+        history.record("76262", &weather(20.0, None, at(1, 6)), at(1, 6));
+        history.record("76262", &weather(20.0, None, at(1, 6)), at(1, 7));
+
+        let days = history
+            .query("76262", at(1, 0), at(2, 0), HistoryInterval::Day)
+            .await
+            .unwrap();
+        assert_eq!(1, days[0].samples);
+    }
+
+    #[tokio::test]
+    async fn forgets_observations_past_retention() {
+        let dir = tempfile::tempdir().unwrap();
+        let history = history(&dir, 1);
+        let now = Utc::now();
+        let old = now - chrono::Duration::days(2);
+        let recent = now - chrono::Duration::hours(1);
+        history.record("76262", &weather(20.0, None, old), old);
+        history.record("76262", &weather(25.0, None, recent), recent);
+        let samples = || async {
+            history
+                .query("76262", old, now, HistoryInterval::Day)
+                .await
+                .unwrap()
+                .iter()
+                .map(|b| b.samples)
+                .sum::<u32>()
+        };
+        assert_eq!(2, samples().await);
+
+        let forgotten = forget_past(
+            &history.connection.lock().unwrap(),
+            history.config.retention,
+        );
+        assert_eq!(1, forgotten.unwrap());
+        assert_eq!(1, samples().await);
+
+        let forgotten = forget_past(&history.connection.lock().unwrap(), Duration::MAX);
+        assert_eq!(0, forgotten.unwrap());
+        assert_eq!(1, samples().await);
+    }
+}
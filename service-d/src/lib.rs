@@ -0,0 +1,1504 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, HeaderName, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{delete, get, post},
+    Json, Router,
+};
+use chrono::Utc;
+use futures_util::{stream, StreamExt};
+use opentelemetry::propagation::TextMapPropagator;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use reqwest::{Error, Url};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use service_common::fault;
+use std::{
+    collections::{BTreeSet, HashMap},
+    future::Future,
+    time::Duration,
+};
+use tracing::{instrument, Instrument, Span};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use utoipa::OpenApi;
+
+use service_models::v1::{
+    service_d::{
+        Aggregate, Alert, AlertNotification, AlertSeverity, AlertSubscription,
+        AlertSubscriptionRequest, AlertsQuery, AlertsResponse, ForecastDay, ForecastQuery,
+        ForecastResponse, HistoryInterval, HistoryQuery, Prefix, Units, WeatherBatchRequest,
+        WeatherBatchResponse, WeatherBatchResult, WeatherHistoryBucket, WeatherHistoryResponse,
+        WeatherResponse,
+    },
+    HealthCheck,
+};
+use service_models::v2;
+
+pub use crate::alerts::{signature, Alerts, AlertsConfig, SIGNATURE_HEADER};
+use crate::cache::Lookup;
+pub use crate::cache::{CacheBackend, CacheConfig, CacheError, WeatherCache};
+pub use crate::config::{BatchConfig, Config};
+pub use crate::history::{HistoryConfig, WeatherHistory};
+use crate::locale::Locale;
+use crate::location::Location;
+pub use crate::models::AppState;
+use crate::models::{
+    weather_v2, WeatherApiAlertsResponse, WeatherApiForecastResponse, WeatherApiResponse,
+};
+mod alerts;
+mod cache;
+mod config;
+mod history;
+mod locale;
+mod location;
+mod models;
+
+/// Set on cached responses, `true` once the entry is past its ttl.
+const STALE_HEADER: &str = "x-stale";
+const DEFAULT_FORECAST_DAYS: u8 = 3;
+/// The most the weather provider forecasts.
+const MAX_FORECAST_DAYS: u8 = 14;
+/// How far back `GET /weather/history` looks when not given `from`.
+const DEFAULT_HISTORY_DAYS: i64 = 7;
+
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        handler,
+        batch,
+        forecast,
+        get_weather_v2,
+        get_history,
+        get_alerts,
+        subscribe,
+        unsubscribe,
+        health
+    ),
+    components(schemas(
+        WeatherResponse,
+        Units,
+        v2::service_d::WeatherResponse,
+        WeatherBatchRequest,
+        WeatherBatchResponse,
+        WeatherBatchResult,
+        ForecastResponse,
+        ForecastDay,
+        WeatherHistoryResponse,
+        WeatherHistoryBucket,
+        HistoryInterval,
+        Aggregate,
+        AlertsResponse,
+        Alert,
+        AlertSeverity,
+        AlertSubscriptionRequest,
+        AlertSubscription,
+        AlertNotification,
+        HealthCheck
+    ))
+)]
+struct ApiDoc;
+
+/// Builds the service's routes with state derived from `config`. The router
+/// can be served on its own or nested under a prefix in another axum app.
+pub fn build_router(config: &Config) -> Router {
+    let app = app(AppState::from_config(config));
+    match &config.faults {
+        Some(faults) => fault::inject(app, faults.clone()),
+        None => app,
+    }
+}
+
+pub fn app(app_state: AppState) -> Router {
+    let app = Router::new()
+        .route("/weather", get(handler))
+        .route("/weather/batch", post(batch))
+        .route("/weather/history", get(get_history))
+        .route("/v2/weather", get(get_weather_v2))
+        .route("/forecast", get(forecast))
+        .route("/alerts", get(get_alerts))
+        .route("/alerts/subscriptions", post(subscribe))
+        .route("/alerts/subscriptions/:id", delete(unsubscribe))
+        .route("/health", get(health))
+        .route("/openapi.json", get(openapi))
+        .with_state(app_state);
+    #[cfg(feature = "swagger-ui")]
+    let app = app.merge(
+        utoipa_swagger_ui::SwaggerUi::new("/swagger-ui")
+            .config(utoipa_swagger_ui::Config::from("/openapi.json")),
+    );
+    app
+}
+
+#[utoipa::path(
+    get,
+    operation_id = "get_weather",
+    path = "/weather",
+    params(
+        Prefix,
+        ("Accept-Language" = Option<String>, Header, description = "Language of the condition text, English when none given is supported")
+    ),
+    responses(
+        (status = 200, description = "Current conditions for the zip", body = WeatherResponse,
+            headers(
+                ("content-language" = String, description = "Language of the condition text"),
+                ("age" = u64, description = "Seconds since the conditions were fetched, when caching is on"),
+                ("x-stale" = bool, description = "Whether the conditions are past their ttl, when caching is on")
+            )
+        ),
+        (status = 400, description = "fields names an unknown field, units is not metric, imperial or both, or the weather provider rejected the request or returned an unexpected body"),
+        (status = 422, description = "zip is missing or not a location"),
+        (status = 500, description = "The weather provider could not be reached")
+    )
+)]
+#[instrument(name = "GET /weather", fields(location, cache_age, stale))]
+async fn handler(
+    State(state): State<AppState>,
+    query: Query<Prefix>,
+    headers: HeaderMap,
+) -> Result<Response, StatusCode> {
+    continue_trace(&state, &headers);
+    let location = parse_location(query.zip.as_deref())?;
+
+    tracing::info!("(Request)={}", location);
+    let units = query.units.unwrap_or_default();
+    let mut fields = select_fields(query.fields.as_deref())?;
+    fields.retain(|field| locale::keeps(units, field));
+    let locale = Locale::negotiate(&headers);
+
+    let (mut weather, freshness) = cached_weather(&state, location).await?;
+    weather.condition = weather
+        .condition
+        .map(|text| locale.condition(weather.condition_code, &text));
+    Ok(respond(only(&weather, &fields), freshness, locale))
+}
+
+#[utoipa::path(
+    get,
+    operation_id = "get_weather_v2",
+    path = "/v2/weather",
+    params(
+        v2::service_d::WeatherQuery,
+        ("Accept-Language" = Option<String>, Header, description = "Language of the condition text and labels, English when none given is supported")
+    ),
+    responses(
+        (status = 200, description = "Current conditions for the zip", body = v2::service_d::WeatherResponse,
+            headers(
+                ("content-language" = String, description = "Language of the condition text and labels"),
+                ("age" = u64, description = "Seconds since the conditions were fetched, when caching is on"),
+                ("x-stale" = bool, description = "Whether the conditions are past their ttl, when caching is on")
+            )
+        ),
+        (status = 400, description = "units is not metric, imperial or both, or the weather provider rejected the request or returned an unexpected body"),
+        (status = 422, description = "zip is missing or not a location"),
+        (status = 500, description = "The weather provider could not be reached")
+    )
+)]
+#[instrument(name = "GET /v2/weather", fields(location, cache_age, stale))]
+async fn get_weather_v2(
+    State(state): State<AppState>,
+    query: Query<v2::service_d::WeatherQuery>,
+    headers: HeaderMap,
+) -> Result<Response, StatusCode> {
+    continue_trace(&state, &headers);
+    let location = parse_location(query.zip.as_deref())?;
+
+    tracing::info!("(Request)={}", location);
+    let units = query.units.unwrap_or_default();
+    let locale = Locale::negotiate(&headers);
+
+    let (weather, freshness) = cached_weather(&state, location).await?;
+    Ok(respond(
+        weather_v2(weather, units, locale),
+        freshness,
+        locale,
+    ))
+}
+
+#[utoipa::path(
+    post,
+    operation_id = "post_weather_batch",
+    path = "/weather/batch",
+    request_body = WeatherBatchRequest,
+    responses(
+        (status = 200, description = "Current conditions for each location, or the status and reason there are none", body = WeatherBatchResponse),
+        (status = 400, description = "No locations, or more than the configured maximum")
+    )
+)]
+#[instrument(
+    name = "POST /weather/batch",
+    skip(request),
+    fields(locations, lookups)
+)]
+async fn batch(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<WeatherBatchRequest>,
+) -> Result<Json<WeatherBatchResponse>, StatusCode> {
+    continue_trace(&state, &headers);
+    let count = request.locations.len();
+    if count == 0 || count > state.batch.max_locations {
+        tracing::error!(
+            "A batch needs between 1 and {} locations, got {}",
+            state.batch.max_locations,
+            count
+        );
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let parsed: Vec<_> = request
+        .locations
+        .iter()
+        .map(|location| Location::parse(Some(location)).map(|l| l.query()))
+        .collect();
+    let lookups: BTreeSet<String> = parsed.iter().flatten().cloned().collect();
+    let span = Span::current();
+    span.record("locations", count);
+    span.record("lookups", lookups.len());
+    tracing::info!("(Request)={} locations, {} lookups", count, lookups.len());
+
+    let found: HashMap<String, Result<WeatherResponse, StatusCode>> = stream::iter(lookups)
+        .map(|location| {
+            let state = state.clone();
+            let span = tracing::info_span!("batch-lookup", location = %location);
+            async move {
+                let weather = cached_weather(&state, location.clone()).await;
+                (location, weather.map(|(weather, _)| weather))
+            }
+            .instrument(span)
+        })
+        .buffer_unordered(state.batch.concurrency)
+        .collect()
+        .await;
+
+    let results = request
+        .locations
+        .into_iter()
+        .zip(parsed)
+        .map(|(location, parsed)| {
+            let weather = parsed
+                .map_err(|e| (StatusCode::UNPROCESSABLE_ENTITY, e.to_string()))
+                .and_then(|query| match &found[&query] {
+                    Ok(weather) => Ok(weather.clone()),
+                    Err(status) => Err((*status, lookup_error(*status))),
+                });
+            match weather {
+                Ok(weather) => WeatherBatchResult {
+                    location,
+                    status: StatusCode::OK.as_u16(),
+                    weather: Some(WeatherResponse {
+                        city: weather.city,
+                        state: weather.state,
+                        celcius: weather.celcius,
+                        farenheight: weather.farenheight,
+                        ..Default::default()
+                    }),
+                    error: None,
+                },
+                Err((status, error)) => WeatherBatchResult {
+                    location,
+                    status: status.as_u16(),
+                    weather: None,
+                    error: Some(error),
+                },
+            }
+        })
+        .collect();
+    Ok(Json(WeatherBatchResponse { results }))
+}
+
+/// Why a batch lookup failed, matching the statuses `fetch_provider` returns.
+fn lookup_error(status: StatusCode) -> String {
+    match status {
+        StatusCode::BAD_REQUEST => String::from("the weather provider rejected the location"),
+        _ => String::from("the weather provider could not be reached"),
+    }
+}
+
+/// Current conditions for the provider query `location`, through the cache.
+async fn cached_weather(
+    state: &AppState,
+    location: String,
+) -> Result<(WeatherResponse, Option<Freshness>), StatusCode> {
+    let key = format!("weather:{}", location);
+    let provider = state.clone();
+    serve_cached(state, key, move || async move {
+        fetch_weather(&provider, &location).await
+    })
+    .await
+}
+
+/// Active alerts for the provider query `location`, through the cache.
+async fn cached_alerts(
+    state: &AppState,
+    location: String,
+) -> Result<(AlertsResponse, Option<Freshness>), StatusCode> {
+    let key = format!("alerts:{}", location);
+    let provider = state.clone();
+    serve_cached(state, key, move || async move {
+        fetch_alerts(&provider, &location).await
+    })
+    .await
+}
+
+/// Parses `fields`, rejecting names `WeatherResponse` does not have.
+fn select_fields(fields: Option<&str>) -> Result<Vec<&str>, StatusCode> {
+    let Some(fields) = fields else {
+        return Ok(WeatherResponse::DEFAULT_FIELDS.to_vec());
+    };
+    let selected: Vec<&str> = fields
+        .split(',')
+        .map(str::trim)
+        .filter(|f| !f.is_empty())
+        .collect();
+    match selected
+        .iter()
+        .find(|f| !WeatherResponse::FIELDS.contains(f))
+    {
+        Some(unknown) => {
+            tracing::error!("Unknown field={}", unknown);
+            Err(StatusCode::BAD_REQUEST)
+        }
+        None if selected.is_empty() => Err(StatusCode::BAD_REQUEST),
+        None => Ok(selected),
+    }
+}
+
+/// `weather` with only `fields` kept. Fields the provider did not report are
+/// left out rather than sent as null.
+fn only(weather: &WeatherResponse, fields: &[&str]) -> Value {
+    let mut value = serde_json::to_value(weather).unwrap_or_default();
+    if let Value::Object(map) = &mut value {
+        map.retain(|k, _| fields.contains(&k.as_str()));
+    }
+    value
+}
+
+#[utoipa::path(
+    get,
+    operation_id = "get_forecast",
+    path = "/forecast",
+    params(
+        ForecastQuery,
+        ("Accept-Language" = Option<String>, Header, description = "Language of the condition text, English when none given is supported")
+    ),
+    responses(
+        (status = 200, description = "Daily forecast for the zip", body = ForecastResponse,
+            headers(
+                ("content-language" = String, description = "Language of the condition text"),
+                ("age" = u64, description = "Seconds since the forecast was fetched, when caching is on"),
+                ("x-stale" = bool, description = "Whether the forecast is past its ttl, when caching is on")
+            )
+        ),
+        (status = 400, description = "days is out of range, units is not metric, imperial or both, or the weather provider rejected the request or returned an unexpected body"),
+        (status = 422, description = "zip is missing or not a location"),
+        (status = 500, description = "The weather provider could not be reached")
+    )
+)]
+#[instrument(name = "GET /forecast", fields(location, cache_age, stale))]
+async fn forecast(
+    State(state): State<AppState>,
+    query: Query<ForecastQuery>,
+    headers: HeaderMap,
+) -> Result<Response, StatusCode> {
+    continue_trace(&state, &headers);
+    let location = parse_location(query.zip.as_deref())?;
+    let days = query.days.unwrap_or(DEFAULT_FORECAST_DAYS);
+    if !(1..=MAX_FORECAST_DAYS).contains(&days) {
+        tracing::error!("days must be between 1 and {}", MAX_FORECAST_DAYS);
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    tracing::info!("(Request)={} for {} days", location, days);
+
+    let key = format!("forecast:{}:{}", location, days);
+    let provider = state.clone();
+    let (mut forecast, freshness) = serve_cached(&state, key, move || async move {
+        fetch_forecast(&provider, &location, days).await
+    })
+    .await?;
+    let locale = Locale::negotiate(&headers);
+    for day in &mut forecast.days {
+        day.condition = locale.condition(None, &day.condition);
+    }
+    let mut body = serde_json::to_value(&forecast).unwrap_or_default();
+    locale::retain_units(&mut body, query.units.unwrap_or_default());
+    Ok(respond(body, freshness, locale))
+}
+
+#[utoipa::path(
+    get,
+    operation_id = "get_weather_history",
+    path = "/weather/history",
+    params(HistoryQuery),
+    responses(
+        (status = 200, description = "The conditions recorded for the zip, aggregated per interval", body = WeatherHistoryResponse),
+        (status = 400, description = "from is not before to, or interval is not hour or day"),
+        (status = 404, description = "History recording is not turned on"),
+        (status = 422, description = "zip is missing or not a location"),
+        (status = 500, description = "The history could not be read")
+    )
+)]
+#[instrument(name = "GET /weather/history", fields(location, buckets))]
+async fn get_history(
+    State(state): State<AppState>,
+    query: Query<HistoryQuery>,
+    headers: HeaderMap,
+) -> Result<Json<WeatherHistoryResponse>, StatusCode> {
+    continue_trace(&state, &headers);
+    let Some(history) = &state.history else {
+        tracing::error!("Weather history is not recorded, set WEATHER_HISTORY_PATH");
+        return Err(StatusCode::NOT_FOUND);
+    };
+    let location = parse_location(query.zip.as_deref())?;
+    let to = query.to.unwrap_or_else(Utc::now);
+    let from = query
+        .from
+        .unwrap_or(to - chrono::Duration::days(DEFAULT_HISTORY_DAYS));
+    if from >= to {
+        tracing::error!("from={} must be before to={}", from, to);
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let interval = query.interval.unwrap_or_default();
+
+    tracing::info!("(Request)={} from {} to {}", location, from, to);
+    let buckets = history
+        .query(&location, from, to, interval)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error reading weather history: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    Span::current().record("buckets", buckets.len());
+    Ok(Json(WeatherHistoryResponse {
+        location,
+        from,
+        to,
+        interval,
+        buckets,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    operation_id = "get_alerts",
+    path = "/alerts",
+    params(AlertsQuery),
+    responses(
+        (status = 200, description = "Active alerts for the zip", body = AlertsResponse,
+            headers(
+                ("age" = u64, description = "Seconds since the alerts were fetched, when caching is on"),
+                ("x-stale" = bool, description = "Whether the alerts are past their ttl, when caching is on")
+            )
+        ),
+        (status = 400, description = "The weather provider rejected the request or returned an unexpected body"),
+        (status = 422, description = "zip is missing or not a location"),
+        (status = 500, description = "The weather provider could not be reached")
+    )
+)]
+#[instrument(name = "GET /alerts", fields(location, cache_age, stale))]
+async fn get_alerts(
+    State(state): State<AppState>,
+    query: Query<AlertsQuery>,
+    headers: HeaderMap,
+) -> Result<Response, StatusCode> {
+    continue_trace(&state, &headers);
+    let location = parse_location(query.zip.as_deref())?;
+
+    tracing::info!("(Request)={}", location);
+    let (alerts, freshness) = cached_alerts(&state, location).await?;
+    Ok(respond(alerts, freshness, Locale::default()))
+}
+
+#[utoipa::path(
+    post,
+    operation_id = "post_alert_subscription",
+    path = "/alerts/subscriptions",
+    request_body = AlertSubscriptionRequest,
+    responses(
+        (status = 201, description = "The webhook is registered; new alerts are POSTed to it as an AlertNotification", body = AlertSubscription),
+        (status = 422, description = "location is not a location, webhook_url is not an http or https url on a public address, or secret is empty"),
+        (status = 503, description = "No more subscriptions are taken")
+    )
+)]
+#[instrument(name = "POST /alerts/subscriptions", skip_all, fields(location))]
+async fn subscribe(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<AlertSubscriptionRequest>,
+) -> Result<(StatusCode, Json<AlertSubscription>), StatusCode> {
+    continue_trace(&state, &headers);
+    let location = parse_location(Some(&request.location))?;
+    if let Err(e) = state.alerts.check_webhook(&request.webhook_url).await {
+        tracing::error!("Invalid webhook url: {}", e);
+        return Err(StatusCode::UNPROCESSABLE_ENTITY);
+    }
+    if request.secret.is_empty() {
+        tracing::error!("A subscription needs a secret to sign deliveries with");
+        return Err(StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    let Some(subscription) = state.alerts.subscribe(request, location) else {
+        tracing::error!(
+            "All {} alert subscriptions are taken",
+            state.alerts.config.max_subscriptions
+        );
+        return Err(StatusCode::SERVICE_UNAVAILABLE);
+    };
+    tracing::info!("(Subscribed)={}", subscription.id);
+    if state.alerts.start_polling() {
+        tokio::spawn(alerts::poll(state.clone()));
+    }
+    Ok((StatusCode::CREATED, Json(subscription)))
+}
+
+#[utoipa::path(
+    delete,
+    operation_id = "delete_alert_subscription",
+    path = "/alerts/subscriptions/{id}",
+    params(
+        ("id" = String, Path, description = "The subscription's id"),
+        ("x-signature-256" = String, Header, description = "`sha256=` and the hex HMAC-SHA256 of the id, keyed by the subscription's secret")
+    ),
+    responses(
+        (status = 204, description = "No more alerts are sent to the webhook"),
+        (status = 401, description = "x-signature-256 is missing or not the subscription's signature of its id"),
+        (status = 404, description = "There is no such subscription")
+    )
+)]
+#[instrument(name = "DELETE /alerts/subscriptions", skip_all, fields(id))]
+async fn unsubscribe(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> StatusCode {
+    continue_trace(&state, &headers);
+    Span::current().record("id", id.as_str());
+    let signature = headers
+        .get(alerts::SIGNATURE_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    match state.alerts.unsubscribe(&id, signature) {
+        Ok(()) => {
+            tracing::info!("(Unsubscribed)={}", id);
+            StatusCode::NO_CONTENT
+        }
+        Err(e @ alerts::UnsubscribeError::NotFound(_)) => {
+            tracing::error!("{}", e);
+            StatusCode::NOT_FOUND
+        }
+        Err(e @ alerts::UnsubscribeError::BadSignature(_)) => {
+            tracing::error!("{}", e);
+            StatusCode::UNAUTHORIZED
+        }
+    }
+}
+
+/// The provider's query for `zip`, recorded on the request span.
+fn parse_location(zip: Option<&str>) -> Result<String, StatusCode> {
+    match Location::parse(zip) {
+        Ok(location) => {
+            let query = location.query();
+            Span::current().record("location", query.as_str());
+            Ok(query)
+        }
+        Err(e) => {
+            tracing::error!("Invalid location: {}", e);
+            Err(StatusCode::UNPROCESSABLE_ENTITY)
+        }
+    }
+}
+
+/// Continues the caller's trace when one is passed along.
+fn continue_trace(state: &AppState, headers: &HeaderMap) {
+    let traceparent = headers.get("traceparent").and_then(|v| v.to_str().ok());
+    if let (true, Some(traceparent)) = (state.has_apm, traceparent) {
+        let mut fields: HashMap<String, String> = HashMap::new();
+        fields.insert("traceparent".to_string(), String::from(traceparent));
+
+        let propagator = TraceContextPropagator::new();
+        let context = propagator.extract(&fields);
+        let span = tracing::Span::current();
+        span.set_parent(context);
+    }
+}
+
+/// How old a cached answer is.
+struct Freshness {
+    age: Duration,
+    stale: bool,
+}
+
+/// Answers from the cache when it is on, calling `fetch` on a miss, in the
+/// background once an entry goes stale, and again once it has expired.
+async fn serve_cached<T, F, Fut>(
+    state: &AppState,
+    key: String,
+    fetch: F,
+) -> Result<(T, Option<Freshness>), StatusCode>
+where
+    T: Serialize + DeserializeOwned + Send + Sync + 'static,
+    F: FnOnce() -> Fut + Send + 'static,
+    Fut: Future<Output = Result<T, StatusCode>> + Send + 'static,
+{
+    let Some(cache) = &state.cache else {
+        return Ok((fetch().await?, None));
+    };
+    let served = |value, age, stale| Ok((value, Some(Freshness { age, stale })));
+    let cached = match cache.lookup::<T>(&key).await {
+        Lookup::Fresh(cached) => return served(cached.value, cached.age, false),
+        Lookup::Revalidate(cached) => {
+            if cache.start_refresh(&key) {
+                refresh(cache.clone(), key, fetch);
+            }
+            return served(cached.value, cached.age, true);
+        }
+        Lookup::Expired(cached) => Some(cached),
+        Lookup::Miss => None,
+    };
+
+    match fetch().await {
+        Ok(value) => {
+            cache.store(&key, &value).await;
+            served(value, Duration::ZERO, false)
+        }
+        Err(status) => match cached {
+            Some(cached) if cached.usable_on_error(&cache.config) => {
+                tracing::warn!("Serving stale {} after {}", key, status);
+                served(cached.value, cached.age, true)
+            }
+            _ => Err(status),
+        },
+    }
+}
+
+/// Sends `body` in `locale`'s language, recording a cached answer's age on
+/// the request span and in the response headers.
+fn respond<B: Serialize>(body: B, freshness: Option<Freshness>, locale: Locale) -> Response {
+    let mut response = (
+        [
+            (header::CONTENT_LANGUAGE, locale.language()),
+            (header::VARY, "accept-language"),
+        ],
+        Json(body),
+    )
+        .into_response();
+    let Some(Freshness { age, stale }) = freshness else {
+        return response;
+    };
+    let span = Span::current();
+    span.record("cache_age", age.as_secs());
+    span.record("stale", stale);
+    let headers = response.headers_mut();
+    headers.insert(header::AGE, HeaderValue::from(age.as_secs()));
+    headers.insert(
+        HeaderName::from_static(STALE_HEADER),
+        HeaderValue::from_static(if stale { "true" } else { "false" }),
+    );
+    response
+}
+
+/// Fetches `key` again in the background while the stale entry is served.
+fn refresh<T, F, Fut>(cache: WeatherCache, key: String, fetch: F)
+where
+    T: Serialize + Send + Sync + 'static,
+    F: FnOnce() -> Fut + Send + 'static,
+    Fut: Future<Output = Result<T, StatusCode>> + Send + 'static,
+{
+    let span = tracing::info_span!("refresh-weather", key = %key);
+    tokio::spawn(
+        async move {
+            match fetch().await {
+                Ok(value) => cache.store(&key, &value).await,
+                Err(status) => tracing::error!("Refreshing {} failed: {}", key, status),
+            }
+            cache.finish_refresh(&key);
+        }
+        .instrument(span),
+    );
+}
+
+async fn fetch_weather(state: &AppState, location: &str) -> Result<WeatherResponse, StatusCode> {
+    let response: WeatherApiResponse =
+        fetch_provider(state, "/current.json", &[("q", location)]).await?;
+    let weather = WeatherResponse::from(response);
+    if let Some(history) = &state.history {
+        history.record(location, &weather, Utc::now());
+    }
+    Ok(weather)
+}
+
+async fn fetch_forecast(
+    state: &AppState,
+    location: &str,
+    days: u8,
+) -> Result<ForecastResponse, StatusCode> {
+    let days = days.to_string();
+    let response: WeatherApiForecastResponse =
+        fetch_provider(state, "/forecast.json", &[("q", location), ("days", &days)]).await?;
+    Ok(ForecastResponse::from(response))
+}
+
+async fn fetch_alerts(state: &AppState, location: &str) -> Result<AlertsResponse, StatusCode> {
+    let response: WeatherApiAlertsResponse =
+        fetch_provider(state, "/alerts.json", &[("q", location)]).await?;
+    Ok(AlertsResponse::from(response))
+}
+
+/// Calls the weather provider with the current trace context attached. The
+/// query is url encoded, so it is safe to pass caller input in it.
+async fn fetch_provider<T: DeserializeOwned>(
+    state: &AppState,
+    path: &str,
+    query: &[(&str, &str)],
+) -> Result<T, StatusCode> {
+    let mut url = match Url::parse(&format!("{}{}", state.weather_api_url, path)) {
+        Ok(url) => url,
+        Err(e) => {
+            tracing::error!("Invalid weather api url: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+    url.query_pairs_mut()
+        .extend_pairs(query)
+        .append_pair("key", &state.weather_api_key);
+    let ctx = Span::current().context();
+    let propagator = TraceContextPropagator::new();
+    let mut fields = HashMap::new();
+
+    propagator.inject_context(&ctx, &mut fields);
+    let headers = fields
+        .into_iter()
+        .map(|(k, v)| {
+            (
+                HeaderName::try_from(k).unwrap(),
+                HeaderValue::try_from(v).unwrap(),
+            )
+        })
+        .collect();
+    tracing::info!("(Request)={}", url.as_str());
+
+    let response = state.http_client.get(url).headers(headers).send().await;
+
+    match response {
+        Ok(r) => {
+            if r.status().is_success() {
+                let j: Result<T, Error> = r.json().await;
+                match j {
+                    Ok(m) => Ok(m),
+                    Err(e) => {
+                        tracing::error!("Error parsing: {}", e);
+                        Err(StatusCode::BAD_REQUEST)
+                    }
+                }
+            } else {
+                tracing::error!("Bad request={:?}", r.status());
+                Err(StatusCode::BAD_REQUEST)
+            }
+        }
+        Err(e) => {
+            tracing::error!("Error requesting: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    operation_id = "get_health",
+    path = "/health",
+    responses((status = 200, description = "Service is healthy", body = HealthCheck))
+)]
+async fn health() -> Result<impl IntoResponse, StatusCode> {
+    let healthy = HealthCheck {
+        status: String::from("Healthy"),
+    };
+
+    Ok(Json(healthy))
+}
+
+async fn openapi() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::{to_bytes, Body};
+    use axum::http::Request;
+    use contract_testing::{verify, Contract};
+    use reqwest::Client;
+    use serde_json::json;
+    use service_common::tls::ClientTlsConfig;
+    use std::sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    };
+    use tower::ServiceExt;
+
+    fn config(weather_api_url: String) -> Config {
+        Config {
+            bind_address: String::from("127.0.0.1:0"),
+            tracing_enabled: false,
+            agent_address: None,
+            tls: None,
+            faults: None,
+            upstream_tls: ClientTlsConfig::default(),
+            weather_api_url,
+            weather_api_key: String::from("test"),
+            weather_cache: None,
+            weather_batch: BatchConfig::default(),
+            weather_alerts: AlertsConfig::default(),
+            weather_history: None,
+        }
+    }
+
+    async fn call(router: Router, uri: &str) -> (StatusCode, serde_json::Value) {
+        let request = Request::builder().uri(uri).body(Body::empty()).unwrap();
+        let response = router.oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or_default())
+    }
+
+    async fn weather_provider(provider_state: Option<String>) -> String {
+        let stub = match provider_state.as_deref() {
+            Some("weather for 76262 is available") => Router::new().route(
+                "/current.json",
+                get(|| async {
+                    Json(json!({
+                        "location": {"name": "Roanoke", "region": "Texas", "lat": 33.0, "lon": -97.23},
+                        "current": {
+                            "temp_c": 20.0,
+                            "temp_f": 68.0,
+                            "last_updated_epoch": 1717243200,
+                            "humidity": 61,
+                            "wind_kph": 14.4,
+                            "wind_mph": 8.9,
+                            "wind_degree": 190,
+                            "wind_dir": "S",
+                            "feelslike_c": 20.5,
+                            "feelslike_f": 68.9,
+                            "condition": {"text": "Partly cloudy", "code": 1003},
+                            "uv": 6.0
+                        }
+                    }))
+                }),
+            ),
+            Some("a forecast for 76262 is available") => Router::new().route(
+                "/forecast.json",
+                get(|| async {
+                    Json(json!({
+                        "location": {"name": "Roanoke", "region": "Texas"},
+                        "forecast": {"forecastday": [{
+                            "date": "2024-06-01",
+                            "day": {
+                                "maxtemp_c": 31.0,
+                                "maxtemp_f": 87.8,
+                                "mintemp_c": 21.0,
+                                "mintemp_f": 69.8,
+                                "daily_chance_of_rain": 20,
+                                "condition": {"text": "Partly cloudy"}
+                            }
+                        }]}
+                    }))
+                }),
+            ),
+            Some("alerts for 33101 are available") => Router::new().route(
+                "/alerts.json",
+                get(|| async {
+                    Json(json!({
+                        "location": {"name": "Miami", "region": "Florida"},
+                        "alerts": {"alert": [{
+                            "headline": "Tropical Storm Warning issued for Miami-Dade",
+                            "severity": "Severe",
+                            "areas": "Miami-Dade; Broward",
+                            "event": "Tropical Storm Warning",
+                            "note": "",
+                            "effective": "2024-06-01T08:00:00-04:00",
+                            "expires": "",
+                            "desc": "Tropical storm force winds are expected.",
+                            "instruction": ""
+                        }]}
+                    }))
+                }),
+            ),
+            _ => Router::new().route("/current.json", get(|| async { StatusCode::BAD_REQUEST })),
+        };
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, stub).await.unwrap() });
+        url
+    }
+
+    #[test]
+    fn openapi_spec_matches_committed_file() {
+        service_common::openapi::assert_matches_committed(
+            &ApiDoc::openapi().to_pretty_json().unwrap(),
+            concat!(env!("CARGO_MANIFEST_DIR"), "/openapi.json"),
+        );
+    }
+
+    #[tokio::test]
+    async fn honours_service_b_contract() {
+        let contract = Contract::load("service-b", "service-d").unwrap();
+        let result = verify(&contract, |provider_state| async move {
+            app(AppState {
+                has_apm: false,
+                http_client: Client::new(),
+                weather_api_url: weather_provider(provider_state).await,
+                weather_api_key: String::from("test"),
+                cache: None,
+                batch: BatchConfig::default(),
+                alerts: Alerts::new(AlertsConfig::default()),
+                history: None,
+            })
+        })
+        .await;
+        if let Err(e) = result {
+            panic!("{}", e);
+        }
+    }
+
+    #[tokio::test]
+    async fn maps_the_provider_response() {
+        let weather_api_url =
+            weather_provider(Some(String::from("weather for 76262 is available"))).await;
+        let (status, body) =
+            call(build_router(&config(weather_api_url)), "/weather?zip=76262").await;
+        assert_eq!(StatusCode::OK, status);
+        assert_eq!(
+            json!({"city": "Roanoke", "state": "Texas", "celcius": 20.0, "farenheight": 68.0}),
+            body
+        );
+    }
+
+    #[tokio::test]
+    async fn returns_only_the_selected_fields() {
+        let weather_api_url =
+            weather_provider(Some(String::from("weather for 76262 is available"))).await;
+        let router = build_router(&config(weather_api_url));
+
+        let (status, body) = call(
+            router.clone(),
+            "/weather?zip=76262&fields=city,humidity,wind_kph,wind_direction,condition_code,observed_at,latitude",
+        )
+        .await;
+        assert_eq!(StatusCode::OK, status);
+        assert_eq!(
+            json!({
+                "city": "Roanoke",
+                "humidity": 61,
+                "wind_kph": 14.4,
+                "wind_direction": "S",
+                "condition_code": 1003,
+                "observed_at": "2024-06-01T12:00:00Z",
+                "latitude": 33.0
+            }),
+            body
+        );
+
+        let (status, _) = call(router, "/weather?zip=76262&fields=city,pressure").await;
+        assert_eq!(StatusCode::BAD_REQUEST, status);
+    }
+
+    #[tokio::test]
+    async fn localizes_v2_weather_in_the_requested_units() {
+        let weather_api_url =
+            weather_provider(Some(String::from("weather for 76262 is available"))).await;
+        let router = build_router(&config(weather_api_url));
+
+        let request = Request::builder()
+            .uri("/v2/weather?zip=76262&units=metric")
+            .header(header::ACCEPT_LANGUAGE, "de-DE, en;q=0.5")
+            .body(Body::empty())
+            .unwrap();
+        let response = router.clone().oneshot(request).await.unwrap();
+        assert_eq!(StatusCode::OK, response.status());
+        assert_eq!("de", response.headers()[header::CONTENT_LANGUAGE]);
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(
+            json!({
+                "city": "Roanoke",
+                "state": "Texas",
+                "language": "de",
+                "celsius": 20.0,
+                "celcius": 20.0,
+                "humidity": 61,
+                "wind_kph": 14.4,
+                "wind_degree": 190,
+                "wind_direction": "S",
+                "feels_like_celsius": 20.5,
+                "condition_code": 1003,
+                "condition": "Teilweise bewölkt",
+                "uv_index": 6.0,
+                "observed_at": "2024-06-01T12:00:00Z",
+                "latitude": 33.0,
+                "longitude": -97.23,
+                "labels": {
+                    "celsius": "°C",
+                    "celcius": "°C",
+                    "humidity": "%",
+                    "wind_kph": "km/h",
+                    "wind_degree": "°",
+                    "feels_like_celsius": "°C"
+                }
+            }),
+            body
+        );
+
+        let (status, body) = call(
+            router.clone(),
+            "/weather?zip=76262&units=imperial&fields=city,celcius,farenheight,wind_kph",
+        )
+        .await;
+        assert_eq!(StatusCode::OK, status);
+        assert_eq!(json!({"city": "Roanoke", "farenheight": 68.0}), body);
+
+        let (status, _) = call(router, "/v2/weather?zip=76262&units=kelvin").await;
+        assert_eq!(StatusCode::BAD_REQUEST, status);
+    }
+
+    #[tokio::test]
+    async fn records_fetched_weather_into_history() {
+        let dir = tempfile::tempdir().unwrap();
+        let weather_api_url =
+            weather_provider(Some(String::from("weather for 76262 is available"))).await;
+        let router = build_router(&Config {
+            weather_history: Some(HistoryConfig {
+                path: dir.path().join("history.db"),
+                retention: Duration::from_secs(u32::MAX as u64),
+            }),
+            ..config(weather_api_url)
+        });
+        let (status, _) = call(router.clone(), "/weather?zip=76262").await;
+        assert_eq!(StatusCode::OK, status);
+
+        let (status, body) = call(
+            router.clone(),
+            "/weather/history?zip=Roanoke,%20TX&from=2024-06-01T00:00:00Z&to=2024-06-02T00:00:00Z&interval=hour",
+        )
+        .await;
+        assert_eq!(StatusCode::OK, status);
+        assert_eq!(
+            json!({
+                "location": "76262",
+                "from": "2024-06-01T00:00:00Z",
+                "to": "2024-06-02T00:00:00Z",
+                "interval": "hour",
+                "buckets": [{
+                    "start": "2024-06-01T12:00:00Z",
+                    "samples": 1,
+                    "celsius": {"min": 20.0, "max": 20.0, "avg": 20.0},
+                    "fahrenheit": {"min": 68.0, "max": 68.0, "avg": 68.0},
+                    "humidity": {"min": 61.0, "max": 61.0, "avg": 61.0},
+                    "wind_kph": {"min": 14.4, "max": 14.4, "avg": 14.4},
+                    "wind_mph": {"min": 8.9, "max": 8.9, "avg": 8.9}
+                }]
+            }),
+            body
+        );
+
+        let (status, _) = call(
+            router,
+            "/weather/history?zip=76262&from=2024-06-02T00:00:00Z&to=2024-06-01T00:00:00Z",
+        )
+        .await;
+        assert_eq!(StatusCode::BAD_REQUEST, status);
+
+        let router = build_router(&config(String::from("http://127.0.0.1:9")));
+        let (status, _) = call(router, "/weather/history?zip=76262").await;
+        assert_eq!(StatusCode::NOT_FOUND, status);
+    }
+
+    #[tokio::test]
+    async fn normalizes_the_provider_alerts() {
+        let weather_api_url =
+            weather_provider(Some(String::from("alerts for 33101 are available"))).await;
+        let (status, body) =
+            call(build_router(&config(weather_api_url)), "/alerts?zip=33101").await;
+        assert_eq!(StatusCode::OK, status);
+        let alert = &body["alerts"][0];
+        assert_eq!(16, alert["id"].as_str().unwrap().len());
+        assert_eq!(
+            json!({
+                "id": alert["id"],
+                "event": "Tropical Storm Warning",
+                "headline": "Tropical Storm Warning issued for Miami-Dade",
+                "severity": "severe",
+                "areas": ["Miami-Dade", "Broward"],
+                "description": "Tropical storm force winds are expected.",
+                "effective": "2024-06-01T12:00:00Z"
+            }),
+            *alert
+        );
+    }
+
+    async fn post_subscription(
+        router: Router,
+        request: serde_json::Value,
+    ) -> (StatusCode, serde_json::Value) {
+        let request = Request::post("/alerts/subscriptions")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(request.to_string()))
+            .unwrap();
+        let response = router.oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or_default())
+    }
+
+    async fn delete_subscription(router: Router, id: &str, signature: Option<&str>) -> StatusCode {
+        let mut request = Request::delete(format!("/alerts/subscriptions/{}", id));
+        if let Some(signature) = signature {
+            request = request.header(alerts::SIGNATURE_HEADER, signature);
+        }
+        let request = request.body(Body::empty()).unwrap();
+        router.oneshot(request).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn registers_and_removes_alert_subscriptions() {
+        let router = build_router(&config(String::from("http://127.0.0.1:9")));
+        let subscription = |location: &str, webhook_url: &str, secret: &str| json!({"location": location, "webhook_url": webhook_url, "secret": secret});
+        for (request, expected) in [
+            (
+                subscription("76262", "http://203.0.113.7/hook", "s3cret"),
+                StatusCode::CREATED,
+            ),
+            (
+                subscription("76262", "http://127.0.0.1:9/hook", "s3cret"),
+                StatusCode::UNPROCESSABLE_ENTITY,
+            ),
+            (
+                subscription("nowhere", "http://203.0.113.7/hook", "s3cret"),
+                StatusCode::UNPROCESSABLE_ENTITY,
+            ),
+            (
+                subscription("76262", "ftp://203.0.113.7/hook", "s3cret"),
+                StatusCode::UNPROCESSABLE_ENTITY,
+            ),
+            (
+                subscription("76262", "http://203.0.113.7/hook", ""),
+                StatusCode::UNPROCESSABLE_ENTITY,
+            ),
+        ] {
+            assert_eq!(
+                expected,
+                post_subscription(router.clone(), request.clone()).await.0,
+                "{}",
+                request
+            );
+        }
+
+        let (status, body) = post_subscription(
+            router.clone(),
+            subscription("76262", "http://203.0.113.7/hook", "s3cret"),
+        )
+        .await;
+        assert_eq!(StatusCode::CREATED, status);
+        assert_eq!(None, body.get("secret"));
+        let id = body["id"].as_str().unwrap();
+
+        // Nobody can list the webhooks, nor remove one without its secret.
+        let (status, _) = call(router.clone(), "/alerts/subscriptions").await;
+        assert_eq!(StatusCode::METHOD_NOT_ALLOWED, status);
+        let guessed = alerts::signature("guess", id.as_bytes());
+        for signature in [None, Some("sha256=00"), Some(guessed.as_str())] {
+            assert_eq!(
+                StatusCode::UNAUTHORIZED,
+                delete_subscription(router.clone(), id, signature).await,
+                "{:?}",
+                signature
+            );
+        }
+
+        let signature = alerts::signature("s3cret", id.as_bytes());
+        for expected in [StatusCode::NO_CONTENT, StatusCode::NOT_FOUND] {
+            assert_eq!(
+                expected,
+                delete_subscription(router.clone(), id, Some(&signature)).await
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn rejects_what_is_not_a_location() {
+        let router = build_router(&config(String::from("http://127.0.0.1:9")));
+        for uri in [
+            "/weather",
+            "/weather?zip=Unknown",
+            "/weather?zip=76262%26key%3Dstolen",
+            "/forecast?zip=91,10",
+        ] {
+            let (status, _) = call(router.clone(), uri).await;
+            assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, status, "{}", uri);
+        }
+    }
+
+    #[tokio::test]
+    async fn resolves_places_before_asking_the_provider() {
+        let weather_api_url =
+            weather_provider(Some(String::from("weather for 76262 is available"))).await;
+        let router = build_router(&config(weather_api_url));
+        for uri in [
+            "/weather?zip=76262-1234",
+            "/weather?zip=Roanoke,%20TX",
+            "/weather?zip=33.0043,-97.2253",
+        ] {
+            let (status, body) = call(router.clone(), uri).await;
+            assert_eq!(StatusCode::OK, status, "{}", uri);
+            assert_eq!("Roanoke", body["city"]);
+        }
+    }
+
+    #[tokio::test]
+    async fn maps_the_provider_forecast() {
+        let weather_api_url =
+            weather_provider(Some(String::from("a forecast for 76262 is available"))).await;
+        let router = build_router(&config(weather_api_url));
+        let (status, body) = call(router.clone(), "/forecast?zip=76262&days=1").await;
+        assert_eq!(StatusCode::OK, status);
+        assert_eq!(
+            json!({
+                "city": "Roanoke",
+                "state": "Texas",
+                "days": [{
+                    "date": "2024-06-01",
+                    "high_celsius": 31.0,
+                    "low_celsius": 21.0,
+                    "high_fahrenheit": 87.8,
+                    "low_fahrenheit": 69.8,
+                    "precipitation_chance": 20,
+                    "condition": "Partly cloudy"
+                }]
+            }),
+            body
+        );
+
+        let (status, body) = call(router.clone(), "/forecast?zip=76262&days=1&units=metric").await;
+        assert_eq!(StatusCode::OK, status);
+        assert_eq!(None, body["days"][0].get("high_fahrenheit"));
+        assert_eq!(31.0, body["days"][0]["high_celsius"]);
+
+        let (status, _) = call(router.clone(), "/forecast?zip=76262&days=0").await;
+        assert_eq!(StatusCode::BAD_REQUEST, status);
+        let (status, _) = call(router, "/forecast?zip=76262&days=15").await;
+        assert_eq!(StatusCode::BAD_REQUEST, status);
+    }
+
+    /// Answers like the weather provider, counting calls, until `failing` is set.
+    async fn flaky_provider(calls: Arc<AtomicUsize>, failing: Arc<AtomicBool>) -> String {
+        let stub = Router::new().route(
+            "/current.json",
+            get(move || async move {
+                calls.fetch_add(1, Ordering::SeqCst);
+                if failing.load(Ordering::SeqCst) {
+                    return StatusCode::SERVICE_UNAVAILABLE.into_response();
+                }
+                Json(json!({
+                    "location": {"name": "Roanoke", "region": "Texas"},
+                    "current": {"temp_c": 20.0, "temp_f": 68.0}
+                }))
+                .into_response()
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, stub).await.unwrap() });
+        url
+    }
+
+    fn cached_app(weather_api_url: String, cache: CacheConfig) -> Router {
+        app(AppState {
+            has_apm: false,
+            http_client: Client::new(),
+            weather_api_url,
+            weather_api_key: String::from("test"),
+            cache: Some(WeatherCache::open(cache).unwrap()),
+            batch: BatchConfig::default(),
+            alerts: Alerts::new(AlertsConfig::default()),
+            history: None,
+        })
+    }
+
+    async fn stale(router: Router) -> (StatusCode, Option<String>) {
+        let request = Request::builder()
+            .uri("/weather?zip=76262")
+            .body(Body::empty())
+            .unwrap();
+        let response = router.oneshot(request).await.unwrap();
+        let stale = response
+            .headers()
+            .get(STALE_HEADER)
+            .map(|v| v.to_str().unwrap().to_string());
+        (response.status(), stale)
+    }
+
+    #[tokio::test]
+    async fn serves_stale_weather_when_the_provider_fails() {
+        let failing = Arc::new(AtomicBool::new(false));
+        let url = flaky_provider(Arc::new(AtomicUsize::new(0)), failing.clone()).await;
+        let router = cached_app(
+            url.clone(),
+            CacheConfig {
+                backend: CacheBackend::Memory,
+                ttl: Duration::ZERO,
+                stale_while_revalidate: Duration::ZERO,
+                stale_if_error: Duration::from_secs(60),
+            },
+        );
+        assert_eq!(
+            (StatusCode::OK, Some(String::from("false"))),
+            stale(router.clone()).await
+        );
+
+        failing.store(true, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        assert_eq!(
+            (StatusCode::OK, Some(String::from("true"))),
+            stale(router).await
+        );
+
+        // Without a stale-if-error window the failure is passed on.
+        let router = cached_app(
+            url,
+            CacheConfig {
+                backend: CacheBackend::Memory,
+                ttl: Duration::ZERO,
+                stale_while_revalidate: Duration::ZERO,
+                stale_if_error: Duration::ZERO,
+            },
+        );
+        assert_eq!(StatusCode::BAD_REQUEST, stale(router).await.0);
+    }
+
+    #[tokio::test]
+    async fn revalidates_stale_weather_in_the_background() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let url = flaky_provider(calls.clone(), Arc::new(AtomicBool::new(false))).await;
+        let router = cached_app(
+            url,
+            CacheConfig {
+                backend: CacheBackend::Memory,
+                ttl: Duration::ZERO,
+                stale_while_revalidate: Duration::from_secs(60),
+                stale_if_error: Duration::ZERO,
+            },
+        );
+        stale(router.clone()).await;
+        assert_eq!(1, calls.load(Ordering::SeqCst));
+
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        assert_eq!(
+            (StatusCode::OK, Some(String::from("true"))),
+            stale(router).await
+        );
+        tokio::time::timeout(Duration::from_secs(1), async {
+            while calls.load(Ordering::SeqCst) < 2 {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .expect("the stale entry was never refreshed");
+    }
+
+    async fn post_batch(router: Router, locations: &[&str]) -> (StatusCode, serde_json::Value) {
+        let request = Request::builder()
+            .method("POST")
+            .uri("/weather/batch")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(json!({ "locations": locations }).to_string()))
+            .unwrap();
+        let response = router.oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or_default())
+    }
+
+    #[tokio::test]
+    async fn batch_looks_up_each_place_once() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let url = flaky_provider(calls.clone(), Arc::new(AtomicBool::new(false))).await;
+        let (status, body) = post_batch(
+            build_router(&config(url)),
+            &["76262", "Roanoke, TX", "76262-1234", "nowhere!", "10001"],
+        )
+        .await;
+        assert_eq!(StatusCode::OK, status);
+        assert_eq!(2, calls.load(Ordering::SeqCst));
+
+        let results = body["results"].as_array().unwrap();
+        let statuses: Vec<_> = results
+            .iter()
+            .map(|r| r["status"].as_u64().unwrap())
+            .collect();
+        assert_eq!(vec![200, 200, 200, 422, 200], statuses);
+        assert_eq!("Roanoke, TX", results[1]["location"]);
+        assert_eq!(
+            json!({"city": "Roanoke", "state": "Texas", "celcius": 20.0, "farenheight": 68.0}),
+            results[1]["weather"]
+        );
+        assert_eq!("\"nowhere!\" is not a location", results[3]["error"]);
+    }
+
+    #[tokio::test]
+    async fn batch_bounds_its_provider_lookups() {
+        let (in_flight, most) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+        let (counting, peak) = (in_flight.clone(), most.clone());
+        let stub = Router::new().route(
+            "/current.json",
+            get(move || async move {
+                let now = counting.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(now, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                counting.fetch_sub(1, Ordering::SeqCst);
+                StatusCode::SERVICE_UNAVAILABLE
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, stub).await.unwrap() });
+
+        let router = app(AppState {
+            has_apm: false,
+            http_client: Client::new(),
+            weather_api_url: url,
+            weather_api_key: String::from("test"),
+            cache: None,
+            batch: BatchConfig {
+                concurrency: 2,
+                max_locations: 6,
+            },
+            alerts: Alerts::new(AlertsConfig::default()),
+            history: None,
+        });
+        let zips = ["10001", "10002", "10003", "10004", "10005", "10006"];
+        let (status, body) = post_batch(router.clone(), &zips).await;
+        assert_eq!(StatusCode::OK, status);
+        assert_eq!(2, most.load(Ordering::SeqCst));
+        for result in body["results"].as_array().unwrap() {
+            assert_eq!(400, result["status"]);
+            assert_eq!(
+                "the weather provider rejected the location",
+                result["error"]
+            );
+        }
+
+        let (status, _) = post_batch(router.clone(), &[]).await;
+        assert_eq!(StatusCode::BAD_REQUEST, status);
+        let (status, _) = post_batch(router, &[&zips[..], &["10007"]].concat()).await;
+        assert_eq!(StatusCode::BAD_REQUEST, status);
+    }
+
+    #[tokio::test]
+    async fn can_be_nested_under_a_prefix() {
+        let router = Router::new().nest(
+            "/service-d",
+            build_router(&config(String::from("http://127.0.0.1:9"))),
+        );
+        let (status, body) = call(router, "/service-d/health").await;
+        assert_eq!(StatusCode::OK, status);
+        assert_eq!("Healthy", body["status"]);
+    }
+}
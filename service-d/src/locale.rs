@@ -0,0 +1,204 @@
+//! Picks the response language from `Accept-Language` and translates
+//! condition text and unit labels with the bundled `translations.json`.
+//! Cached entries stay in the provider's English and are translated per
+//! request, so every language shares one cache entry.
+use std::{collections::HashMap, sync::OnceLock};
+
+use axum::http::{header, HeaderMap};
+use serde::Deserialize;
+use serde_json::Value;
+use service_models::v1::service_d::Units;
+
+const BUNDLED_TRANSLATIONS: &str = include_str!("../translations.json");
+const DEFAULT_LANGUAGE: &str = "en";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Locale {
+    language: &'static str,
+}
+
+impl Default for Locale {
+    fn default() -> Self {
+        Locale {
+            language: DEFAULT_LANGUAGE,
+        }
+    }
+}
+
+impl Locale {
+    /// The supported language the caller ranks highest, English when none
+    /// of theirs is supported.
+    pub fn negotiate(headers: &HeaderMap) -> Locale {
+        let accept = headers
+            .get(header::ACCEPT_LANGUAGE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default();
+        let mut best: Option<(&'static str, f32)> = None;
+        for range in accept.split(',') {
+            let mut parts = range.split(';');
+            let tag = parts.next().unwrap_or_default().trim();
+            let quality = parts
+                .find_map(|p| p.trim().strip_prefix("q="))
+                .map(|q| q.trim().parse().unwrap_or(0.0))
+                .unwrap_or(1.0);
+            let primary = tag.split('-').next().unwrap_or_default();
+            let language = match primary {
+                "*" => Some(DEFAULT_LANGUAGE),
+                _ => translations().language(primary),
+            };
+            let preferred = best.is_none_or(|(_, q)| quality > q);
+            if let (Some(language), true) = (language, quality > 0.0 && preferred) {
+                best = Some((language, quality));
+            }
+        }
+        best.map(|(language, _)| Locale { language })
+            .unwrap_or_default()
+    }
+
+    pub fn language(&self) -> &'static str {
+        self.language
+    }
+
+    /// The provider's English `text` in this locale's language, found by
+    /// `code` when there is one. Conditions the table does not have are left
+    /// in English.
+    pub fn condition(&self, code: Option<u32>, text: &str) -> String {
+        translations()
+            .conditions
+            .iter()
+            .find(|c| match code {
+                Some(code) => c.code == code,
+                None => c.text.get(DEFAULT_LANGUAGE).map(String::as_str) == Some(text),
+            })
+            .and_then(|c| c.text.get(self.language))
+            .cloned()
+            .unwrap_or_else(|| text.to_string())
+    }
+
+    /// The unit label of `field`, if it is measured in one.
+    pub fn label(&self, field: &str) -> Option<&'static str> {
+        let labels = translations().labels.get(self.language)?;
+        labels.get(unit(field)?).map(String::as_str)
+    }
+}
+
+/// Whether a response in `units` carries `field`. Fields without a unit are
+/// always carried.
+pub fn keeps(units: Units, field: &str) -> bool {
+    !matches!(
+        (units, unit(field)),
+        (Units::Metric, Some("fahrenheit" | "mph")) | (Units::Imperial, Some("celsius" | "kph"))
+    )
+}
+
+/// Removes the fields `units` leaves out from every object in `value`.
+pub fn retain_units(value: &mut Value, units: Units) {
+    match value {
+        Value::Object(map) => {
+            map.retain(|k, _| keeps(units, k));
+            map.values_mut().for_each(|v| retain_units(v, units));
+        }
+        Value::Array(items) => items.iter_mut().for_each(|v| retain_units(v, units)),
+        _ => {}
+    }
+}
+
+/// The unit `field` is measured in, as named in the `labels` table.
+fn unit(field: &str) -> Option<&'static str> {
+    match field {
+        "celsius" | "celcius" | "feels_like_celsius" | "high_celsius" | "low_celsius" => {
+            Some("celsius")
+        }
+        "fahrenheit"
+        | "farenheight"
+        | "feels_like_fahrenheit"
+        | "high_fahrenheit"
+        | "low_fahrenheit" => Some("fahrenheit"),
+        "wind_kph" => Some("kph"),
+        "wind_mph" => Some("mph"),
+        "humidity" | "precipitation_chance" => Some("percent"),
+        "wind_degree" => Some("degrees"),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Condition {
+    code: u32,
+    /// Keyed by language.
+    #[serde(flatten)]
+    text: HashMap<String, String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Translations {
+    /// Unit labels keyed by language, then unit.
+    labels: HashMap<String, HashMap<String, String>>,
+    conditions: Vec<Condition>,
+}
+
+static TRANSLATIONS: OnceLock<Translations> = OnceLock::new();
+
+/// The translations compiled into the crate from `translations.json`.
+fn translations() -> &'static Translations {
+    TRANSLATIONS.get_or_init(|| {
+        serde_json::from_str(BUNDLED_TRANSLATIONS).expect("bundled translations are valid")
+    })
+}
+
+impl Translations {
+    fn language(&'static self, tag: &str) -> Option<&'static str> {
+        self.labels
+            .keys()
+            .find(|language| language.eq_ignore_ascii_case(tag))
+            .map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn negotiate(accept_language: &str) -> &'static str {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::ACCEPT_LANGUAGE,
+            HeaderValue::from_str(accept_language).unwrap(),
+        );
+        Locale::negotiate(&headers).language()
+    }
+
+    #[test]
+    fn negotiates_the_preferred_supported_language() {
+        assert_eq!("en", Locale::negotiate(&HeaderMap::new()).language());
+        assert_eq!("fr", negotiate("fr-CA"));
+        assert_eq!("de", negotiate("ja, de;q=0.5, es;q=0.4"));
+        assert_eq!("es", negotiate("de;q=0.2, ES;q=0.9"));
+        assert_eq!("en", negotiate("fr;q=0, *;q=0.1"));
+        assert_eq!("en", negotiate("ja, zh-Hant"));
+    }
+
+    #[test]
+    fn translates_conditions_and_labels() {
+        let de = Locale { language: "de" };
+        assert_eq!(
+            "Teilweise bewölkt",
+            de.condition(Some(1003), "Partly cloudy")
+        );
+        assert_eq!("Leichter Regen", de.condition(None, "Light rain"));
+        assert_eq!("Blizzard", de.condition(Some(1117), "Blizzard"));
+        assert_eq!(Some("km/h"), de.label("wind_kph"));
+        assert_eq!(None, de.label("city"));
+        assert_eq!(Some("mi/h"), Locale { language: "es" }.label("wind_mph"));
+    }
+
+    #[test]
+    fn keeps_the_fields_of_the_requested_units() {
+        assert!(keeps(Units::Metric, "celcius"));
+        assert!(!keeps(Units::Metric, "farenheight"));
+        assert!(!keeps(Units::Imperial, "wind_kph"));
+        assert!(keeps(Units::Imperial, "humidity"));
+        assert!(keeps(Units::Both, "wind_kph"));
+    }
+}
@@ -0,0 +1,286 @@
+//! Parses the `zip` query parameter, which accepts more than zips, into a
+//! location the weather provider can be asked about. Places found in the
+//! bundled gazetteer are resolved to their zip so that different spellings
+//! share a cache entry and a provider lookup.
+use std::{fmt, sync::OnceLock};
+
+use serde::Deserialize;
+
+const BUNDLED_GAZETTEER: &str = include_str!("../gazetteer.json");
+/// Coordinates this close to a gazetteer entry resolve to its zip.
+const NEAR_KM: f64 = 10.0;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Location {
+    /// A five digit US zip. ZIP+4 input is reduced to this.
+    Zip(String),
+    /// A non-US postal code, upper cased with single spaces.
+    Postal(String),
+    /// A place the gazetteer does not know, passed on for the provider to
+    /// resolve.
+    CityState {
+        city: String,
+        state: String,
+    },
+    Coordinates {
+        lat: f64,
+        lon: f64,
+    },
+}
+
+#[derive(Debug, PartialEq)]
+pub enum LocationError {
+    Missing,
+    Invalid(String),
+    OutOfRange(String),
+}
+
+impl fmt::Display for LocationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LocationError::Missing => write!(f, "no location given"),
+            LocationError::Invalid(input) => write!(f, "{:?} is not a location", input),
+            LocationError::OutOfRange(input) => {
+                write!(f, "{:?} is outside latitude/longitude range", input)
+            }
+        }
+    }
+}
+
+impl std::error::Error for LocationError {}
+
+impl Location {
+    pub fn parse(input: Option<&str>) -> Result<Location, LocationError> {
+        let input = input.map(str::trim).unwrap_or_default();
+        if input.is_empty() {
+            return Err(LocationError::Missing);
+        }
+        let invalid = || LocationError::Invalid(input.to_string());
+
+        match us_zip(input) {
+            Some(Ok(zip)) => return Ok(Location::Zip(zip.to_string())),
+            Some(Err(())) => return Err(invalid()),
+            None => {}
+        }
+        if let Some((first, second)) = input.split_once(',') {
+            let (first, second) = (first.trim(), second.trim());
+            if let (Ok(lat), Ok(lon)) = (first.parse::<f64>(), second.parse::<f64>()) {
+                return coordinates(input, lat, lon);
+            }
+            if !is_place_name(first) || !is_place_name(second) {
+                return Err(invalid());
+            }
+            return Ok(gazetteer()
+                .find_place(first, second)
+                .map(|place| Location::Zip(place.zip.clone()))
+                .unwrap_or_else(|| Location::CityState {
+                    city: title_case(first),
+                    state: match second.len() {
+                        2 => second.to_uppercase(),
+                        _ => title_case(second),
+                    },
+                }));
+        }
+        postal(input).ok_or_else(invalid)
+    }
+
+    /// The `q` parameter for the weather provider, also used as the cache key.
+    pub fn query(&self) -> String {
+        match self {
+            Location::Zip(zip) => zip.clone(),
+            Location::Postal(code) => code.clone(),
+            Location::CityState { city, state } => format!("{}, {}", city, state),
+            Location::Coordinates { lat, lon } => format!("{:.4},{:.4}", lat, lon),
+        }
+    }
+}
+
+/// `76262` or `76262-1234`, returning the five digit part. Five digits and a
+/// hyphen followed by anything else is a mistyped ZIP+4, not a postal code.
+fn us_zip(input: &str) -> Option<Result<&str, ()>> {
+    let digits = |s: &str, n: usize| s.len() == n && s.bytes().all(|b| b.is_ascii_digit());
+    match input.split_once('-') {
+        None if digits(input, 5) => Some(Ok(input)),
+        Some((zip, plus_four)) if digits(zip, 5) => {
+            Some(digits(plus_four, 4).then_some(zip).ok_or(()))
+        }
+        _ => None,
+    }
+}
+
+fn coordinates(input: &str, lat: f64, lon: f64) -> Result<Location, LocationError> {
+    if !(-90.0..=90.0).contains(&lat) || !(-180.0..=180.0).contains(&lon) {
+        return Err(LocationError::OutOfRange(input.to_string()));
+    }
+    Ok(gazetteer()
+        .nearest(lat, lon)
+        .map(|place| Location::Zip(place.zip.clone()))
+        .unwrap_or(Location::Coordinates { lat, lon }))
+}
+
+/// Codes like `SW1A 1AA`, `M5V 3L9` or `1010`: letters, digits and single
+/// spaces or hyphens, with at least one digit.
+fn postal(input: &str) -> Option<Location> {
+    let code = input
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_uppercase();
+    let valid = (3..=10).contains(&code.len())
+        && code.bytes().any(|b| b.is_ascii_digit())
+        && code
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b' ' || b == b'-')
+        && !code.contains("  ")
+        && !code.starts_with('-')
+        && !code.ends_with('-');
+    valid.then_some(Location::Postal(code))
+}
+
+fn is_place_name(s: &str) -> bool {
+    !s.is_empty()
+        && s.len() <= 64
+        && s.chars().any(char::is_alphabetic)
+        && s.chars()
+            .all(|c| c.is_alphabetic() || matches!(c, ' ' | '.' | '\'' | '-'))
+}
+
+fn title_case(s: &str) -> String {
+    s.split_whitespace()
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first
+                    .to_uppercase()
+                    .chain(chars.flat_map(char::to_lowercase))
+                    .collect(),
+                None => String::new(),
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+#[derive(Debug, Deserialize)]
+struct Place {
+    zip: String,
+    city: String,
+    state: String,
+    state_code: String,
+    lat: f64,
+    lon: f64,
+}
+
+#[derive(Debug)]
+struct Gazetteer {
+    places: Vec<Place>,
+}
+
+static GAZETTEER: OnceLock<Gazetteer> = OnceLock::new();
+
+/// The gazetteer compiled into the crate from `gazetteer.json`.
+fn gazetteer() -> &'static Gazetteer {
+    GAZETTEER.get_or_init(|| Gazetteer {
+        places: serde_json::from_str(BUNDLED_GAZETTEER).expect("bundled gazetteer is valid"),
+    })
+}
+
+impl Gazetteer {
+    /// `state` may be the full name or the two letter code, in any case.
+    fn find_place(&self, city: &str, state: &str) -> Option<&Place> {
+        self.places.iter().find(|p| {
+            p.city.eq_ignore_ascii_case(city)
+                && (p.state.eq_ignore_ascii_case(state) || p.state_code.eq_ignore_ascii_case(state))
+        })
+    }
+
+    fn nearest(&self, lat: f64, lon: f64) -> Option<&Place> {
+        self.places
+            .iter()
+            .map(|p| (p, distance_km(lat, lon, p.lat, p.lon)))
+            .filter(|(_, km)| *km <= NEAR_KM)
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(p, _)| p)
+    }
+}
+
+/// Equirectangular approximation, close enough at gazetteer distances.
+fn distance_km(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    const EARTH_RADIUS_KM: f64 = 6371.0;
+    let x = (lon2 - lon1).to_radians() * ((lat1 + lat2) / 2.0).to_radians().cos();
+    let y = (lat2 - lat1).to_radians();
+    (x * x + y * y).sqrt() * EARTH_RADIUS_KM
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str) -> Result<Location, LocationError> {
+        Location::parse(Some(input))
+    }
+
+    fn zip(zip: &str) -> Result<Location, LocationError> {
+        Ok(Location::Zip(zip.to_string()))
+    }
+
+    #[test]
+    fn accepts_us_zips() {
+        assert_eq!(zip("76262"), parse("76262"));
+        assert_eq!(zip("76262"), parse(" 76262-1234 "));
+        assert_eq!(zip("00000"), parse("00000"));
+    }
+
+    #[test]
+    fn normalizes_postal_codes() {
+        assert_eq!(
+            Ok(Location::Postal(String::from("SW1A 1AA"))),
+            parse("sw1a   1aa")
+        );
+        assert_eq!(Ok(Location::Postal(String::from("1010"))), parse("1010"));
+    }
+
+    #[test]
+    fn resolves_places_from_the_gazetteer() {
+        assert_eq!(zip("76262"), parse("roanoke, tx"));
+        assert_eq!(zip("98101"), parse("Seattle,Washington"));
+        assert_eq!(
+            Ok(Location::CityState {
+                city: String::from("St. Louis"),
+                state: String::from("MO")
+            }),
+            parse("st. louis, MO")
+        );
+    }
+
+    #[test]
+    fn resolves_nearby_coordinates() {
+        assert_eq!(zip("76262"), parse("33.01,-97.22"));
+        let far = parse("33.0, -100.0").unwrap();
+        assert_eq!("33.0000,-100.0000", far.query());
+    }
+
+    #[test]
+    fn rejects_what_is_not_a_location() {
+        assert_eq!(Err(LocationError::Missing), Location::parse(None));
+        assert_eq!(Err(LocationError::Missing), parse("  "));
+        for input in [
+            "Unknown",
+            "76262-12",
+            "76262-abcd",
+            "a&key=stolen",
+            "roanoke, 12",
+            "<script>",
+        ] {
+            assert!(
+                matches!(parse(input), Err(LocationError::Invalid(_))),
+                "{}",
+                input
+            );
+        }
+        assert!(matches!(
+            parse("91.0,10.0"),
+            Err(LocationError::OutOfRange(_))
+        ));
+    }
+}
@@ -1,15 +1,23 @@
 use core::f64;
 
+use chrono::{DateTime, NaiveDate, Utc};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use service_models::{
+    v1::service_d::{
+        Alert, AlertSeverity, AlertsResponse, ForecastDay, ForecastResponse, Units, WeatherResponse,
+    },
+    v2,
+};
+use std::collections::BTreeMap;
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct WeatherResponse {
-    city: String,
-    state: String,
-    celcius: f64,
-    farenheight: f64,
-}
+use crate::{
+    alerts::{self, Alerts},
+    cache::WeatherCache,
+    config::{BatchConfig, Config},
+    history::WeatherHistory,
+    locale::{self, Locale},
+};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct WeatherApiResponse {
@@ -17,41 +25,302 @@ pub struct WeatherApiResponse {
     current: WeatherApiCurrentResponse,
 }
 
+/// Only the name and region are relied on; the rest of the provider's
+/// fields are passed on when present.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct WeatherApiLocationResponse {
     name: String,
     region: String,
+    #[serde(default)]
+    lat: Option<f64>,
+    #[serde(default)]
+    lon: Option<f64>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct WeatherApiCurrentResponse {
     temp_c: f64,
     temp_f: f64,
+    #[serde(default)]
+    last_updated_epoch: Option<i64>,
+    #[serde(default)]
+    humidity: Option<u8>,
+    #[serde(default)]
+    wind_kph: Option<f64>,
+    #[serde(default)]
+    wind_mph: Option<f64>,
+    #[serde(default)]
+    wind_degree: Option<u16>,
+    #[serde(default)]
+    wind_dir: Option<String>,
+    #[serde(default)]
+    feelslike_c: Option<f64>,
+    #[serde(default)]
+    feelslike_f: Option<f64>,
+    #[serde(default)]
+    condition: Option<WeatherApiCurrentCondition>,
+    #[serde(default)]
+    uv: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WeatherApiCurrentCondition {
+    text: String,
+    code: u32,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct Prefix {
-    pub zip: Option<String>,
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WeatherApiForecastResponse {
+    location: WeatherApiLocationResponse,
+    forecast: WeatherApiForecast,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct HealthCheck {
-    pub status: String,
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WeatherApiForecast {
+    forecastday: Vec<WeatherApiForecastDay>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WeatherApiForecastDay {
+    date: NaiveDate,
+    day: WeatherApiDay,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WeatherApiDay {
+    maxtemp_c: f64,
+    maxtemp_f: f64,
+    mintemp_c: f64,
+    mintemp_f: f64,
+    daily_chance_of_rain: u8,
+    condition: WeatherApiCondition,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WeatherApiCondition {
+    text: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WeatherApiAlertsResponse {
+    location: WeatherApiLocationResponse,
+    alerts: WeatherApiAlerts,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WeatherApiAlerts {
+    #[serde(default)]
+    alert: Vec<WeatherApiAlert>,
+}
+
+/// The provider sends empty strings rather than leaving fields out.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WeatherApiAlert {
+    headline: String,
+    event: String,
+    #[serde(default)]
+    severity: String,
+    /// Semicolon separated.
+    #[serde(default)]
+    areas: String,
+    #[serde(default)]
+    desc: String,
+    #[serde(default)]
+    instruction: String,
+    #[serde(default)]
+    effective: String,
+    #[serde(default)]
+    expires: String,
 }
 
 #[derive(Clone, Debug)]
 pub struct AppState {
     pub has_apm: bool,
     pub http_client: Client,
+    pub weather_api_url: String,
+    pub weather_api_key: String,
+    pub cache: Option<WeatherCache>,
+    pub batch: BatchConfig,
+    pub alerts: Alerts,
+    pub history: Option<WeatherHistory>,
+}
+
+impl AppState {
+    pub fn from_config(config: &Config) -> AppState {
+        AppState {
+            has_apm: config.tracing_enabled,
+            http_client: config
+                .upstream_tls
+                .http_client()
+                .expect("invalid upstream TLS configuration"),
+            weather_api_url: config.weather_api_url.clone(),
+            weather_api_key: config.weather_api_key.clone(),
+            cache: config
+                .weather_cache
+                .clone()
+                .map(|cache| WeatherCache::open(cache).expect("unable to open the weather cache")),
+            batch: config.weather_batch,
+            alerts: Alerts::new(config.weather_alerts),
+            history: config.weather_history.clone().map(|history| {
+                WeatherHistory::open(history).expect("unable to open the weather history")
+            }),
+        }
+    }
 }
 
 impl From<WeatherApiResponse> for WeatherResponse {
     fn from(r: WeatherApiResponse) -> Self {
+        let (condition_code, condition) = match r.current.condition {
+            Some(c) => (Some(c.code), Some(c.text)),
+            None => (None, None),
+        };
         WeatherResponse {
             celcius: r.current.temp_c,
             farenheight: r.current.temp_f,
             city: r.location.name,
             state: r.location.region,
+            humidity: r.current.humidity,
+            wind_kph: r.current.wind_kph,
+            wind_mph: r.current.wind_mph,
+            wind_degree: r.current.wind_degree,
+            wind_direction: r.current.wind_dir,
+            feels_like_celsius: r.current.feelslike_c,
+            feels_like_fahrenheit: r.current.feelslike_f,
+            condition_code,
+            condition,
+            uv_index: r.current.uv,
+            observed_at: r
+                .current
+                .last_updated_epoch
+                .and_then(|epoch| DateTime::from_timestamp(epoch, 0)),
+            latitude: r.location.lat,
+            longitude: r.location.lon,
+        }
+    }
+}
+
+impl From<WeatherApiForecastResponse> for ForecastResponse {
+    fn from(r: WeatherApiForecastResponse) -> Self {
+        ForecastResponse {
+            city: r.location.name,
+            state: r.location.region,
+            days: r
+                .forecast
+                .forecastday
+                .into_iter()
+                .map(|d| ForecastDay {
+                    date: d.date,
+                    high_celsius: d.day.maxtemp_c,
+                    low_celsius: d.day.mintemp_c,
+                    high_fahrenheit: d.day.maxtemp_f,
+                    low_fahrenheit: d.day.mintemp_f,
+                    precipitation_chance: d.day.daily_chance_of_rain,
+                    condition: d.day.condition.text,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<WeatherApiAlertsResponse> for AlertsResponse {
+    fn from(r: WeatherApiAlertsResponse) -> Self {
+        AlertsResponse {
+            city: r.location.name,
+            state: r.location.region,
+            alerts: r.alerts.alert.into_iter().map(Alert::from).collect(),
+        }
+    }
+}
+
+impl From<WeatherApiAlert> for Alert {
+    fn from(a: WeatherApiAlert) -> Self {
+        let text = |s: String| Some(s.trim().to_string()).filter(|s| !s.is_empty());
+        let time = |s: &str| {
+            DateTime::parse_from_rfc3339(s)
+                .ok()
+                .map(|t| t.with_timezone(&Utc))
+        };
+        Alert {
+            id: alerts::digest(&[&a.event, &a.headline, &a.effective, &a.areas]),
+            severity: match a.severity.to_ascii_lowercase().as_str() {
+                "extreme" => AlertSeverity::Extreme,
+                "severe" => AlertSeverity::Severe,
+                "moderate" => AlertSeverity::Moderate,
+                "minor" => AlertSeverity::Minor,
+                _ => AlertSeverity::Unknown,
+            },
+            areas: a
+                .areas
+                .split(';')
+                .map(str::trim)
+                .filter(|area| !area.is_empty())
+                .map(String::from)
+                .collect(),
+            effective: time(&a.effective),
+            expires: time(&a.expires),
+            description: text(a.desc),
+            instruction: text(a.instruction),
+            event: a.event,
+            headline: a.headline,
+        }
+    }
+}
+
+/// The v2 shape of `weather` with only the fields of `units`, translated for
+/// `locale`.
+pub fn weather_v2(
+    weather: WeatherResponse,
+    units: Units,
+    locale: Locale,
+) -> v2::service_d::WeatherResponse {
+    let mut fields = Labelled {
+        units,
+        locale,
+        labels: BTreeMap::new(),
+    };
+    let condition = weather
+        .condition
+        .map(|text| locale.condition(weather.condition_code, &text));
+    v2::service_d::WeatherResponse {
+        city: weather.city,
+        state: weather.state,
+        language: locale.language().to_string(),
+        celsius: fields.keep("celsius", Some(weather.celcius)),
+        fahrenheit: fields.keep("fahrenheit", Some(weather.farenheight)),
+        celcius: fields.keep("celcius", Some(weather.celcius)),
+        farenheight: fields.keep("farenheight", Some(weather.farenheight)),
+        humidity: fields.keep("humidity", weather.humidity),
+        wind_kph: fields.keep("wind_kph", weather.wind_kph),
+        wind_mph: fields.keep("wind_mph", weather.wind_mph),
+        wind_degree: fields.keep("wind_degree", weather.wind_degree),
+        wind_direction: weather.wind_direction,
+        feels_like_celsius: fields.keep("feels_like_celsius", weather.feels_like_celsius),
+        feels_like_fahrenheit: fields.keep("feels_like_fahrenheit", weather.feels_like_fahrenheit),
+        condition_code: weather.condition_code,
+        condition,
+        uv_index: weather.uv_index,
+        observed_at: weather.observed_at,
+        latitude: weather.latitude,
+        longitude: weather.longitude,
+        labels: fields.labels,
+    }
+}
+
+/// Drops the fields `units` leaves out and collects the labels of the rest.
+struct Labelled {
+    units: Units,
+    locale: Locale,
+    labels: BTreeMap<String, String>,
+}
+
+impl Labelled {
+    fn keep<T>(&mut self, field: &str, value: Option<T>) -> Option<T> {
+        if value.is_none() || !locale::keeps(self.units, field) {
+            return None;
+        }
+        if let Some(label) = self.locale.label(field) {
+            self.labels.insert(field.to_string(), label.to_string());
         }
+        value
     }
 }
@@ -0,0 +1,11 @@
+//! Wire types shared between the services. Each module under a version is
+//! owned by the service that produces the JSON; consumers deserialize into the
+//! same types rather than keeping their own copies.
+//!
+//! Breaking changes to a shape go into a new version module so existing
+//! consumers keep compiling against the old one.
+pub mod v1;
+pub mod v2;
+
+#[cfg(test)]
+mod testing;
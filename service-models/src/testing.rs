@@ -0,0 +1,25 @@
+//! Helpers shared by each version's wire format tests.
+use serde::{de::DeserializeOwned, Serialize};
+use utoipa::PartialSchema;
+
+/// Asserts `value` serializes to `json` and back.
+pub fn round_trip<T>(value: T, json: &str)
+where
+    T: Serialize + DeserializeOwned + PartialEq + std::fmt::Debug,
+{
+    let expected: serde_json::Value = serde_json::from_str(json).unwrap();
+    assert_eq!(expected, serde_json::to_value(&value).unwrap());
+    assert_eq!(value, serde_json::from_str::<T>(json).unwrap());
+}
+
+/// Compares `T`'s schema with `schemas/{version}/{name}.json`.
+pub fn snapshot<T: PartialSchema>(version: &str, name: &str) {
+    let schema = serde_json::to_string_pretty(&T::schema()).unwrap();
+    let path = format!(
+        "{}/schemas/{}/{}.json",
+        env!("CARGO_MANIFEST_DIR"),
+        version,
+        name
+    );
+    service_common::openapi::assert_matches_committed(&schema, &path);
+}
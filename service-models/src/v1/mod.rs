@@ -0,0 +1,373 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+pub mod service_a;
+pub mod service_b;
+pub mod service_c;
+pub mod service_d;
+
+/// Body of every service's health route.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct HealthCheck {
+    pub status: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::round_trip;
+    use chrono::{FixedOffset, NaiveDate, TimeZone, Utc};
+    use utoipa::PartialSchema;
+
+    fn weather() -> service_d::WeatherResponse {
+        service_d::WeatherResponse {
+            city: String::from("Roanoke"),
+            state: String::from("Texas"),
+            celcius: 20.0,
+            farenheight: 68.0,
+            ..Default::default()
+        }
+    }
+
+    fn forecast() -> service_d::ForecastResponse {
+        service_d::ForecastResponse {
+            city: String::from("Roanoke"),
+            state: String::from("Texas"),
+            days: vec![service_d::ForecastDay {
+                date: NaiveDate::from_ymd_opt(2024, 6, 1).unwrap(),
+                high_celsius: 31.0,
+                low_celsius: 21.0,
+                high_fahrenheit: 87.8,
+                low_fahrenheit: 69.8,
+                precipitation_chance: 20,
+                condition: String::from("Partly cloudy"),
+            }],
+        }
+    }
+
+    fn snapshot<T: PartialSchema>(name: &str) {
+        crate::testing::snapshot::<T>("v1", name);
+    }
+
+    #[test]
+    fn round_trips_wire_format() {
+        round_trip(
+            HealthCheck {
+                status: String::from("Healthy"),
+            },
+            r#"{"status": "Healthy"}"#,
+        );
+        round_trip(
+            service_a::Model {
+                key_one: String::from("(Ben)Field 1"),
+                key_two: String::from("(Ben)Field 2"),
+            },
+            r#"{"key_one": "(Ben)Field 1", "key_two": "(Ben)Field 2"}"#,
+        );
+        round_trip(
+            service_c::ExternalModel {
+                key_time: Utc.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap(),
+                local: None,
+                formatted: None,
+            },
+            r#"{"key_time": "2024-06-01T12:00:00Z"}"#,
+        );
+        round_trip(
+            weather(),
+            r#"{"city": "Roanoke", "state": "Texas", "celcius": 20.0, "farenheight": 68.0}"#,
+        );
+        round_trip(
+            service_d::WeatherBatchResponse {
+                results: vec![
+                    service_d::WeatherBatchResult {
+                        location: String::from("76262"),
+                        status: 200,
+                        weather: Some(weather()),
+                        error: None,
+                    },
+                    service_d::WeatherBatchResult {
+                        location: String::from("nowhere"),
+                        status: 422,
+                        weather: None,
+                        error: Some(String::from("\"nowhere\" is not a location")),
+                    },
+                ],
+            },
+            r#"{"results": [
+                {
+                    "location": "76262",
+                    "status": 200,
+                    "weather": {"city": "Roanoke", "state": "Texas", "celcius": 20.0, "farenheight": 68.0}
+                },
+                {"location": "nowhere", "status": 422, "error": "\"nowhere\" is not a location"}
+            ]}"#,
+        );
+        round_trip(
+            service_b::ExternalModel {
+                key_one: String::from("(Ben)Field 1"),
+                key_two: String::from("(Ben)Field 2"),
+                key_time: Utc.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap(),
+                weather: weather(),
+                forecast: None,
+                local_time: None,
+            },
+            r#"{
+                "key_one": "(Ben)Field 1",
+                "key_two": "(Ben)Field 2",
+                "key_time": "2024-06-01T12:00:00Z",
+                "weather": {"city": "Roanoke", "state": "Texas", "celcius": 20.0, "farenheight": 68.0}
+            }"#,
+        );
+        round_trip(
+            service_d::WeatherResponse {
+                humidity: Some(61),
+                wind_kph: Some(14.4),
+                wind_mph: Some(8.9),
+                wind_degree: Some(190),
+                wind_direction: Some(String::from("S")),
+                feels_like_celsius: Some(20.5),
+                feels_like_fahrenheit: Some(68.9),
+                condition_code: Some(1003),
+                condition: Some(String::from("Partly cloudy")),
+                uv_index: Some(6.0),
+                observed_at: Some(Utc.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap()),
+                latitude: Some(33.0),
+                longitude: Some(-97.23),
+                ..weather()
+            },
+            r#"{
+                "city": "Roanoke",
+                "state": "Texas",
+                "celcius": 20.0,
+                "farenheight": 68.0,
+                "humidity": 61,
+                "wind_kph": 14.4,
+                "wind_mph": 8.9,
+                "wind_degree": 190,
+                "wind_direction": "S",
+                "feels_like_celsius": 20.5,
+                "feels_like_fahrenheit": 68.9,
+                "condition_code": 1003,
+                "condition": "Partly cloudy",
+                "uv_index": 6.0,
+                "observed_at": "2024-06-01T12:00:00Z",
+                "latitude": 33.0,
+                "longitude": -97.23
+            }"#,
+        );
+        round_trip(
+            forecast(),
+            r#"{
+                "city": "Roanoke",
+                "state": "Texas",
+                "days": [{
+                    "date": "2024-06-01",
+                    "high_celsius": 31.0,
+                    "low_celsius": 21.0,
+                    "high_fahrenheit": 87.8,
+                    "low_fahrenheit": 69.8,
+                    "precipitation_chance": 20,
+                    "condition": "Partly cloudy"
+                }]
+            }"#,
+        );
+        round_trip(
+            service_b::ExternalModel {
+                key_one: String::from("(Ben)Field 1"),
+                key_two: String::from("(Ben)Field 2"),
+                key_time: Utc.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap(),
+                weather: weather(),
+                forecast: Some(forecast()),
+                local_time: None,
+            },
+            r#"{
+                "key_one": "(Ben)Field 1",
+                "key_two": "(Ben)Field 2",
+                "key_time": "2024-06-01T12:00:00Z",
+                "weather": {"city": "Roanoke", "state": "Texas", "celcius": 20.0, "farenheight": 68.0},
+                "forecast": {
+                    "city": "Roanoke",
+                    "state": "Texas",
+                    "days": [{
+                        "date": "2024-06-01",
+                        "high_celsius": 31.0,
+                        "low_celsius": 21.0,
+                        "high_fahrenheit": 87.8,
+                        "low_fahrenheit": 69.8,
+                        "precipitation_chance": 20,
+                        "condition": "Partly cloudy"
+                    }]
+                }
+            }"#,
+        );
+    }
+
+    #[test]
+    fn round_trips_local_and_formatted_time() {
+        let key_time = Utc.with_ymd_and_hms(2024, 6, 1, 17, 0, 0).unwrap();
+        round_trip(
+            service_c::ExternalModel {
+                key_time,
+                local: Some(service_c::LocalTime {
+                    time_zone: String::from("America/Chicago"),
+                    local_time: key_time.with_timezone(&FixedOffset::west_opt(5 * 3600).unwrap()),
+                    utc_offset: String::from("-05:00"),
+                    utc_offset_seconds: -5 * 3600,
+                    dst: true,
+                    abbreviation: String::from("CDT"),
+                }),
+                formatted: Some(service_c::FormattedTime {
+                    format: String::from("iso_week"),
+                    value: String::from("2024-W22-6"),
+                    components: service_c::TimeComponents {
+                        year: 2024,
+                        month: 6,
+                        day: 1,
+                        weekday: String::from("Saturday"),
+                        ordinal: 153,
+                    },
+                }),
+            },
+            r#"{
+                "key_time": "2024-06-01T17:00:00Z",
+                "local": {
+                    "time_zone": "America/Chicago",
+                    "local_time": "2024-06-01T12:00:00-05:00",
+                    "utc_offset": "-05:00",
+                    "utc_offset_seconds": -18000,
+                    "dst": true,
+                    "abbreviation": "CDT"
+                },
+                "formatted": {
+                    "format": "iso_week",
+                    "value": "2024-W22-6",
+                    "components": {
+                        "year": 2024,
+                        "month": 6,
+                        "day": 1,
+                        "weekday": "Saturday",
+                        "ordinal": 153
+                    }
+                }
+            }"#,
+        );
+    }
+
+    #[test]
+    fn round_trips_alerts() {
+        let alert = service_d::Alert {
+            id: String::from("5f0c7a1e2b9d4c38"),
+            event: String::from("Tropical Storm Warning"),
+            headline: String::from("Tropical Storm Warning issued for Miami-Dade"),
+            severity: service_d::AlertSeverity::Severe,
+            areas: vec![String::from("Miami-Dade")],
+            description: None,
+            instruction: Some(String::from("Secure outdoor objects.")),
+            effective: Some(Utc.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap()),
+            expires: None,
+        };
+        round_trip(
+            service_d::AlertNotification {
+                subscription_id: String::from("9b2e4f60d1a7c385"),
+                location: String::from("33101"),
+                city: String::from("Miami"),
+                state: String::from("Florida"),
+                alerts: vec![alert],
+            },
+            r#"{
+                "subscription_id": "9b2e4f60d1a7c385",
+                "location": "33101",
+                "city": "Miami",
+                "state": "Florida",
+                "alerts": [{
+                    "id": "5f0c7a1e2b9d4c38",
+                    "event": "Tropical Storm Warning",
+                    "headline": "Tropical Storm Warning issued for Miami-Dade",
+                    "severity": "severe",
+                    "areas": ["Miami-Dade"],
+                    "instruction": "Secure outdoor objects.",
+                    "effective": "2024-06-01T12:00:00Z"
+                }]
+            }"#,
+        );
+        round_trip(
+            service_d::AlertSubscriptionRequest {
+                location: String::from("Miami, FL"),
+                webhook_url: String::from("https://example.com/hooks/alerts"),
+                secret: String::from("s3cret"),
+            },
+            r#"{
+                "location": "Miami, FL",
+                "webhook_url": "https://example.com/hooks/alerts",
+                "secret": "s3cret"
+            }"#,
+        );
+    }
+
+    #[test]
+    fn round_trips_history() {
+        let temperature = |min: f64, max: f64| service_d::Aggregate {
+            min,
+            max,
+            avg: (min + max) / 2.0,
+        };
+        round_trip(
+            service_d::WeatherHistoryResponse {
+                location: String::from("76262"),
+                from: Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap(),
+                to: Utc.with_ymd_and_hms(2024, 6, 2, 0, 0, 0).unwrap(),
+                interval: service_d::HistoryInterval::Day,
+                buckets: vec![service_d::WeatherHistoryBucket {
+                    start: Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap(),
+                    samples: 2,
+                    celsius: temperature(20.0, 30.0),
+                    fahrenheit: temperature(68.0, 86.0),
+                    humidity: Some(temperature(40.0, 60.0)),
+                    wind_kph: None,
+                    wind_mph: None,
+                }],
+            },
+            r#"{
+                "location": "76262",
+                "from": "2024-06-01T00:00:00Z",
+                "to": "2024-06-02T00:00:00Z",
+                "interval": "day",
+                "buckets": [{
+                    "start": "2024-06-01T00:00:00Z",
+                    "samples": 2,
+                    "celsius": {"min": 20.0, "max": 30.0, "avg": 25.0},
+                    "fahrenheit": {"min": 68.0, "max": 86.0, "avg": 77.0},
+                    "humidity": {"min": 40.0, "max": 60.0, "avg": 50.0}
+                }]
+            }"#,
+        );
+    }
+
+    #[test]
+    fn schemas_match_snapshots() {
+        snapshot::<HealthCheck>("HealthCheck");
+        snapshot::<service_a::Model>("service_a.Model");
+        snapshot::<service_b::ExternalModel>("service_b.ExternalModel");
+        snapshot::<service_c::ExternalModel>("service_c.ExternalModel");
+        snapshot::<service_c::LocalTime>("service_c.LocalTime");
+        snapshot::<service_c::FormattedTime>("service_c.FormattedTime");
+        snapshot::<service_c::TimeComponents>("service_c.TimeComponents");
+        snapshot::<service_d::WeatherResponse>("service_d.WeatherResponse");
+        snapshot::<service_d::ForecastDay>("service_d.ForecastDay");
+        snapshot::<service_d::ForecastResponse>("service_d.ForecastResponse");
+        snapshot::<service_d::WeatherBatchRequest>("service_d.WeatherBatchRequest");
+        snapshot::<service_d::WeatherBatchResult>("service_d.WeatherBatchResult");
+        snapshot::<service_d::WeatherBatchResponse>("service_d.WeatherBatchResponse");
+        snapshot::<service_d::Units>("service_d.Units");
+        snapshot::<service_d::AlertSeverity>("service_d.AlertSeverity");
+        snapshot::<service_d::Alert>("service_d.Alert");
+        snapshot::<service_d::AlertsResponse>("service_d.AlertsResponse");
+        snapshot::<service_d::AlertSubscriptionRequest>("service_d.AlertSubscriptionRequest");
+        snapshot::<service_d::AlertSubscription>("service_d.AlertSubscription");
+        snapshot::<service_d::AlertNotification>("service_d.AlertNotification");
+        snapshot::<service_d::HistoryInterval>("service_d.HistoryInterval");
+        snapshot::<service_d::Aggregate>("service_d.Aggregate");
+        snapshot::<service_d::WeatherHistoryBucket>("service_d.WeatherHistoryBucket");
+        snapshot::<service_d::WeatherHistoryResponse>("service_d.WeatherHistoryResponse");
+    }
+}
@@ -0,0 +1,16 @@
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+/// Response of service-a's `GET /route`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct Model {
+    pub key_one: String,
+    pub key_two: String,
+}
+
+/// Query parameters of service-a's `GET /route`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct Prefix {
+    pub p: Option<String>,
+}
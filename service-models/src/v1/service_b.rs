@@ -0,0 +1,36 @@
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use chrono::{DateTime, Utc};
+
+use super::{
+    service_c::LocalTime,
+    service_d::{ForecastResponse, WeatherResponse},
+};
+
+/// Response of service-b's `GET /`, aggregated from the other services.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct ExternalModel {
+    pub key_one: String,
+    pub key_two: String,
+    pub key_time: DateTime<Utc>,
+    pub weather: WeatherResponse,
+    /// Only present when the request asked for `days`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub forecast: Option<ForecastResponse>,
+    /// The time where the weather is. Only present when `zip` is a US zip.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub local_time: Option<LocalTime>,
+}
+
+/// Query parameters of service-b's `GET /`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct Prefix {
+    pub name: Option<String>,
+    /// Required. A US ZIP or ZIP+4, a postal code, `city, state` or
+    /// `lat,lon`, as service-d's `GET /weather` takes.
+    pub zip: Option<String>,
+    /// Days of forecast to include, none when not given.
+    pub days: Option<u8>,
+}
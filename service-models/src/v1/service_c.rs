@@ -0,0 +1,81 @@
+use chrono::{DateTime, FixedOffset, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+/// Response of service-c's `GET /time`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct ExternalModel {
+    pub key_time: DateTime<Utc>,
+    /// Only present when the request named a zone, zip or coordinates.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub local: Option<LocalTime>,
+    /// Only present when the request asked for a `format`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub formatted: Option<FormattedTime>,
+}
+
+/// `key_time` as the clocks in one time zone show it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct LocalTime {
+    /// IANA name, e.g. `America/Chicago`.
+    pub time_zone: String,
+    pub local_time: DateTime<FixedOffset>,
+    /// e.g. `-05:00`.
+    pub utc_offset: String,
+    pub utc_offset_seconds: i32,
+    /// Whether daylight saving time is in effect.
+    pub dst: bool,
+    /// e.g. `CDT`, or the offset for zones without one.
+    pub abbreviation: String,
+}
+
+/// `key_time` written out in the requested format, in the requested zone
+/// or UTC.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct FormattedTime {
+    /// The `format` asked for.
+    pub format: String,
+    pub value: String,
+    pub components: TimeComponents,
+}
+
+/// The calendar date `FormattedTime` was written from.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct TimeComponents {
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+    /// English name, e.g. `Saturday`.
+    pub weekday: String,
+    /// Day of the year, 1 to 366.
+    pub ordinal: u32,
+}
+
+/// Query parameters of service-c's `GET /time`. At most one of `tz`, `zip`
+/// or `lat` and `lon` may be given.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TimeQuery {
+    /// IANA time zone, e.g. `America/Chicago`.
+    pub tz: Option<String>,
+    /// A US ZIP or ZIP+4.
+    pub zip: Option<String>,
+    pub lat: Option<f64>,
+    pub lon: Option<f64>,
+    /// `rfc3339`, `rfc2822`, `unix`, `unix_millis`, `unix_nanos`, `iso_week`
+    /// or a strftime pattern such as `%Y-%m-%d %H:%M`.
+    pub format: Option<String>,
+}
+
+/// Query parameters of service-c's `GET /time/stream` and `GET /time/ws`.
+/// Each tick is the `ExternalModel` `GET /time` would answer with.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TimeStreamQuery {
+    /// Milliseconds between ticks, from 100 to 3600000. 1000 when not given.
+    pub interval_ms: Option<u64>,
+    /// IANA time zone, e.g. `America/Chicago`. UTC only when not given.
+    pub tz: Option<String>,
+    /// As on `GET /time`.
+    pub format: Option<String>,
+}
@@ -0,0 +1,302 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+/// Response of service-d's `GET /weather`. The misspelled temperature names
+/// are part of the published shape. Everything after them is only sent when
+/// asked for with `fields`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, ToSchema)]
+pub struct WeatherResponse {
+    pub city: String,
+    pub state: String,
+    pub celcius: f64,
+    pub farenheight: f64,
+    /// Percent, 0 to 100.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub humidity: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wind_kph: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wind_mph: Option<f64>,
+    /// Compass degrees the wind blows from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wind_degree: Option<u16>,
+    /// 16 point compass direction, e.g. `SSW`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wind_direction: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub feels_like_celsius: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub feels_like_fahrenheit: Option<f64>,
+    /// The provider's condition code, stable across languages.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub condition_code: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub condition: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uv_index: Option<f64>,
+    /// When the provider last updated the conditions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub observed_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latitude: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub longitude: Option<f64>,
+}
+
+impl WeatherResponse {
+    /// Sent when the request does not choose its `fields`.
+    pub const DEFAULT_FIELDS: [&'static str; 4] = ["city", "state", "celcius", "farenheight"];
+    pub const FIELDS: [&'static str; 17] = [
+        "city",
+        "state",
+        "celcius",
+        "farenheight",
+        "humidity",
+        "wind_kph",
+        "wind_mph",
+        "wind_degree",
+        "wind_direction",
+        "feels_like_celsius",
+        "feels_like_fahrenheit",
+        "condition_code",
+        "condition",
+        "uv_index",
+        "observed_at",
+        "latitude",
+        "longitude",
+    ];
+}
+
+/// Query parameters of service-d's `GET /weather`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct Prefix {
+    /// A US ZIP or ZIP+4, a postal code, `city, state` or `lat,lon`.
+    pub zip: Option<String>,
+    /// Comma separated names of the fields to return, e.g.
+    /// `city,humidity,wind_kph`. Defaults to city, state and temperatures.
+    pub fields: Option<String>,
+    /// Leaves out the other system's fields. Both when not given.
+    pub units: Option<Units>,
+}
+
+/// Which measurement system's fields a weather response carries.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Units {
+    /// Celsius and km/h.
+    Metric,
+    /// Fahrenheit and mph.
+    Imperial,
+    #[default]
+    Both,
+}
+
+/// One day of service-d's `GET /forecast`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct ForecastDay {
+    pub date: NaiveDate,
+    pub high_celsius: f64,
+    pub low_celsius: f64,
+    pub high_fahrenheit: f64,
+    pub low_fahrenheit: f64,
+    /// Percent, 0 to 100.
+    pub precipitation_chance: u8,
+    pub condition: String,
+}
+
+/// Response of service-d's `GET /forecast`, one entry per day starting today.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct ForecastResponse {
+    pub city: String,
+    pub state: String,
+    pub days: Vec<ForecastDay>,
+}
+
+/// Query parameters of service-d's `GET /forecast`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ForecastQuery {
+    /// A US ZIP or ZIP+4, a postal code, `city, state` or `lat,lon`.
+    pub zip: Option<String>,
+    /// Between 1 and 14, 3 when not given.
+    pub days: Option<u8>,
+    /// Leaves out the other system's temperatures. Both when not given.
+    pub units: Option<Units>,
+}
+
+/// Body of service-d's `POST /weather/batch`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct WeatherBatchRequest {
+    /// Each accepts what `zip` does on `GET /weather`. Spellings of the same
+    /// place are looked up once.
+    pub locations: Vec<String>,
+}
+
+/// Response of service-d's `POST /weather/batch`, one result per requested
+/// location in the order they were asked for.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct WeatherBatchResponse {
+    pub results: Vec<WeatherBatchResult>,
+}
+
+/// The weather for one location of a batch, or why there is none.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct WeatherBatchResult {
+    /// The location as it was given.
+    pub location: String,
+    /// The status `GET /weather` would have answered this location with.
+    pub status: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weather: Option<WeatherResponse>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Query parameters of service-d's `GET /alerts`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AlertsQuery {
+    /// A US ZIP or ZIP+4, a postal code, `city, state` or `lat,lon`.
+    pub zip: Option<String>,
+}
+
+/// How much harm an alert warns of, from the provider's CAP severity.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertSeverity {
+    Extreme,
+    Severe,
+    Moderate,
+    Minor,
+    #[default]
+    Unknown,
+}
+
+/// One active weather alert, normalized from the provider's.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct Alert {
+    /// Derived from the alert's contents, so it is the same on every poll.
+    pub id: String,
+    /// e.g. `Tornado Warning`.
+    pub event: String,
+    pub headline: String,
+    pub severity: AlertSeverity,
+    /// Counties or zones the alert covers.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub areas: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// What people in the area should do.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instruction: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub effective: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires: Option<DateTime<Utc>>,
+}
+
+/// Response of service-d's `GET /alerts`. `alerts` is empty when there are
+/// none.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct AlertsResponse {
+    pub city: String,
+    pub state: String,
+    pub alerts: Vec<Alert>,
+}
+
+/// Body of service-d's `POST /alerts/subscriptions`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct AlertSubscriptionRequest {
+    /// Accepts what `zip` does on `GET /alerts`.
+    pub location: String,
+    /// An http or https url new alerts are POSTed to.
+    pub webhook_url: String,
+    /// Key of the `x-signature-256` HMAC-SHA256 sent with each delivery,
+    /// and asked for to unsubscribe. Never sent back.
+    pub secret: String,
+}
+
+/// A registered webhook, as returned by service-d's
+/// `POST /alerts/subscriptions`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct AlertSubscription {
+    pub id: String,
+    /// The location as it was given.
+    pub location: String,
+    pub webhook_url: String,
+}
+
+/// Body service-d POSTs to a subscription's webhook. Each alert is only
+/// delivered once per subscription.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct AlertNotification {
+    pub subscription_id: String,
+    pub location: String,
+    pub city: String,
+    pub state: String,
+    /// The alerts not delivered to this subscription before.
+    pub alerts: Vec<Alert>,
+}
+
+/// Query parameters of service-d's `GET /weather/history`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct HistoryQuery {
+    /// A US ZIP or ZIP+4, a postal code, `city, state` or `lat,lon`.
+    pub zip: Option<String>,
+    /// RFC 3339, inclusive. 7 days before `to` when not given.
+    pub from: Option<DateTime<Utc>>,
+    /// RFC 3339, exclusive. Now when not given.
+    pub to: Option<DateTime<Utc>>,
+    /// Day when not given.
+    pub interval: Option<HistoryInterval>,
+}
+
+/// The width of each bucket of `GET /weather/history`. Buckets start on the
+/// UTC hour or day.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum HistoryInterval {
+    Hour,
+    #[default]
+    Day,
+}
+
+/// The lowest, highest and mean of a measurement over a bucket.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, ToSchema)]
+pub struct Aggregate {
+    pub min: f64,
+    pub max: f64,
+    pub avg: f64,
+}
+
+/// The recorded conditions of one interval.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct WeatherHistoryBucket {
+    pub start: DateTime<Utc>,
+    /// Observations recorded in the bucket.
+    pub samples: u32,
+    pub celsius: Aggregate,
+    pub fahrenheit: Aggregate,
+    /// Left out when none of the observations reported it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub humidity: Option<Aggregate>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wind_kph: Option<Aggregate>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wind_mph: Option<Aggregate>,
+}
+
+/// Response of service-d's `GET /weather/history`. Buckets without
+/// observations are left out.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct WeatherHistoryResponse {
+    /// The provider's query the observations were recorded under.
+    pub location: String,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub interval: HistoryInterval,
+    pub buckets: Vec<WeatherHistoryBucket>,
+}
@@ -0,0 +1,53 @@
+//! Shapes that replaced a v1 one. Only the types that changed live here;
+//! everything else is still served from v1.
+pub mod service_d;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::round_trip;
+    use chrono::{TimeZone, Utc};
+    use std::collections::BTreeMap;
+    use utoipa::PartialSchema;
+
+    fn snapshot<T: PartialSchema>(name: &str) {
+        crate::testing::snapshot::<T>("v2", name);
+    }
+
+    #[test]
+    fn round_trips_wire_format() {
+        round_trip(
+            service_d::WeatherResponse {
+                city: String::from("Roanoke"),
+                state: String::from("Texas"),
+                language: String::from("de"),
+                celsius: Some(20.0),
+                celcius: Some(20.0),
+                wind_kph: Some(14.4),
+                condition: Some(String::from("Teilweise bewölkt")),
+                observed_at: Some(Utc.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap()),
+                labels: BTreeMap::from([
+                    (String::from("celsius"), String::from("°C")),
+                    (String::from("wind_kph"), String::from("km/h")),
+                ]),
+                ..Default::default()
+            },
+            r#"{
+                "city": "Roanoke",
+                "state": "Texas",
+                "language": "de",
+                "celsius": 20.0,
+                "celcius": 20.0,
+                "wind_kph": 14.4,
+                "condition": "Teilweise bewölkt",
+                "observed_at": "2024-06-01T12:00:00Z",
+                "labels": {"celsius": "°C", "wind_kph": "km/h"}
+            }"#,
+        );
+    }
+
+    #[test]
+    fn schemas_match_snapshots() {
+        snapshot::<service_d::WeatherResponse>("service_d.WeatherResponse");
+    }
+}
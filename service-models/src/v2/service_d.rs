@@ -0,0 +1,75 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+pub use crate::v1::service_d::Units;
+
+/// Response of service-d's `GET /v2/weather`. Temperatures are spelled
+/// correctly; the v1 spellings are sent alongside them until consumers have
+/// moved over. Fields `units` leaves out, or the provider did not report, are
+/// not sent.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, ToSchema)]
+#[schema(as = v2::WeatherResponse)]
+pub struct WeatherResponse {
+    pub city: String,
+    pub state: String,
+    /// Language of `condition` and `labels`, negotiated from
+    /// `Accept-Language`.
+    pub language: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub celsius: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fahrenheit: Option<f64>,
+    /// Same as `celsius`, kept from v1.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub celcius: Option<f64>,
+    /// Same as `fahrenheit`, kept from v1.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub farenheight: Option<f64>,
+    /// Percent, 0 to 100.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub humidity: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wind_kph: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wind_mph: Option<f64>,
+    /// Compass degrees the wind blows from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wind_degree: Option<u16>,
+    /// 16 point compass direction, e.g. `SSW`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wind_direction: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub feels_like_celsius: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub feels_like_fahrenheit: Option<f64>,
+    /// The provider's condition code, stable across languages.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub condition_code: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub condition: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uv_index: Option<f64>,
+    /// When the provider last updated the conditions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub observed_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latitude: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub longitude: Option<f64>,
+    /// The unit of each field sent that has one, e.g. `"wind_kph": "km/h"`.
+    #[serde(default)]
+    pub labels: BTreeMap<String, String>,
+}
+
+/// Query parameters of service-d's `GET /v2/weather`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct WeatherQuery {
+    /// A US ZIP or ZIP+4, a postal code, `city, state` or `lat,lon`.
+    pub zip: Option<String>,
+    /// Leaves out the other system's fields. Both when not given.
+    pub units: Option<Units>,
+}
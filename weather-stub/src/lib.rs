@@ -0,0 +1,390 @@
+//! A stand-in for the external weather provider service-d calls. Serves
+//! `/current.json`, `/forecast.json` and `/alerts.json` from a fixtures file
+//! and can be told to misbehave.
+use std::{
+    collections::HashMap,
+    io,
+    path::Path,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use axum::{
+    extract::{self, Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, put},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+const BUNDLED_FIXTURES: &str = include_str!("../fixtures.json");
+
+/// What to do to a request instead of, or before, answering it.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Fault {
+    #[serde(default)]
+    pub latency_ms: u64,
+    #[serde(default)]
+    pub status: Option<u16>,
+    #[serde(default)]
+    pub malformed: bool,
+}
+
+/// Provider responses keyed by zip, plus faults pinned to particular zips.
+/// `forecasts` holds the provider's `forecastday` entries for a location and
+/// `alerts` its active `alert` entries.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Fixtures {
+    #[serde(default)]
+    pub locations: HashMap<String, Value>,
+    #[serde(default)]
+    pub forecasts: HashMap<String, Vec<Value>>,
+    #[serde(default)]
+    pub alerts: HashMap<String, Vec<Value>>,
+    #[serde(default)]
+    pub faults: HashMap<String, Fault>,
+}
+
+impl Fixtures {
+    /// The fixtures compiled into the crate from `fixtures.json`.
+    pub fn bundled() -> Fixtures {
+        serde_json::from_str(BUNDLED_FIXTURES).expect("bundled fixtures are valid")
+    }
+
+    pub fn load(path: &Path) -> io::Result<Fixtures> {
+        let contents = std::fs::read_to_string(path)?;
+        serde_json::from_str(&contents).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Stub {
+    fixtures: Arc<Fixtures>,
+    api_key: Option<String>,
+    fault: Arc<RwLock<Option<Fault>>>,
+    alerts: Arc<RwLock<HashMap<String, Vec<Value>>>>,
+}
+
+impl Stub {
+    /// Requests must carry `api_key` when one is given, otherwise any key is
+    /// accepted.
+    pub fn new(fixtures: Fixtures, api_key: Option<String>) -> Stub {
+        Stub {
+            alerts: Arc::new(RwLock::new(fixtures.alerts.clone())),
+            fixtures: Arc::new(fixtures),
+            api_key,
+            fault: Arc::new(RwLock::new(None)),
+        }
+    }
+
+    /// Replaces the active alerts for `zip`, overriding the fixture's.
+    pub fn set_alerts(&self, zip: &str, alerts: Vec<Value>) {
+        self.alerts.write().unwrap().insert(zip.to_string(), alerts);
+    }
+
+    /// Applies `fault` to every request until it is cleared.
+    pub fn set_fault(&self, fault: Option<Fault>) {
+        *self.fault.write().unwrap() = fault;
+    }
+
+    fn fault_for(&self, zip: &str) -> Option<Fault> {
+        self.fault
+            .read()
+            .unwrap()
+            .clone()
+            .or_else(|| self.fixtures.faults.get(zip).cloned())
+    }
+}
+
+/// `/current.json`, `/forecast.json` and `/alerts.json` as the provider
+/// serves them, `/_stub/fault` to read, set (`PUT`) or clear (`DELETE`) a
+/// fault applied to every request, and `/_stub/alerts/:zip` to replace
+/// (`PUT`) a location's active alerts.
+pub fn app(stub: Stub) -> Router {
+    Router::new()
+        .route("/current.json", get(current))
+        .route("/forecast.json", get(forecast))
+        .route("/alerts.json", get(alerts))
+        .route(
+            "/_stub/fault",
+            get(get_fault).put(put_fault).delete(delete_fault),
+        )
+        .route("/_stub/alerts/:zip", put(put_alerts))
+        .with_state(stub)
+}
+
+fn error(status: StatusCode, code: u32, message: &str) -> Response {
+    (
+        status,
+        Json(json!({"error": {"code": code, "message": message}})),
+    )
+        .into_response()
+}
+
+/// Checks the key and applies any fault, returning the response to send
+/// instead of the fixture.
+async fn misbehave(stub: &Stub, params: &HashMap<String, String>) -> Option<Response> {
+    match (params.get("key"), &stub.api_key) {
+        (None, _) => {
+            return Some(error(
+                StatusCode::UNAUTHORIZED,
+                1002,
+                "API key is invalid or not provided.",
+            ))
+        }
+        (Some(key), Some(expected)) if key != expected => {
+            return Some(error(StatusCode::UNAUTHORIZED, 2006, "API key is invalid."))
+        }
+        _ => {}
+    }
+    let zip = params.get("q").map(String::as_str).unwrap_or_default();
+
+    if let Some(fault) = stub.fault_for(zip) {
+        tracing::info!("Injecting {:?} for {}", fault, zip);
+        if fault.latency_ms > 0 {
+            tokio::time::sleep(Duration::from_millis(fault.latency_ms)).await;
+        }
+        if let Some(status) = fault.status {
+            let status = StatusCode::from_u16(status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            return Some(error(status, 9999, "Injected fault."));
+        }
+        if fault.malformed {
+            return Some(
+                (
+                    [(header::CONTENT_TYPE, "application/json")],
+                    r#"{"location": {"name": "#,
+                )
+                    .into_response(),
+            );
+        }
+    }
+    None
+}
+
+async fn current(
+    State(stub): State<Stub>,
+    Query(params): Query<HashMap<String, String>>,
+) -> Response {
+    if let Some(response) = misbehave(&stub, &params).await {
+        return response;
+    }
+    let zip = params.get("q").map(String::as_str).unwrap_or_default();
+    match stub.fixtures.locations.get(zip) {
+        Some(body) => Json(body.clone()).into_response(),
+        None => error(StatusCode::BAD_REQUEST, 1006, "No matching location found."),
+    }
+}
+
+/// Like the provider, `days` defaults to 1. Asking for more days than the
+/// fixture holds returns what there is.
+async fn forecast(
+    State(stub): State<Stub>,
+    Query(params): Query<HashMap<String, String>>,
+) -> Response {
+    if let Some(response) = misbehave(&stub, &params).await {
+        return response;
+    }
+    let zip = params.get("q").map(String::as_str).unwrap_or_default();
+    let days: usize = params.get("days").and_then(|d| d.parse().ok()).unwrap_or(1);
+    match stub.fixtures.locations.get(zip) {
+        Some(body) => {
+            let forecast = stub
+                .fixtures
+                .forecasts
+                .get(zip)
+                .cloned()
+                .unwrap_or_default();
+            let mut body = body.clone();
+            body["forecast"] = json!({
+                "forecastday": forecast.into_iter().take(days).collect::<Vec<_>>()
+            });
+            Json(body).into_response()
+        }
+        None => error(StatusCode::BAD_REQUEST, 1006, "No matching location found."),
+    }
+}
+
+/// Locations without alerts answer with an empty `alert` list, like the
+/// provider.
+async fn alerts(
+    State(stub): State<Stub>,
+    Query(params): Query<HashMap<String, String>>,
+) -> Response {
+    if let Some(response) = misbehave(&stub, &params).await {
+        return response;
+    }
+    let zip = params.get("q").map(String::as_str).unwrap_or_default();
+    match stub.fixtures.locations.get(zip) {
+        Some(body) => {
+            let alerts = stub
+                .alerts
+                .read()
+                .unwrap()
+                .get(zip)
+                .cloned()
+                .unwrap_or_default();
+            Json(json!({
+                "location": body["location"],
+                "alerts": {"alert": alerts}
+            }))
+            .into_response()
+        }
+        None => error(StatusCode::BAD_REQUEST, 1006, "No matching location found."),
+    }
+}
+
+async fn put_alerts(
+    State(stub): State<Stub>,
+    extract::Path(zip): extract::Path<String>,
+    Json(alerts): Json<Vec<Value>>,
+) -> StatusCode {
+    stub.set_alerts(&zip, alerts);
+    StatusCode::NO_CONTENT
+}
+
+async fn get_fault(State(stub): State<Stub>) -> Json<Option<Fault>> {
+    Json(stub.fault.read().unwrap().clone())
+}
+
+async fn put_fault(State(stub): State<Stub>, Json(fault): Json<Fault>) -> StatusCode {
+    stub.set_fault(Some(fault));
+    StatusCode::NO_CONTENT
+}
+
+async fn delete_fault(State(stub): State<Stub>) -> StatusCode {
+    stub.set_fault(None);
+    StatusCode::NO_CONTENT
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        body::{to_bytes, Body},
+        http::Request,
+    };
+    use tower::ServiceExt;
+
+    fn router() -> Router {
+        app(Stub::new(Fixtures::bundled(), Some(String::from("test"))))
+    }
+
+    async fn call(router: Router, request: Request<Body>) -> (StatusCode, String) {
+        let response = router.oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, String::from_utf8(bytes.to_vec()).unwrap())
+    }
+
+    async fn current(router: Router, zip: &str) -> (StatusCode, String) {
+        let uri = format!("/current.json?q={}&key=test", zip);
+        call(router, Request::get(uri).body(Body::empty()).unwrap()).await
+    }
+
+    #[tokio::test]
+    async fn serves_fixtures_by_zip() {
+        let (status, body) = current(router(), "76262").await;
+        assert_eq!(StatusCode::OK, status);
+        let body: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!("Roanoke", body["location"]["name"]);
+        assert_eq!(68.0, body["current"]["temp_f"]);
+
+        let (status, body) = current(router(), "00000").await;
+        assert_eq!(StatusCode::BAD_REQUEST, status);
+        assert!(body.contains("1006"));
+    }
+
+    #[tokio::test]
+    async fn serves_forecasts_for_the_requested_days() {
+        let uri = "/forecast.json?q=76262&days=2&key=test";
+        let (status, body) = call(router(), Request::get(uri).body(Body::empty()).unwrap()).await;
+        assert_eq!(StatusCode::OK, status);
+        let body: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!("Roanoke", body["location"]["name"]);
+        let days = body["forecast"]["forecastday"].as_array().unwrap();
+        assert_eq!(2, days.len());
+        assert_eq!("2024-06-01", days[0]["date"]);
+        assert_eq!(31.0, days[0]["day"]["maxtemp_c"]);
+
+        let uri = "/forecast.json?q=50300&key=test";
+        let request = Request::get(uri).body(Body::empty()).unwrap();
+        assert_eq!(
+            StatusCode::SERVICE_UNAVAILABLE,
+            call(router(), request).await.0
+        );
+    }
+
+    #[tokio::test]
+    async fn serves_alerts_that_can_be_replaced_at_runtime() {
+        let router = router();
+        let alerts = |router: Router| async move {
+            let uri = "/alerts.json?q=33101&key=test";
+            let (status, body) = call(router, Request::get(uri).body(Body::empty()).unwrap()).await;
+            assert_eq!(StatusCode::OK, status);
+            let body: Value = serde_json::from_str(&body).unwrap();
+            assert_eq!("Miami", body["location"]["name"]);
+            body["alerts"]["alert"].as_array().unwrap().clone()
+        };
+        let bundled = alerts(router.clone()).await;
+        assert_eq!(1, bundled.len());
+        assert_eq!("Tropical Storm Warning", bundled[0]["event"]);
+
+        let request = Request::put("/_stub/alerts/33101")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from("[]"))
+            .unwrap();
+        assert_eq!(
+            StatusCode::NO_CONTENT,
+            call(router.clone(), request).await.0
+        );
+        assert!(alerts(router).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn rejects_the_wrong_key() {
+        let request = Request::get("/current.json?q=76262&key=nope")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(StatusCode::UNAUTHORIZED, call(router(), request).await.0);
+
+        let request = Request::get("/current.json?q=76262")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(StatusCode::UNAUTHORIZED, call(router(), request).await.0);
+    }
+
+    #[tokio::test]
+    async fn applies_faults_pinned_to_a_zip() {
+        assert_eq!(
+            StatusCode::SERVICE_UNAVAILABLE,
+            current(router(), "50300").await.0
+        );
+
+        let (status, body) = current(router(), "99999").await;
+        assert_eq!(StatusCode::OK, status);
+        assert!(serde_json::from_str::<Value>(&body).is_err());
+    }
+
+    #[tokio::test]
+    async fn faults_can_be_set_and_cleared_at_runtime() {
+        let router = router();
+        let request = Request::put("/_stub/fault")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(r#"{"status": 502, "latency_ms": 10}"#))
+            .unwrap();
+        assert_eq!(
+            StatusCode::NO_CONTENT,
+            call(router.clone(), request).await.0
+        );
+        assert_eq!(
+            StatusCode::BAD_GATEWAY,
+            current(router.clone(), "76262").await.0
+        );
+
+        let request = Request::delete("/_stub/fault").body(Body::empty()).unwrap();
+        call(router.clone(), request).await;
+        assert_eq!(StatusCode::OK, current(router, "76262").await.0);
+    }
+}
@@ -0,0 +1,32 @@
+use std::path::PathBuf;
+
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, Registry};
+use weather_stub::{app, Fixtures, Stub};
+
+#[tokio::main]
+async fn main() {
+    let fmt_layer = tracing_subscriber::fmt::layer()
+        .with_target(false)
+        .without_time();
+    Registry::default()
+        .with(fmt_layer)
+        .with(tracing_subscriber::EnvFilter::from_default_env())
+        .init();
+
+    let bind_address = std::env::var("BIND_ADDRESS").expect("BIND_ADDRESS is required");
+    let fixtures = match std::env::var("STUB_FIXTURES_PATH") {
+        Ok(path) => {
+            let path = PathBuf::from(path);
+            Fixtures::load(&path)
+                .unwrap_or_else(|e| panic!("error reading {}: {}", path.display(), e))
+        }
+        Err(_) => Fixtures::bundled(),
+    };
+    let stub = Stub::new(fixtures, std::env::var("WEATHER_API_KEY").ok());
+
+    let listener = tokio::net::TcpListener::bind(bind_address.clone())
+        .await
+        .unwrap();
+    tracing::info!("Up and running ... listening on {}", bind_address);
+    axum::serve(listener, app(stub)).await.unwrap();
+}